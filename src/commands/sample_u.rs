@@ -0,0 +1,33 @@
+use std::path::Path;
+use crate::services::random_sampling::sample_from_data;
+use crate::services::similarity_config::CONFIG;
+
+/// The default seed, so that runs without a seed are reproducible too.
+pub const SEED: u64 = 0;
+
+/// Command "sample-u OUTPUT PAIRS [SEED]" which estimates Fellegi–Sunter
+/// u-probabilities from random pairs of persons in our DATA.
+///
+/// The comparators and other parameters come from the default profile of the config.
+/// The parameters with the estimated u-probabilities are written to OUTPUT,
+/// as TOML or JSON by the file extension, which a profile can load via
+/// `fellegi_sunter_path`. The comparison level frequencies are printed as JSON.
+///
+pub fn sample_u(args: &[String]) -> Result<(), String> {
+    let usage = "usage: sample-u OUTPUT PAIRS [SEED]";
+    let output = args.first().ok_or(usage)?;
+    let pairs = args.get(1).ok_or(usage)?;
+    let pairs: usize = pairs.parse().map_err(|e| format!("PAIRS {:?}: {}", pairs, e))?;
+    let seed: u64 = match args.get(2) {
+        Some(seed) => seed.parse().map_err(|e| format!("SEED {:?}: {}", seed, e))?,
+        None => SEED,
+    };
+    let profile = {
+        let config = CONFIG.read().unwrap();
+        config.profile(None).unwrap().clone()
+    };
+    let sample = sample_from_data(pairs, seed, &profile);
+    sample.apply(&profile.fellegi_sunter).to_path(Path::new(output)).map_err(|e| e.to_string())?;
+    println!("{}", serde_json::to_string(&sample).unwrap());
+    Ok(())
+}
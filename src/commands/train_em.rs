@@ -0,0 +1,40 @@
+use std::path::Path;
+use crate::services::blocking::BlockingRule;
+use crate::services::expectation_maximisation::{EmOptions, train_from_data};
+use crate::services::similarity_config::CONFIG;
+
+/// Command "train-em OUTPUT [MAX_ITERATIONS] [BLOCKING...]" which estimates
+/// Fellegi–Sunter parameters from our DATA by expectation–maximisation.
+///
+/// Each BLOCKING is a blocking rule, "FIELD" or "FIELD:ENCODER" such as
+/// "family_name:double_metaphone"; without any, every pair is a candidate.
+///
+/// The comparators and starting parameters come from the default profile of the config.
+/// The estimated parameters are written to OUTPUT, as TOML or JSON by the
+/// file extension, which a profile can load via `fellegi_sunter_path`.
+/// The convergence diagnostics are printed as JSON.
+///
+pub fn train_em(args: &[String]) -> Result<(), String> {
+    let output = args.first().ok_or("usage: train-em OUTPUT [MAX_ITERATIONS] [BLOCKING...]")?;
+    let mut options = EmOptions::default();
+    if let Some(max_iterations) = args.get(1) {
+        options.max_iterations = max_iterations.parse().map_err(|e| format!("MAX_ITERATIONS {:?}: {}", max_iterations, e))?;
+    }
+    for rule in args.iter().skip(2) {
+        options.blocking.push(rule.parse::<BlockingRule>().map_err(|e| format!("BLOCKING {:?}: {}", rule, e))?);
+    }
+    let profile = {
+        let config = CONFIG.read().unwrap();
+        config.profile(None).unwrap().clone()
+    };
+    let result = train_from_data(&profile, &options);
+    result.parameters.to_path(Path::new(output)).map_err(|e| e.to_string())?;
+    println!("{}", serde_json::json!({
+        "output": output,
+        "pairs": result.pairs,
+        "iterations": result.iterations,
+        "converged": result.converged,
+        "log_likelihoods": result.log_likelihoods,
+    }));
+    Ok(())
+}
@@ -15,13 +15,19 @@ pub async fn get_persons() -> axum::response::Html<String> {
         let table: Vec<Vec<String>> = persons.iter().map(|person| 
             vec![
                 person.id.clone(),
+                (match &person.nhs_number { Some(x) => x.to_string(), None => "".to_string() }),
+                (match &person.title { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.given_name { Some(x) => x.to_string(), None => "".to_string() }),
+                (match &person.middle_names { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.family_name { Some(x) => x.to_string(), None => "".to_string() }),
-                (match &person.birth_date_year { Some(x) => x.to_string(), None => "".to_string() }),
-                (match &person.birth_date_month { Some(x) => x.to_string(), None => "".to_string() }),
-                (match &person.birth_date_month_day { Some(x) => x.to_string(), None => "".to_string() }),
+                (match &person.suffix { Some(x) => x.to_string(), None => "".to_string() }),
+                person.other_names.iter().map(|x| x.to_string()).collect::<Vec<String>>().join("; "),
+                (match &person.birth_date { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.primary_email { Some(x) => x.to_string(), None => "".to_string() }),
+                person.other_emails.join("; "),
                 (match &person.primary_phone { Some(x) => x.to_string(), None => "".to_string() }),
+                person.other_phones.join("; "),
+                (match &person.address { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.note { Some(x) => x.to_string(), None => "".to_string() }),
             ]
         ).collect();
@@ -44,11 +50,13 @@ pub async fn get_persons_id_form(
                 concat!(
                     "<form method=\"post\" action=\"/persons/{}/form\">\n",
                     "<input type=\"hidden\" name=\"id\" value=\"{}\">\n",
+                    "<p><input name=\"nhs_number\" value=\"{}\"></p>\n",
+                    "<p><input name=\"title\" value=\"{}\"></p>\n",
                     "<p><input name=\"given_name\" value=\"{}\"></p>\n",
+                    "<p><input name=\"middle_names\" value=\"{}\"></p>\n",
                     "<p><input name=\"family_name\" value=\"{}\"></p>\n",
-                    "<p><input name=\"birth_date_year\" value=\"{}\"></p>\n",
-                    "<p><input name=\"birth_date_month\" value=\"{}\"></p>\n",
-                    "<p><input name=\"birth_date_month_day\" value=\"{}\"></p>\n",
+                    "<p><input name=\"suffix\" value=\"{}\"></p>\n",
+                    "<p><input name=\"birth_date\" value=\"{}\"></p>\n",
                     "<p><input name=\"primary_email\" value=\"{}\"></p>\n",
                     "<p><input name=\"primary_phone\" value=\"{}\"></p>\n",
                     "<p><input name=\"note\" value=\"{}\"></p>\n",
@@ -57,11 +65,13 @@ pub async fn get_persons_id_form(
                 ),
                 &person.id, 
                 &person.id, 
+                (match &person.nhs_number { Some(x) => x.to_string(), None => "".to_string() }),
+                (match &person.title { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.given_name { Some(x) => x.to_string(), None => "".to_string() }),
+                (match &person.middle_names { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.family_name { Some(x) => x.to_string(), None => "".to_string() }),
-                (match &person.birth_date_year { Some(x) => x.to_string(), None => "".to_string() }),
-                (match &person.birth_date_month { Some(x) => x.to_string(), None => "".to_string() }),
-                (match &person.birth_date_month_day { Some(x) => x.to_string(), None => "".to_string() }),
+                (match &person.suffix { Some(x) => x.to_string(), None => "".to_string() }),
+                (match &person.birth_date { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.primary_email { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.primary_phone { Some(x) => x.to_string(), None => "".to_string() }),
                 (match &person.note { Some(x) => x.to_string(), None => "".to_string() }),
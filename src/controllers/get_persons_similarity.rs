@@ -1,9 +1,116 @@
+use serde::{Deserialize, Serialize};
 use crate::models::person::Person;
+use crate::services::similarity::*;
+use crate::services::similarity_config::CONFIG;
 
 type PersonsPair = (Person, Person);
 
+/// Query parameters for "POST /persons/similarity" and "POST /persons/similarity/batch".
+/// The profile is optional; the default is the config default profile.
+#[derive(Debug, Default, Deserialize)]
+pub struct PersonsSimilarityParams {
+    pub profile: Option<String>,
+}
+
+/// axum handler for "POST /persons/similarity" which responds with JSON.
+/// The request body is a JSON array of two persons.
+/// The response body is a similarity report with the score and each field's part in it.
+/// An unknown profile responds with HTTP status code Bad Request (400).
 pub async fn persons_similarity(
+    axum::extract::Query(params): axum::extract::Query<PersonsSimilarityParams>,
     axum::extract::Json(pair): axum::extract::Json<PersonsPair>
-) -> String{
-    format!("Get demo JSON data: {:?}", pair)
+) -> Result<axum::extract::Json<SimilarityReport>, (axum::http::StatusCode, String)> {
+    let config = CONFIG.read().unwrap();
+    match config.profile(params.profile.as_deref()) {
+        Some(profile) => Ok(axum::extract::Json(similarity_report_of_persons((&pair.0, &pair.1), profile))),
+        None => Err((axum::http::StatusCode::BAD_REQUEST, format!("No profile {:?}", params.profile.unwrap_or_default()))),
+    }
+}
+
+/// One item of the response body for "POST /persons/similarity/batch":
+/// the ids of the pair, then the similarity report of the pair.
+#[derive(Debug, Serialize)]
+pub struct PersonsSimilarityBatchItem {
+    pub ids: (String, String),
+    #[serde(flatten)]
+    pub report: SimilarityReport,
+}
+
+/// axum handler for "POST /persons/similarity/batch" which responds with JSON.
+/// The request body is a JSON array of pairs, each a JSON array of two persons.
+/// The response body is a JSON array of similarity reports, in the same order,
+/// each with its match decision.
+/// An unknown profile responds with HTTP status code Bad Request (400).
+pub async fn persons_similarity_batch(
+    axum::extract::Query(params): axum::extract::Query<PersonsSimilarityParams>,
+    axum::extract::Json(pairs): axum::extract::Json<Vec<PersonsPair>>
+) -> Result<axum::extract::Json<Vec<PersonsSimilarityBatchItem>>, (axum::http::StatusCode, String)> {
+    let config = CONFIG.read().unwrap();
+    match config.profile(params.profile.as_deref()) {
+        Some(profile) => Ok(axum::extract::Json(pairs.iter().map(|pair|
+            PersonsSimilarityBatchItem {
+                ids: (pair.0.id.clone(), pair.1.id.clone()),
+                report: similarity_report_of_persons((&pair.0, &pair.1), profile),
+            }
+        ).collect())),
+        None => Err((axum::http::StatusCode::BAD_REQUEST, format!("No profile {:?}", params.profile.unwrap_or_default()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pair() -> PersonsPair {
+        serde_json::from_str(r#"[
+            {"id": "0", "given_name": "Alice", "family_name": "Adams"},
+            {"id": "1", "given_name": "Alice", "family_name": "Adams", "primary_phone": "3787581685"}
+        ]"#).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_persons_similarity() {
+        let axum::extract::Json(response) = persons_similarity(
+            axum::extract::Query(PersonsSimilarityParams::default()),
+            axum::extract::Json(pair()),
+        ).await.unwrap();
+        assert_eq!(response.profile, "default");
+        assert_eq!(response.decision, MatchDecision::NonMatch);
+        assert_eq!(response.fields[0].field, Field::GivenName);
+        assert_eq!(response.fields[0].value, Some(1.0));
+        assert_eq!(response.fields[0].weight, GIVEN_NAME_EQ);
+        assert_eq!(response.fields[1].value, Some(1.0));
+        assert!(!response.fields[5].present);
+        assert_eq!(response.score, (GIVEN_NAME_EQ + FAMILY_NAME_EQ) / ((GIVEN_NAME_EQ + FAMILY_NAME_EQ + SIMILARITY_MAX) / 2.0));
+    }
+
+    #[tokio::test]
+    async fn test_persons_similarity_unknown_profile() {
+        let (status, _) = persons_similarity(
+            axum::extract::Query(PersonsSimilarityParams { profile: Some(String::from("missing")) }),
+            axum::extract::Json(pair()),
+        ).await.unwrap_err();
+        assert_eq!(status, axum::http::StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_persons_similarity_batch() {
+        let a = pair();
+        let b: Person = serde_json::from_str(r#"
+            {"id": "2", "given_name": "Alice", "family_name": "Adams", "birth_date": "1999", "primary_email": "alice@example.com"}
+        "#).unwrap();
+        let b = (b.clone(), b);
+        let axum::extract::Json(response) = persons_similarity_batch(
+            axum::extract::Query(PersonsSimilarityParams::default()),
+            axum::extract::Json(vec![a, b]),
+        ).await.unwrap();
+        assert_eq!(response.len(), 2);
+        assert_eq!(response[0].ids, (String::from("0"), String::from("1")));
+        assert_eq!(response[0].report.decision, MatchDecision::NonMatch);
+        assert_eq!(response[1].report.decision, MatchDecision::PossibleMatch);
+        assert_eq!(response[1].ids, (String::from("2"), String::from("2")));
+        let json = serde_json::to_value(&response[1]).unwrap();
+        assert_eq!(json["decision"], "possible_match");
+    }
+
 }
@@ -0,0 +1,80 @@
+use std::thread;
+use serde::Deserialize;
+use crate::data::{DATA, data_changed};
+use crate::models::person::Person;
+use crate::services::name_parser::parse_person_names;
+
+/// Query parameters for "POST /persons".
+/// When `parse_names` is true, each person's name fields are parsed into
+/// title, given name, middle names, family name, and suffix before saving.
+#[derive(Debug, Default, Deserialize)]
+pub struct PostPersonsParams {
+    #[serde(default)]
+    pub parse_names: bool,
+}
+
+/// Prepare persons for saving: check each id, then parse names if asked.
+pub fn prepare_persons(persons: Vec<Person>, parse_names: bool) -> Result<Vec<Person>, String> {
+    if let Some(i) = persons.iter().position(|person| person.id.trim().is_empty()) {
+        return Err(format!("Person {} id must not be blank", i));
+    }
+    Ok(persons.into_iter()
+        .map(|person| if parse_names { parse_person_names(&person) } else { person })
+        .collect())
+}
+
+/// axum handler for "POST /persons" which responds with JSON.
+/// The request body is a JSON array of persons, each inserted or replaced by id.
+/// The response body is a JSON array of the saved persons, in the same order.
+/// A blank id responds with HTTP status code Bad Request (400).
+pub async fn post_persons(
+    axum::extract::Query(params): axum::extract::Query<PostPersonsParams>,
+    axum::extract::Json(persons): axum::extract::Json<Vec<Person>>
+) -> Result<axum::extract::Json<Vec<Person>>, (axum::http::StatusCode, String)> {
+    let persons = prepare_persons(persons, params.parse_names)
+        .map_err(|e| (axum::http::StatusCode::BAD_REQUEST, e))?;
+    let saved = persons.clone();
+    thread::spawn(move || {
+        let mut data = DATA.lock().unwrap();
+        for person in persons {
+            data.insert(person.id.clone(), person);
+        }
+    })
+    .join()
+    .unwrap();
+    data_changed();
+    Ok(axum::extract::Json(saved))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn persons() -> Vec<Person> {
+        serde_json::from_str(r#"[
+            {"id": "0", "given_name": "Dr. Alice M. Adams Jr"},
+            {"id": "1", "given_name": "Bob", "family_name": "Brown"}
+        ]"#).unwrap()
+    }
+
+    #[test]
+    fn test_prepare_persons() {
+        let prepared = prepare_persons(persons(), false).unwrap();
+        assert_eq!(prepared, persons());
+        let prepared = prepare_persons(persons(), true).unwrap();
+        assert_eq!(prepared[0].title.as_deref(), Some("Dr."));
+        assert_eq!(prepared[0].given_name.as_deref(), Some("Alice"));
+        assert_eq!(prepared[0].middle_names.as_deref(), Some("M."));
+        assert_eq!(prepared[0].family_name.as_deref(), Some("Adams"));
+        assert_eq!(prepared[0].suffix.as_deref(), Some("Jr"));
+        assert_eq!(prepared[1], persons()[1]);
+    }
+
+    #[test]
+    fn test_prepare_persons_blank_id() {
+        let mut persons = persons();
+        persons[1].id = String::from(" ");
+        assert!(prepare_persons(persons, true).is_err());
+    }
+
+}
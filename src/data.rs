@@ -4,6 +4,9 @@ use std::sync::LazyLock;
 // Use Mutex for thread-safe access to a variable e.g. our DATA.
 use std::sync::Mutex;
 
+// Use AtomicU64 for counting changes to our DATA without a lock.
+use std::sync::atomic::{AtomicU64, Ordering};
+
 // Use HashMap for storing data as key-value pairs e.g. our DATA.
 use std::collections::HashMap;
 
@@ -36,13 +39,19 @@ pub static DATA: LazyLock<Mutex<HashMap<String, Person>>> = LazyLock::new(|| {
             String::from("cc1143129505d87f5f0a044b7dbef236"),
             Person {
                 id: String::from("cc1143129505d87f5f0a044b7dbef236"),
+                nhs_number: None,
+                title: None,
                 given_name: Some(String::from("Alice")), 
+                middle_names: None,
                 family_name: Some(String::from("Adams")),
-                birth_date_year: None,
-                birth_date_month: None,
-                birth_date_month_day: None,
+                suffix: None,
+                other_names: Vec::new(),
+                birth_date: None,
                 primary_email: Some(String::from("alice.adams@example.com")),
+                other_emails: Vec::new(),
                 primary_phone: Some(String::from("3787581685")),
+                other_phones: Vec::new(),
+                address: None,
                 note: Some(String::from("Lorem ipsum dolor sit amet, consectetur adipiscing elit, sed do eiusmod tempor incididunt ut labore et dolore magna aliqua.")),
             },
         ),
@@ -50,13 +59,19 @@ pub static DATA: LazyLock<Mutex<HashMap<String, Person>>> = LazyLock::new(|| {
             String::from("925561d3c5d097b690d029ef03d08721"),
             Person { 
                 id: String::from("925561d3c5d097b690d029ef03d08721"),
+                nhs_number: None,
+                title: None,
                 given_name: Some(String::from("Bob")), 
+                middle_names: None,
                 family_name: Some(String::from("Brown")),
-                birth_date_year: None,
-                birth_date_month: None,
-                birth_date_month_day: None,
+                suffix: None,
+                other_names: Vec::new(),
+                birth_date: None,
                 primary_email: Some(String::from("bob.brown@example.com")),
+                other_emails: Vec::new(),
                 primary_phone: Some(String::from("7792181073")),
+                other_phones: Vec::new(),
+                address: None,
                 note: Some(String::from("Vitae suscipit tellus mauris a diam maecenas sed. Nunc sed velit dignissim sodales ut eu sem integer vitae.")),
             },
         ),
@@ -64,13 +79,19 @@ pub static DATA: LazyLock<Mutex<HashMap<String, Person>>> = LazyLock::new(|| {
             String::from("ebb7f695a7301810fcd17efff78f222d"),
             Person { 
                 id: String::from("ebb7f695a7301810fcd17efff78f222d"),
+                nhs_number: None,
+                title: None,
                 given_name: Some(String::from("Carol")), 
+                middle_names: None,
                 family_name: Some(String::from("Clark")),
-                birth_date_year: None,
-                birth_date_month: None,
-                birth_date_month_day: None,
+                suffix: None,
+                other_names: Vec::new(),
+                birth_date: None,
                 primary_email: Some(String::from("carol.clark@example.com")),
+                other_emails: Vec::new(),
                 primary_phone: Some(String::from("6955100299")),
+                other_phones: Vec::new(),
+                address: None,
                 note: Some(String::from("Blandit libero volutpat sed cras. A cras semper auctor neque vitae tempus quam pellentesque.")),
             },
         ),
@@ -78,13 +99,19 @@ pub static DATA: LazyLock<Mutex<HashMap<String, Person>>> = LazyLock::new(|| {
             String::from("d8913a341ff72be5a6716d90c46a29da"),
             Person { 
                 id: String::from("d8913a341ff72be5a6716d90c46a29da"),
+                nhs_number: None,
+                title: None,
                 given_name: Some(String::from("David")), 
+                middle_names: None,
                 family_name: Some(String::from("Davis")),
-                birth_date_year: None,
-                birth_date_month: None,
-                birth_date_month_day: None,
+                suffix: None,
+                other_names: Vec::new(),
+                birth_date: None,
                 primary_email: Some(String::from("david.davis@example.com")),
+                other_emails: Vec::new(),
                 primary_phone: Some(String::from("9995622828")),
+                other_phones: Vec::new(),
+                address: None,
                 note: Some(String::from("Quis eleifend quam adipiscing vitae. Quisque non tellus orci ac auctor augue mauris augue neque. Lacinia quis vel eros donec.")),
             },
         ),
@@ -92,13 +119,19 @@ pub static DATA: LazyLock<Mutex<HashMap<String, Person>>> = LazyLock::new(|| {
             String::from("6eeb89a7967a5f08851290092f9e3c2a"),
             Person { 
                 id: String::from("6eeb89a7967a5f08851290092f9e3c2a"),
+                nhs_number: None,
+                title: None,
                 given_name: Some(String::from("Eve")), 
+                middle_names: None,
                 family_name: Some(String::from("Evans")),
-                birth_date_year: None,
-                birth_date_month: None,
-                birth_date_month_day: None,
+                suffix: None,
+                other_names: Vec::new(),
+                birth_date: None,
                 primary_email: Some(String::from("eve.evans@example.com")),
+                other_emails: Vec::new(),
                 primary_phone: Some(String::from("8187236185")),
+                other_phones: Vec::new(),
+                address: None,
                 note: Some(String::from("Lacus suspendisse faucibus interdum posuere. Malesuada fames ac turpis egestas maecenas. Adipiscing tristique risus nec feugiat.")),
             },
         ),
@@ -106,15 +139,34 @@ pub static DATA: LazyLock<Mutex<HashMap<String, Person>>> = LazyLock::new(|| {
             String::from("acefb313c39ca3cdeea597e08204cc0f"),
             Person { 
                 id: String::from("acefb313c39ca3cdeea597e08204cc0f"),
+                nhs_number: None,
+                title: None,
                 given_name: Some(String::from("Frank")), 
+                middle_names: None,
                 family_name: Some(String::from("Franklin")),
-                birth_date_year: None,
-                birth_date_month: None,
-                birth_date_month_day: None,
+                suffix: None,
+                other_names: Vec::new(),
+                birth_date: None,
                 primary_email: Some(String::from("frank.franklin@example.com")),
+                other_emails: Vec::new(),
                 primary_phone: Some(String::from("9104733641")),
+                other_phones: Vec::new(),
+                address: None,
                 note: Some(String::from("Etiam ut feugiat nibh. Suspendisse at scelerisque lectus, ut rutrum purus. Nulla non mattis mauris. In gravida risus in ipsum venenatis feugiat quis luctus dui.")),
             },
         ),
     ]))
 });
+
+// Count changes to our DATA, so caches that are built from it, such as the
+// term frequency tables, know when to build again.
+//
+// Call `data_changed` after every insert, update, or remove, once the DATA
+// lock is released.
+
+pub static DATA_GENERATION: AtomicU64 = AtomicU64::new(0);
+
+/// Record that our DATA has changed.
+pub fn data_changed() {
+    DATA_GENERATION.fetch_add(1, Ordering::Release);
+}
@@ -1,4 +1,4 @@
-use axum::routing::get;
+use axum::routing::{get, post};
 
 mod fallback;
 use fallback::fallback;
@@ -10,6 +10,10 @@ pub mod data;
 
 pub mod models {
     pub mod person;
+    pub mod partial_date;
+    pub mod nhs_number;
+    pub mod other_name;
+    pub mod address;
 }
 
 pub mod views {
@@ -19,14 +23,75 @@ pub mod views {
 pub mod controllers {
     pub mod get_persons;
     pub mod get_persons_similarity;
+    pub mod post_persons;
 }
 
 pub mod services {
     pub mod similarity;
+    pub mod similarity_config;
+    pub mod comparators;
+    pub mod fellegi_sunter;
+    pub mod expectation_maximisation;
+    pub mod random_sampling;
+    pub mod phonetic;
+    pub mod blocking;
+    pub mod welsh;
+    pub mod nicknames;
+    pub mod normalization;
+    pub mod birth_date;
+    pub mod name_swap;
+    pub mod email;
+    pub mod phone;
+    pub mod nhs_number;
+    pub mod term_frequency;
+    pub mod name_parser;
+    pub mod postcode;
+    pub mod place_names;
+}
+
+pub mod commands {
+    pub mod train_em;
+    pub mod sample_u;
 }
 
 #[tokio::main]
 pub async fn main() {
+    // Load the nicknames CSV file, if any, and fail fast if it is invalid.
+    if let Ok(path) = std::env::var("NICKNAMES_CSV") {
+        crate::services::nicknames::NICKNAMES.write().unwrap()
+            .extend_from_path(std::path::Path::new(&path))
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    // Load the reference term frequencies CSV file, if any, and fail fast if it is invalid.
+    if let Ok(path) = std::env::var("TERM_FREQUENCIES_CSV") {
+        crate::services::term_frequency::TERM_FREQUENCIES.write().unwrap()
+            .extend_reference_from_path(std::path::Path::new(&path))
+            .unwrap_or_else(|e| panic!("{}", e));
+    }
+
+    // Load the similarity config file, if any, and fail fast if it is invalid.
+    if let Ok(path) = std::env::var("SIMILARITY_CONFIG") {
+        let config = crate::services::similarity_config::SimilarityConfig::from_path(std::path::Path::new(&path))
+            .unwrap_or_else(|e| panic!("{}: {}", path, e));
+        *crate::services::similarity_config::CONFIG.write().unwrap() = config;
+    }
+
+    // Run a command instead of the server, when the first argument names one.
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    if let Some(command) = args.first() {
+        let result = match command.as_str() {
+            "train-em" => crate::commands::train_em::train_em(&args[1..]),
+            "sample-u" => crate::commands::sample_u::sample_u(&args[1..]),
+            _ => Err(format!("unknown command {:?}; expected train-em or sample-u", command)),
+        };
+        if let Err(e) = result {
+            eprintln!("{}", e);
+            std::process::exit(1);
+        }
+        return;
+    }
+
      // Build our application by creating our router.
     let app = axum::Router::new()
         .fallback(
@@ -34,6 +99,13 @@ pub async fn main() {
         )
         .route("/persons",
             get(crate::controllers::get_persons::get_persons)
+            .post(crate::controllers::post_persons::post_persons)
+        )
+        .route("/persons/similarity",
+            post(crate::controllers::get_persons_similarity::persons_similarity)
+        )
+        .route("/persons/similarity/batch",
+            post(crate::controllers::get_persons_similarity::persons_similarity_batch)
         );
 
 
@@ -0,0 +1,84 @@
+/// Use Serialize and Deserialize to convert to and from request JSON.
+use serde::{Deserialize, Serialize};
+
+/// A postal address: the lines, such as the house and street, then the town,
+/// the county, and the postcode.
+///
+/// ```json
+/// {"lines": ["1 Heol y Frenhines"], "town": "Caerdydd", "postcode": "CF10 1AA"}
+/// ```
+///
+/// Similarity compares the lines by tokens, the town with Welsh and English
+/// place names as equivalents, and the postcode by level; the county is not
+/// compared, because the postcode area says more.
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct Address {
+    #[serde(default)]
+    pub lines: Vec<String>,
+    pub town: Option<String>,
+    pub county: Option<String>,
+    pub postcode: Option<String>,
+}
+
+impl Address {
+    /// Get a copy of the address with blank lines removed,
+    /// and each other blank text field as `None`.
+    pub fn without_blanks(&self) -> Address {
+        let text = |x: &Option<String>| x.clone().filter(|x| !x.trim().is_empty());
+        Address {
+            lines: self.lines.iter().filter(|x| !x.trim().is_empty()).cloned().collect(),
+            town: text(&self.town),
+            county: text(&self.county),
+            postcode: text(&self.postcode),
+        }
+    }
+
+    /// True when the address has no lines and no other fields.
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty() && self.town.is_none() && self.county.is_none() && self.postcode.is_none()
+    }
+}
+
+// Display the address on one line, such as "1 Heol y Frenhines, Caerdydd, CF10 1AA".
+impl std::fmt::Display for Address {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let parts: Vec<&str> = self.lines.iter().map(|x| x.as_str())
+            .chain([self.town.as_deref(), self.county.as_deref(), self.postcode.as_deref()].into_iter().flatten())
+            .collect();
+        write!(f, "{}", parts.join(", "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde() {
+        let address: Address = serde_json::from_str(r#"{"lines": ["1 Heol y Frenhines"], "town": "Caerdydd", "postcode": "CF10 1AA"}"#).unwrap();
+        assert_eq!(address.lines, vec![String::from("1 Heol y Frenhines")]);
+        assert_eq!(address.county, None);
+        assert_eq!(address.to_string(), "1 Heol y Frenhines, Caerdydd, CF10 1AA");
+        let address: Address = serde_json::from_str(r#"{"postcode": "SA1 1AA"}"#).unwrap();
+        assert!(address.lines.is_empty());
+        assert!(serde_json::from_str::<Address>(r#"{"city": "Cardiff"}"#).is_err());
+    }
+
+    #[test]
+    fn test_without_blanks() {
+        let address = Address {
+            lines: vec![String::from(" "), String::from("1 High Street")],
+            town: Some(String::from("")),
+            county: None,
+            postcode: Some(String::from("CF10 1AA")),
+        };
+        let address = address.without_blanks();
+        assert_eq!(address.lines, vec![String::from("1 High Street")]);
+        assert_eq!(address.town, None);
+        assert!(!address.is_empty());
+        assert!(Address { lines: vec![String::from("")], ..Address::default() }.without_blanks().is_empty());
+    }
+
+}
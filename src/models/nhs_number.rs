@@ -0,0 +1,131 @@
+/// Use Serialize and Deserialize to convert to and from strings of digits.
+use serde::{Deserialize, Serialize};
+
+/// An NHS number: ten digits, the last of which is a Modulus 11 check digit.
+///
+/// Parsing accepts the spaced form "943 476 5919" and the unspaced form
+/// "9434765919". Display uses the spaced form, and serde uses the unspaced form.
+///
+/// The range 999 000 0000 to 999 999 9999 is reserved for testing;
+/// see [NhsNumber::is_test] and [NhsNumber::parse_rejecting_test].
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct NhsNumber(String);
+
+impl NhsNumber {
+
+    /// Get the ten digits.
+    pub fn as_str(&self) -> &str {
+        &self.0
+    }
+
+    /// True when the number is in the range reserved for testing.
+    pub fn is_test(&self) -> bool {
+        self.0.starts_with("999")
+    }
+
+    /// Parse a number, and reject it if it is in the range reserved for testing.
+    pub fn parse_rejecting_test(s: &str) -> Result<Self, String> {
+        let number: NhsNumber = s.parse()?;
+        if number.is_test() {
+            return Err(format!("NHS number {:?} is in the range reserved for testing", s));
+        }
+        Ok(number)
+    }
+
+}
+
+/// Calculate the Modulus 11 check digit of the first nine digits, if any.
+///
+/// Multiply each digit by its weight, from 10 for the first digit down to 2
+/// for the ninth, then subtract the sum modulo 11 from 11. A result of 11
+/// means a check digit of 0, and a result of 10 means no number is valid.
+///
+pub fn check_digit(digits: &[u8; 9]) -> Option<u8> {
+    let sum: u32 = digits.iter().zip((2..=10).rev()).map(|(&digit, weight)| digit as u32 * weight).sum();
+    match 11 - sum % 11 {
+        11 => Some(0),
+        10 => None,
+        x => Some(x as u8),
+    }
+}
+
+impl std::str::FromStr for NhsNumber {
+    type Err = String;
+
+    /// Parse a number in the spaced form or the unspaced form.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let digits: String = s.chars().filter(|c| *c != ' ').collect();
+        if digits.len() != 10 || !digits.bytes().all(|b| b.is_ascii_digit()) {
+            return Err(format!("NHS number {:?} must have ten digits", s));
+        }
+        let values: Vec<u8> = digits.bytes().map(|b| b - b'0').collect();
+        let first: [u8; 9] = values[..9].try_into().unwrap();
+        if check_digit(&first) != Some(values[9]) {
+            return Err(format!("NHS number {:?} fails the Modulus 11 check", s));
+        }
+        Ok(NhsNumber(digits))
+    }
+}
+
+impl TryFrom<String> for NhsNumber {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<NhsNumber> for String {
+    fn from(number: NhsNumber) -> Self {
+        number.0
+    }
+}
+
+// Display the number in the spaced form, such as "943 476 5919".
+impl std::fmt::Display for NhsNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {} {}", &self.0[..3], &self.0[3..6], &self.0[6..])
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let number: NhsNumber = "943 476 5919".parse().unwrap();
+        assert_eq!(number.as_str(), "9434765919");
+        assert_eq!(number.to_string(), "943 476 5919");
+        assert_eq!("9434765919".parse::<NhsNumber>().unwrap(), number);
+        for s in ["", "943 476 591", "943 476 59190", "943-476-5919", "943 476 5918", "94347659x9"] {
+            assert!(s.parse::<NhsNumber>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_check_digit() {
+        assert_eq!(check_digit(&[9, 4, 3, 4, 7, 6, 5, 9, 1]), Some(9));
+        assert_eq!(check_digit(&[4, 0, 1, 0, 2, 3, 2, 1, 3]), Some(7));
+        assert_eq!(check_digit(&[0, 0, 0, 0, 0, 0, 0, 0, 0]), Some(0));
+        assert_eq!(check_digit(&[0, 0, 0, 0, 0, 0, 0, 0, 6]), None);
+    }
+
+    #[test]
+    fn test_test_range() {
+        let number: NhsNumber = "999 000 0018".parse().unwrap();
+        assert!(number.is_test());
+        assert!(NhsNumber::parse_rejecting_test("999 000 0018").is_err());
+        assert!(NhsNumber::parse_rejecting_test("943 476 5919").is_ok());
+    }
+
+    #[test]
+    fn test_serde() {
+        let number: NhsNumber = serde_json::from_str("\"943 476 5919\"").unwrap();
+        assert_eq!(serde_json::to_string(&number).unwrap(), "\"9434765919\"");
+        assert!(serde_json::from_str::<NhsNumber>("\"943 476 5918\"").is_err());
+    }
+
+}
@@ -0,0 +1,89 @@
+/// Use Serialize and Deserialize to convert to and from request JSON.
+use serde::{Deserialize, Serialize};
+use crate::models::partial_date::PartialDate;
+
+/// The kind of another name of a person.
+///
+/// - `Previous`: a name that the person used before, such as a family name
+///   before marriage.
+///
+/// - `Alias`: another name that the person is known by.
+///
+/// - `Preferred`: the name that the person prefers to be called,
+///   such as "Beth" for "Elizabeth".
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NameKind {
+    #[default]
+    Previous,
+    Alias,
+    Preferred,
+}
+
+impl NameKind {
+    /// Get the name of the kind, as in JSON.
+    pub fn name(&self) -> &'static str {
+        match self {
+            NameKind::Previous => "previous",
+            NameKind::Alias => "alias",
+            NameKind::Preferred => "preferred",
+        }
+    }
+}
+
+/// Another name of a person, with optional validity dates.
+///
+/// A missing given name or family name means the same as the person's
+/// current one, so a previous family name needs only the family name.
+///
+/// The validity dates record when the name was in use. Similarity compares
+/// every name whatever its dates, because records of any age can match.
+///
+/// ```json
+/// {"kind": "previous", "family_name": "Adams", "valid_to": "2015-06"}
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct OtherName {
+    #[serde(default)]
+    pub kind: NameKind,
+    pub given_name: Option<String>,
+    pub family_name: Option<String>,
+    pub valid_from: Option<PartialDate>,
+    pub valid_to: Option<PartialDate>,
+}
+
+// Display the name using a reasonable format, such as "previous: Adams (to 2015-06)".
+impl std::fmt::Display for OtherName {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        let names: Vec<&str> = [self.given_name.as_deref(), self.family_name.as_deref()].into_iter().flatten().collect();
+        write!(f, "{}: {}", self.kind.name(), names.join(" "))?;
+        match (self.valid_from, self.valid_to) {
+            (Some(from), Some(to)) => write!(f, " ({} to {})", from, to),
+            (Some(from), None) => write!(f, " (from {})", from),
+            (None, Some(to)) => write!(f, " (to {})", to),
+            (None, None) => Ok(()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_serde() {
+        let name: OtherName = serde_json::from_str(r#"{"family_name": "Adams", "valid_to": "2015-06"}"#).unwrap();
+        assert_eq!(name.kind, NameKind::Previous);
+        assert_eq!(name.family_name.as_deref(), Some("Adams"));
+        assert_eq!(name.valid_to, Some("2015-06".parse().unwrap()));
+        assert_eq!(name.to_string(), "previous: Adams (to 2015-06)");
+        let name: OtherName = serde_json::from_str(r#"{"kind": "preferred", "given_name": "Beth"}"#).unwrap();
+        assert_eq!(name.to_string(), "preferred: Beth");
+        assert!(serde_json::from_str::<OtherName>(r#"{"kind": "maiden"}"#).is_err());
+        assert!(serde_json::from_str::<OtherName>(r#"{"surname": "Adams"}"#).is_err());
+    }
+
+}
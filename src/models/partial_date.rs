@@ -0,0 +1,199 @@
+/// Use Serialize and Deserialize to convert to and from ISO 8601 strings.
+use serde::{Deserialize, Serialize};
+
+/// The precision of a partial date.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DatePrecision {
+    Year,
+    YearMonth,
+    Date,
+}
+
+/// A date that may be known only to the year, or to the year and month.
+///
+/// Every partial date is valid: the month is from 1 to 12, and the day
+/// exists in the month, such as 29 February only in a leap year.
+///
+/// Serde uses ISO 8601 strings, by precision:
+///
+/// - Year: "1999"
+/// - Year and month: "1999-12"
+/// - Date: "1999-12-31"
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(try_from = "String", into = "String")]
+pub struct PartialDate {
+    year: i32,
+    month: Option<u8>,
+    day: Option<u8>,
+}
+
+impl PartialDate {
+
+    /// Create a partial date with year precision.
+    pub fn year(year: i32) -> Result<Self, String> {
+        if !(1..=9999).contains(&year) {
+            return Err(format!("year {} must be from 1 to 9999", year));
+        }
+        Ok(PartialDate { year, month: None, day: None })
+    }
+
+    /// Create a partial date with year and month precision.
+    pub fn year_month(year: i32, month: u8) -> Result<Self, String> {
+        let date = PartialDate::year(year)?;
+        if !(1..=12).contains(&month) {
+            return Err(format!("month {} must be from 1 to 12", month));
+        }
+        Ok(PartialDate { month: Some(month), ..date })
+    }
+
+    /// Create a partial date with date precision.
+    pub fn date(year: i32, month: u8, day: u8) -> Result<Self, String> {
+        let date = PartialDate::year_month(year, month)?;
+        let days = days_in_month(year, month);
+        if !(1..=days).contains(&day) {
+            return Err(format!("day {} must be from 1 to {} in {}-{:02}", day, days, year, month));
+        }
+        Ok(PartialDate { day: Some(day), ..date })
+    }
+
+    /// Get the year.
+    pub fn get_year(&self) -> i32 {
+        self.year
+    }
+
+    /// Get the month, from 1 to 12, if known.
+    pub fn get_month(&self) -> Option<u8> {
+        self.month
+    }
+
+    /// Get the day of the month, if known.
+    pub fn get_day(&self) -> Option<u8> {
+        self.day
+    }
+
+    /// Get the precision.
+    pub fn precision(&self) -> DatePrecision {
+        match (self.month, self.day) {
+            (Some(_), Some(_)) => DatePrecision::Date,
+            (Some(_), None) => DatePrecision::YearMonth,
+            _ => DatePrecision::Year,
+        }
+    }
+
+    /// Reduce the date to at most the precision, such as "1999-12-31" to "1999".
+    pub fn truncate(&self, precision: DatePrecision) -> Self {
+        match precision {
+            DatePrecision::Year => PartialDate { month: None, day: None, ..*self },
+            DatePrecision::YearMonth => PartialDate { day: None, ..*self },
+            DatePrecision::Date => *self,
+        }
+    }
+
+    /// Get the common precision of two dates, which is the lesser precision.
+    pub fn common_precision(&self, other: &PartialDate) -> DatePrecision {
+        std::cmp::min(self.precision(), other.precision())
+    }
+
+}
+
+/// The number of days in a month, using the Gregorian calendar for leap years.
+pub fn days_in_month(year: i32, month: u8) -> u8 {
+    match month {
+        2 if (year % 4 == 0 && year % 100 != 0) || year % 400 == 0 => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+impl std::str::FromStr for PartialDate {
+    type Err = String;
+
+    /// Parse an ISO 8601 string: "YYYY", "YYYY-MM", or "YYYY-MM-DD".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let parts: Vec<&str> = s.split('-').collect();
+        let widths = [4, 2, 2];
+        if parts.len() > widths.len() || parts.iter().zip(widths.iter()).any(|(part, &width)| part.len() != width || !part.bytes().all(|b| b.is_ascii_digit())) {
+            return Err(format!("partial date {:?} must be YYYY, YYYY-MM, or YYYY-MM-DD", s));
+        }
+        // Each part is all digits of a fixed width, so each parse succeeds.
+        let year: i32 = parts[0].parse().unwrap();
+        match parts.len() {
+            1 => PartialDate::year(year),
+            2 => PartialDate::year_month(year, parts[1].parse().unwrap()),
+            _ => PartialDate::date(year, parts[1].parse().unwrap(), parts[2].parse().unwrap()),
+        }
+    }
+}
+
+impl TryFrom<String> for PartialDate {
+    type Error = String;
+
+    fn try_from(s: String) -> Result<Self, Self::Error> {
+        s.parse()
+    }
+}
+
+impl From<PartialDate> for String {
+    fn from(date: PartialDate) -> Self {
+        date.to_string()
+    }
+}
+
+// Display the date as an ISO 8601 string, such as "1999-12".
+impl std::fmt::Display for PartialDate {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{:04}", self.year)?;
+        if let Some(month) = self.month {
+            write!(f, "-{:02}", month)?;
+        }
+        if let Some(day) = self.day {
+            write!(f, "-{:02}", day)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("1999".parse(), PartialDate::year(1999));
+        assert_eq!("1999-12".parse(), PartialDate::year_month(1999, 12));
+        assert_eq!("1999-12-31".parse(), PartialDate::date(1999, 12, 31));
+        assert_eq!("2000-02-29".parse::<PartialDate>().unwrap().precision(), DatePrecision::Date);
+        for s in ["", "99", "1999-1", "1999-13", "1999-02-29", "1900-02-29", "1999-04-31", "1999-12-31-1", "1999-00", "0000", "199x", "1999/12"] {
+            assert!(s.parse::<PartialDate>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        for s in ["1999", "1999-02", "1999-12-01", "0801"] {
+            assert_eq!(s.parse::<PartialDate>().unwrap().to_string(), s);
+        }
+    }
+
+    #[test]
+    fn test_serde() {
+        let date: PartialDate = serde_json::from_str("\"1999-12\"").unwrap();
+        assert_eq!(date, PartialDate::year_month(1999, 12).unwrap());
+        assert_eq!(serde_json::to_string(&date).unwrap(), "\"1999-12\"");
+        assert!(serde_json::from_str::<PartialDate>("\"1999-02-31\"").is_err());
+        assert!(serde_json::from_str::<PartialDate>("1999").is_err());
+    }
+
+    #[test]
+    fn test_precision() {
+        let date = PartialDate::date(1999, 12, 31).unwrap();
+        assert_eq!(date.truncate(DatePrecision::YearMonth), PartialDate::year_month(1999, 12).unwrap());
+        assert_eq!(date.truncate(DatePrecision::Year), PartialDate::year(1999).unwrap());
+        assert_eq!(date.common_precision(&PartialDate::year(2000).unwrap()), DatePrecision::Year);
+        assert_eq!(date.common_precision(&date), DatePrecision::Date);
+    }
+
+}
@@ -1,42 +1,100 @@
-/// Use Deserialize to convert e.g. from request JSON into Book struct.
-use serde::Deserialize;
+/// Use Deserialize to convert e.g. from request JSON into Book struct,
+/// and Serialize to convert back e.g. into response JSON.
+use serde::{Deserialize, Serialize};
+use crate::models::address::Address;
+use crate::models::nhs_number::NhsNumber;
+use crate::models::other_name::OtherName;
+use crate::models::partial_date::PartialDate;
 
 // Demo person structure with some example fields for title and author.
-#[derive(Debug, Deserialize, Clone, Eq, Hash, PartialEq)]
+#[derive(Debug, Default, Serialize, Deserialize, Clone, Eq, Hash, PartialEq)]
 pub struct Person {
     pub id: String,
+    pub nhs_number: Option<NhsNumber>,
+    pub title: Option<String>,
     pub given_name: Option<String>,
+    pub middle_names: Option<String>,
     pub family_name: Option<String>,
-    pub birth_date_year: Option<i32>,
-    pub birth_date_month: Option<i8>,
-    pub birth_date_month_day: Option<i8>,
+    pub suffix: Option<String>,
+    #[serde(default)]
+    pub other_names: Vec<OtherName>,
+    pub birth_date: Option<PartialDate>,
     pub primary_email: Option<String>,
+    #[serde(default)]
+    pub other_emails: Vec<String>,
     pub primary_phone: Option<String>,
+    #[serde(default)]
+    pub other_phones: Vec<String>,
+    pub address: Option<Address>,
     pub note: Option<String>,
 }
 
+impl Person {
+    /// Get a copy of the person with each blank text field as `None`,
+    /// such as `Some("")` or `Some("  ")`.
+    ///
+    /// Blank other emails and phones are removed, and so are other names
+    /// whose given name and family name are both blank. Blank address lines
+    /// are removed, and an address with nothing but blanks is `None`.
+    ///
+    pub fn without_blanks(&self) -> Person {
+        let text = |x: &Option<String>| x.clone().filter(|x| !x.trim().is_empty());
+        let texts = |x: &[String]| x.iter().filter(|x| !x.trim().is_empty()).cloned().collect();
+        Person {
+            other_names: self.other_names.iter()
+                .map(|x| OtherName { given_name: text(&x.given_name), family_name: text(&x.family_name), ..x.clone() })
+                .filter(|x| x.given_name.is_some() || x.family_name.is_some())
+                .collect(),
+            other_emails: texts(&self.other_emails),
+            other_phones: texts(&self.other_phones),
+            address: self.address.as_ref().map(|x| x.without_blanks()).filter(|x| !x.is_empty()),
+            title: text(&self.title),
+            given_name: text(&self.given_name),
+            middle_names: text(&self.middle_names),
+            family_name: text(&self.family_name),
+            suffix: text(&self.suffix),
+            primary_email: text(&self.primary_email),
+            primary_phone: text(&self.primary_phone),
+            note: text(&self.note),
+            ..self.clone()
+        }
+    }
+}
+
 // Display the person using any kind of reasonable format.
 // This is a typical Rust trait and is not axum-specific.
 impl std::fmt::Display for Person {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
         write!(f,
             concat!(
+                "NHS number: {:?}, ",
+                "title: {:?}, ",
                 "given name: {:?}, ",
+                "middle names: {:?}, ",
                 "family name: {:?}, ",
-                "birth date year: {:?}, ",
-                "birth date month: {:?}, ",
-                "birth date month day: {:?}, ",
+                "suffix: {:?}, ",
+                "other names: {:?}, ",
+                "birth date: {:?}, ",
                 "primary email: {:?}, ",
-                "primary phone: {:?}",
+                "other emails: {:?}, ",
+                "primary phone: {:?}, ",
+                "other phones: {:?}, ",
+                "address: {:?}, ",
                 "note: {:?}",
             ),
+            self.nhs_number.as_ref().map(|x| x.to_string()),
+            self.title,
             self.given_name,
+            self.middle_names,
             self.family_name,
-            self.birth_date_year,
-            self.birth_date_month,
-            self.birth_date_month_day,
+            self.suffix,
+            self.other_names.iter().map(|x| x.to_string()).collect::<Vec<String>>(),
+            self.birth_date.map(|x| x.to_string()),
             self.primary_email,
+            self.other_emails,
             self.primary_phone,
+            self.other_phones,
+            self.address.as_ref().map(|x| x.to_string()),
             self.note,
         )
     }
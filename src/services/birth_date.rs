@@ -0,0 +1,308 @@
+// Fuzzy comparison of birth dates.
+//
+// Comparing each part of a birth date by equality penalises common
+// data-entry errors as much as a different date. The fuzzy comparison
+// gives partial credit for these near misses:
+//
+// - Day and month transposed, such as US order versus UK order.
+// - A year out by one, out by ten, or with a single-digit typo.
+// - A day out by one, in the same month.
+//
+// Dates are compared at their common precision, so "1999" and "1999-12-31"
+// compare only the year.
+//
+// It also treats 1 January as a placeholder for an unknown day and month,
+// when the other date is not 1 January, so that the day and month count as
+// missing rather than different.
+
+use serde::{Deserialize, Serialize};
+use similarity_trait::Similarity;
+use crate::models::person::Person;
+use crate::models::partial_date::PartialDate;
+use crate::services::similarity::{BIRTH_DATE_MONTH_DAY_EQ, BIRTH_DATE_MONTH_EQ, BIRTH_DATE_YEAR_EQ};
+
+/// The sub-score of the month and the day when they are transposed.
+pub const DAY_MONTH_TRANSPOSED: f64 = 0.8;
+
+/// The sub-score of the year when it is out by one.
+pub const YEAR_OFF_BY_ONE: f64 = 0.8;
+
+/// The sub-score of the year when it is out by ten.
+pub const YEAR_OFF_BY_TEN: f64 = 0.6;
+
+/// The sub-score of the year when one digit differs.
+pub const YEAR_TYPO: f64 = 0.6;
+
+/// The sub-score of the day when it is out by one, in the same month.
+pub const DAY_OFF_BY_ONE: f64 = 0.8;
+
+/// How birth dates are compared.
+///
+/// - `Fuzzy`: compare the whole date, with partial credit for near misses;
+///   see [compare_birth_dates].
+///
+/// - `Parts`: compare each part with the comparators of its field.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum BirthDateComparator {
+    #[default]
+    Fuzzy,
+    Parts,
+}
+
+/// The kind of near miss between two birth dates.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum DateNearMiss {
+    DayMonthTransposed,
+    YearOffByOne,
+    YearOffByTen,
+    YearTypo,
+    DayOffByOne,
+    Placeholder,
+}
+
+impl DateNearMiss {
+    /// Get the name of the near miss, as in the explanation.
+    pub fn name(&self) -> &'static str {
+        match self {
+            DateNearMiss::DayMonthTransposed => "day_month_transposed",
+            DateNearMiss::YearOffByOne => "year_off_by_one",
+            DateNearMiss::YearOffByTen => "year_off_by_ten",
+            DateNearMiss::YearTypo => "year_typo",
+            DateNearMiss::DayOffByOne => "day_off_by_one",
+            DateNearMiss::Placeholder => "placeholder",
+        }
+    }
+}
+
+/// The comparison of two birth dates.
+///
+/// - `year`, `month`, `month_day`: the sub-score of each part, which is
+///   `None` when either date lacks the part, such as the month and the
+///   month day when either date has only a year.
+///
+/// - `year_near_miss`: the near miss of the year, if any.
+///
+/// - `month_day_near_miss`: the near miss of the month and day, if any.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct BirthDateComparison {
+    pub year: Option<f64>,
+    pub month: Option<f64>,
+    pub month_day: Option<f64>,
+    pub year_near_miss: Option<DateNearMiss>,
+    pub month_day_near_miss: Option<DateNearMiss>,
+}
+
+/// Compare two birth dates, with partial credit for near misses.
+///
+/// Equal parts score 1.0, and different parts score 0.0, except:
+///
+/// - Year out by one: [YEAR_OFF_BY_ONE].
+///
+/// - Year out by ten: [YEAR_OFF_BY_TEN].
+///
+/// - Year with a single-digit typo: [YEAR_TYPO].
+///
+/// - Day and month transposed: each scores [DAY_MONTH_TRANSPOSED].
+///
+/// - Day out by one, in the same month: the day scores [DAY_OFF_BY_ONE].
+///
+/// - Either date is 1 January and the other is not: the day and month
+///   are `None`, as if unknown.
+///
+pub fn compare_birth_dates(a: &PartialDate, b: &PartialDate) -> BirthDateComparison {
+    let precision = a.common_precision(b);
+    let (a, b) = (a.truncate(precision), b.truncate(precision));
+    let (value, near_miss) = compare_years(a.get_year(), b.get_year());
+    let mut comparison = BirthDateComparison {
+        year: Some(value),
+        year_near_miss: near_miss,
+        ..BirthDateComparison::default()
+    };
+
+    let equal = |x: Option<u8>, y: Option<u8>| match (x, y) {
+        (Some(x), Some(y)) => Some(if x == y { 1.0 } else { 0.0 }),
+        _ => None,
+    };
+    comparison.month = equal(a.get_month(), b.get_month());
+    comparison.month_day = equal(a.get_day(), b.get_day());
+
+    if let (Some(am), Some(ad), Some(bm), Some(bd)) = (a.get_month(), a.get_day(), b.get_month(), b.get_day()) {
+        if am == bm && ad == bd {
+            // Equal.
+        }
+        else if is_placeholder(&a) || is_placeholder(&b) {
+            comparison.month = None;
+            comparison.month_day = None;
+            comparison.month_day_near_miss = Some(DateNearMiss::Placeholder);
+        }
+        else if am == bd && ad == bm {
+            comparison.month = Some(DAY_MONTH_TRANSPOSED);
+            comparison.month_day = Some(DAY_MONTH_TRANSPOSED);
+            comparison.month_day_near_miss = Some(DateNearMiss::DayMonthTransposed);
+        }
+        else if am == bm && ad.abs_diff(bd) == 1 {
+            comparison.month_day = Some(DAY_OFF_BY_ONE);
+            comparison.month_day_near_miss = Some(DateNearMiss::DayOffByOne);
+        }
+    }
+
+    comparison
+}
+
+/// Compare the birth dates of two persons; see [compare_birth_dates].
+///
+/// Every part is `None` when either person lacks a birth date.
+///
+pub fn compare_birth_dates_of_persons(a: &Person, b: &Person) -> BirthDateComparison {
+    match (&a.birth_date, &b.birth_date) {
+        (Some(a), Some(b)) => compare_birth_dates(a, b),
+        _ => BirthDateComparison::default(),
+    }
+}
+
+impl Similarity<(&PartialDate, &PartialDate), f64> for PartialDate {
+    /// The mean of the sub-scores of [compare_birth_dates], weighted by the
+    /// default weights of the year, month, and month day, over the parts
+    /// that both dates have.
+    fn similarity(input: (&PartialDate, &PartialDate)) -> f64 {
+        let comparison = compare_birth_dates(input.0, input.1);
+        let parts = [
+            (comparison.year, BIRTH_DATE_YEAR_EQ),
+            (comparison.month, BIRTH_DATE_MONTH_EQ),
+            (comparison.month_day, BIRTH_DATE_MONTH_DAY_EQ),
+        ];
+        let (sum, total) = parts.iter()
+            .filter_map(|(value, weight)| value.map(|value| (value * weight, *weight)))
+            .fold((0.0, 0.0), |(sum, total), (x, w)| (sum + x, total + w));
+        if total > 0.0 { sum / total } else { 0.0 }
+    }
+}
+
+/// True when the date is 1 January, which often means an unknown date.
+pub fn is_placeholder(date: &PartialDate) -> bool {
+    date.get_month() == Some(1) && date.get_day() == Some(1)
+}
+
+// Compare two years, with partial credit for near misses.
+fn compare_years(x: i32, y: i32) -> (f64, Option<DateNearMiss>) {
+    let (xs, ys) = (x.to_string(), y.to_string());
+    if x == y {
+        (1.0, None)
+    }
+    else if (x - y).abs() == 1 {
+        (YEAR_OFF_BY_ONE, Some(DateNearMiss::YearOffByOne))
+    }
+    else if (x - y).abs() == 10 {
+        (YEAR_OFF_BY_TEN, Some(DateNearMiss::YearOffByTen))
+    }
+    else if xs.len() == ys.len() && xs.chars().zip(ys.chars()).filter(|(a, b)| a != b).count() == 1 {
+        (YEAR_TYPO, Some(DateNearMiss::YearTypo))
+    }
+    else {
+        (0.0, None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(year: i32, month: u8, day: u8) -> PartialDate {
+        PartialDate::date(year, month, day).unwrap()
+    }
+
+    #[test]
+    fn test_exact() {
+        let comparison = compare_birth_dates(&date(1980, 5, 17), &date(1980, 5, 17));
+        assert_eq!(comparison, BirthDateComparison {
+            year: Some(1.0),
+            month: Some(1.0),
+            month_day: Some(1.0),
+            year_near_miss: None,
+            month_day_near_miss: None,
+        });
+    }
+
+    #[test]
+    fn test_different() {
+        let comparison = compare_birth_dates(&date(1980, 5, 17), &date(1955, 11, 3));
+        assert_eq!((comparison.year, comparison.month, comparison.month_day), (Some(0.0), Some(0.0), Some(0.0)));
+        assert_eq!((comparison.year_near_miss, comparison.month_day_near_miss), (None, None));
+    }
+
+    #[test]
+    fn test_day_month_transposed() {
+        let comparison = compare_birth_dates(&date(1980, 5, 7), &date(1980, 7, 5));
+        assert_eq!(comparison.month, Some(DAY_MONTH_TRANSPOSED));
+        assert_eq!(comparison.month_day, Some(DAY_MONTH_TRANSPOSED));
+        assert_eq!(comparison.month_day_near_miss, Some(DateNearMiss::DayMonthTransposed));
+    }
+
+    #[test]
+    fn test_years() {
+        for (x, y, value, near_miss) in [
+            (1980, 1981, YEAR_OFF_BY_ONE, Some(DateNearMiss::YearOffByOne)),
+            (1989, 1990, YEAR_OFF_BY_ONE, Some(DateNearMiss::YearOffByOne)),
+            (1980, 1970, YEAR_OFF_BY_TEN, Some(DateNearMiss::YearOffByTen)),
+            (1980, 1983, YEAR_TYPO, Some(DateNearMiss::YearTypo)),
+            (1980, 1080, YEAR_TYPO, Some(DateNearMiss::YearTypo)),
+            (1980, 1908, 0.0, None),
+        ] {
+            let comparison = compare_birth_dates(&date(x, 5, 17), &date(y, 5, 17));
+            assert_eq!(comparison.year, Some(value), "{} {}", x, y);
+            assert_eq!(comparison.year_near_miss, near_miss, "{} {}", x, y);
+            assert_eq!(comparison.month_day, Some(1.0));
+        }
+    }
+
+    #[test]
+    fn test_day_off_by_one() {
+        let comparison = compare_birth_dates(&date(1980, 5, 17), &date(1980, 5, 18));
+        assert_eq!(comparison.month, Some(1.0));
+        assert_eq!(comparison.month_day, Some(DAY_OFF_BY_ONE));
+        assert_eq!(comparison.month_day_near_miss, Some(DateNearMiss::DayOffByOne));
+        let comparison = compare_birth_dates(&date(1980, 5, 17), &date(1980, 6, 18));
+        assert_eq!(comparison.month_day, Some(0.0));
+        assert_eq!(comparison.month_day_near_miss, None);
+    }
+
+    #[test]
+    fn test_placeholder() {
+        let comparison = compare_birth_dates(&date(1980, 1, 1), &date(1980, 5, 17));
+        assert_eq!(comparison.year, Some(1.0));
+        assert_eq!(comparison.month, None);
+        assert_eq!(comparison.month_day, None);
+        assert_eq!(comparison.month_day_near_miss, Some(DateNearMiss::Placeholder));
+        let comparison = compare_birth_dates(&date(1980, 1, 1), &date(1980, 1, 1));
+        assert_eq!(comparison.month_day, Some(1.0));
+        assert_eq!(comparison.month_day_near_miss, None);
+    }
+
+    #[test]
+    fn test_missing() {
+        let a = PartialDate::year(1980).unwrap();
+        let comparison = compare_birth_dates(&a, &date(1980, 5, 17));
+        assert_eq!((comparison.year, comparison.month, comparison.month_day), (Some(1.0), None, None));
+        let a = PartialDate::year_month(1980, 5).unwrap();
+        let comparison = compare_birth_dates(&a, &date(1981, 5, 17));
+        assert_eq!((comparison.year, comparison.month, comparison.month_day), (Some(YEAR_OFF_BY_ONE), Some(1.0), None));
+        let a = PartialDate::year_month(1980, 1).unwrap();
+        let comparison = compare_birth_dates(&a, &date(1980, 1, 1));
+        assert_eq!(comparison.month_day_near_miss, None);
+    }
+
+    #[test]
+    fn test_similarity_trait() {
+        assert_eq!(PartialDate::similarity((&date(1980, 5, 17), &date(1980, 5, 17))), 1.0);
+        assert_eq!(PartialDate::similarity((&date(1980, 5, 17), &date(1955, 11, 3))), 0.0);
+        let transposed = PartialDate::similarity((&date(1980, 5, 7), &date(1980, 7, 5)));
+        assert!(0.8 < transposed && transposed < 1.0);
+        let a = PartialDate::year(1980).unwrap();
+        assert_eq!(PartialDate::similarity((&a, &date(1981, 5, 17))), YEAR_OFF_BY_ONE);
+    }
+
+}
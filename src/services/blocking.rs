@@ -0,0 +1,145 @@
+// Blocking: choose candidate pairs of records by shared keys.
+//
+// Comparing every pair of records grows with the square of the record count.
+// Blocking compares only the pairs that share a blocking key, such as the
+// same phonetic code of the family name, which keeps most true matches
+// while skipping most non-matches.
+//
+// A blocking rule is a field and an optional phonetic encoder. Two records
+// are a candidate pair when they share a key of any rule.
+
+use std::collections::{BTreeSet, HashMap};
+use serde::{Deserialize, Serialize};
+use crate::models::person::Person;
+use crate::services::phonetic::PhoneticEncoder;
+use crate::services::similarity::{Field, field_text};
+
+/// A blocking rule: the field, and the phonetic encoder of the field, if any.
+///
+/// Without an encoder, the key is the field text, trimmed and uppercase.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BlockingRule {
+    pub field: Field,
+    #[serde(default)]
+    pub encoder: Option<PhoneticEncoder>,
+}
+
+impl std::str::FromStr for BlockingRule {
+    type Err = String;
+
+    /// Parse a rule from "FIELD" or "FIELD:ENCODER", such as "family_name:soundex".
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (field, encoder) = match s.split_once(':') {
+            Some((field, encoder)) => (field, Some(encoder)),
+            None => (s, None),
+        };
+        let field = Field::from_name(field).ok_or_else(|| format!("unknown field {:?}", field))?;
+        let encoder = match encoder {
+            Some(encoder) => Some(PhoneticEncoder::from_name(encoder).ok_or_else(|| format!("unknown phonetic encoder {:?}", encoder))?),
+            None => None,
+        };
+        Ok(BlockingRule { field, encoder })
+    }
+}
+
+impl BlockingRule {
+    /// Get the blocking keys of a person, which are none when the person lacks the field.
+    pub fn keys(&self, person: &Person) -> Vec<String> {
+        let Some(text) = field_text(person, self.field) else { return Vec::new() };
+        match self.encoder {
+            Some(encoder) => encoder.encode(&text),
+            None => {
+                let key = text.trim().to_uppercase();
+                if key.is_empty() { Vec::new() } else { vec![key] }
+            }
+        }
+    }
+}
+
+/// Get each unordered pair of persons that shares a key of any rule, once,
+/// in order of the persons.
+pub fn candidate_pairs<'a>(persons: &'a [Person], rules: &[BlockingRule]) -> Vec<(&'a Person, &'a Person)> {
+    let mut pairs: BTreeSet<(usize, usize)> = BTreeSet::new();
+    for (r, rule) in rules.iter().enumerate() {
+        let mut blocks: HashMap<(usize, String), Vec<usize>> = HashMap::new();
+        for (i, person) in persons.iter().enumerate() {
+            for key in rule.keys(person) {
+                blocks.entry((r, key)).or_default().push(i);
+            }
+        }
+        for block in blocks.values() {
+            for x in 0..block.len() {
+                for y in (x + 1)..block.len() {
+                    pairs.insert((block[x], block[y]));
+                }
+            }
+        }
+    }
+    pairs.into_iter().map(|(i, j)| (&persons[i], &persons[j])).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn person(id: &str, given_name: &str, family_name: &str) -> Person {
+        Person {
+            id: String::from(id),
+            nhs_number: None,
+            title: None,
+            given_name: Some(String::from(given_name)),
+            middle_names: None,
+            family_name: Some(String::from(family_name)),
+            suffix: None,
+            other_names: Vec::new(),
+            birth_date: None,
+            primary_email: None,
+            other_emails: Vec::new(),
+            primary_phone: None,
+            other_phones: Vec::new(),
+            address: None,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_from_str() {
+        assert_eq!("family_name:soundex".parse(), Ok(BlockingRule { field: Field::FamilyName, encoder: Some(PhoneticEncoder::Soundex) }));
+        assert_eq!("primary_email".parse(), Ok(BlockingRule { field: Field::PrimaryEmail, encoder: None }));
+        assert!("nope:soundex".parse::<BlockingRule>().is_err());
+        assert!("family_name:nope".parse::<BlockingRule>().is_err());
+    }
+
+    #[test]
+    fn test_keys() {
+        let rule = BlockingRule { field: Field::FamilyName, encoder: Some(PhoneticEncoder::DoubleMetaphone) };
+        assert_eq!(rule.keys(&person("1", "Alice", "Smith")), vec!["SM0", "XMT"]);
+        let rule = BlockingRule { field: Field::GivenName, encoder: None };
+        assert_eq!(rule.keys(&person("1", " alice ", "Smith")), vec!["ALICE"]);
+        assert!(rule.keys(&person("1", "", "Smith")).is_empty());
+    }
+
+    #[test]
+    fn test_candidate_pairs() {
+        let persons = vec![
+            person("1", "Alice", "Smith"),
+            person("2", "Alicia", "Smyth"),
+            person("3", "Bob", "Jones"),
+            person("4", "Bob", "Brown"),
+        ];
+        let ids = |rules: &[BlockingRule]| -> Vec<(String, String)> {
+            candidate_pairs(&persons, rules).into_iter().map(|(a, b)| (a.id.clone(), b.id.clone())).collect()
+        };
+        let family_name = BlockingRule { field: Field::FamilyName, encoder: Some(PhoneticEncoder::Soundex) };
+        let given_name = BlockingRule { field: Field::GivenName, encoder: None };
+        assert_eq!(ids(&[family_name]), vec![(String::from("1"), String::from("2"))]);
+        assert_eq!(ids(&[family_name, given_name]), vec![
+            (String::from("1"), String::from("2")),
+            (String::from("3"), String::from("4")),
+        ]);
+        assert!(ids(&[]).is_empty());
+    }
+
+}
@@ -0,0 +1,320 @@
+// String comparators, and a registry of comparators keyed by name.
+//
+// Each field of a similarity profile names the comparators that it uses;
+// see [crate::services::similarity_config::SimilarityProfile]. When a field
+// names more than one comparator, its value is their average.
+
+// Use LazyLock for creating a thread-safe global variable e.g. our COMPARATORS.
+use std::sync::LazyLock;
+
+// Use RwLock because the registry is read by every comparison and written rarely.
+use std::sync::RwLock;
+
+use std::collections::HashMap;
+use std::sync::Arc;
+use similarity_trait::Similarity;
+use crate::services::phonetic::PhoneticEncoder;
+use crate::services::place_names::PlaceName;
+use crate::services::welsh::WelshName;
+
+/// Compare two strings, returning 0.0 for no similarity up to 1.0 for identical.
+pub trait StringComparator: Send + Sync {
+    fn compare(&self, a: &str, b: &str) -> f64;
+}
+
+/// [Jaro similarity](https://en.wikipedia.org/wiki/Jaro%E2%80%93Winkler_distance).
+pub struct Jaro;
+
+impl StringComparator for Jaro {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        strsim::jaro(a, b)
+    }
+}
+
+/// [Jaro-Winkler similarity](https://en.wikipedia.org/wiki/Jaro%E2%80%93Winkler_distance),
+/// which favours strings with a common prefix.
+pub struct JaroWinkler;
+
+impl StringComparator for JaroWinkler {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        strsim::jaro_winkler(a, b)
+    }
+}
+
+/// [Levenshtein distance](https://en.wikipedia.org/wiki/Levenshtein_distance),
+/// normalized to a similarity.
+pub struct Levenshtein;
+
+impl StringComparator for Levenshtein {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        strsim::normalized_levenshtein(a, b)
+    }
+}
+
+/// [Optimal string alignment distance](https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance#Optimal_string_alignment_distance),
+/// normalized to a similarity.
+pub struct Osa;
+
+impl StringComparator for Osa {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        let len = std::cmp::max(a.chars().count(), b.chars().count());
+        if len == 0 {
+            return 1.0;
+        }
+        1.0 - (strsim::osa_distance(a, b) as f64 / len as f64)
+    }
+}
+
+/// [Damerau–Levenshtein distance](https://en.wikipedia.org/wiki/Damerau%E2%80%93Levenshtein_distance),
+/// normalized to a similarity.
+pub struct DamerauLevenshtein;
+
+impl StringComparator for DamerauLevenshtein {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        strsim::normalized_damerau_levenshtein(a, b)
+    }
+}
+
+/// [Sørensen-Dice coefficient](https://en.wikipedia.org/wiki/Dice-S%C3%B8rensen_coefficient)
+/// of character bigrams.
+pub struct SorensenDice;
+
+impl StringComparator for SorensenDice {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        strsim::sorensen_dice(a, b)
+    }
+}
+
+/// [Jaccard index](https://en.wikipedia.org/wiki/Jaccard_index) of character q-grams.
+///
+/// A string shorter than q is one q-gram.
+///
+pub struct Jaccard {
+    pub q: usize,
+}
+
+impl StringComparator for Jaccard {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        let a = q_grams(a, self.q);
+        let b = q_grams(b, self.q);
+        let union = a.union(&b).count();
+        if union == 0 {
+            return 1.0;
+        }
+        a.intersection(&b).count() as f64 / union as f64
+    }
+}
+
+fn q_grams(s: &str, q: usize) -> std::collections::HashSet<String> {
+    let chars: Vec<char> = s.chars().collect();
+    if chars.len() <= q {
+        return std::collections::HashSet::from([s.to_string()]);
+    }
+    chars.windows(q).map(|w| w.iter().collect()).collect()
+}
+
+/// [Longest common subsequence](https://en.wikipedia.org/wiki/Longest_common_subsequence),
+/// as a similarity: twice the subsequence length divided by the total length.
+pub struct LongestCommonSubsequence;
+
+impl StringComparator for LongestCommonSubsequence {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        let a: Vec<char> = a.chars().collect();
+        let b: Vec<char> = b.chars().collect();
+        if a.is_empty() && b.is_empty() {
+            return 1.0;
+        }
+        let mut previous = vec![0usize; b.len() + 1];
+        let mut current = vec![0usize; b.len() + 1];
+        for x in a.iter() {
+            for (j, y) in b.iter().enumerate() {
+                current[j + 1] = if x == y { previous[j] + 1 } else { std::cmp::max(previous[j + 1], current[j]) };
+            }
+            std::mem::swap(&mut previous, &mut current);
+        }
+        2.0 * previous[b.len()] as f64 / (a.len() + b.len()) as f64
+    }
+}
+
+/// Exact equality: 1.0 if equal, otherwise 0.0.
+pub struct Exact;
+
+impl StringComparator for Exact {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        if a == b { 1.0 } else { 0.0 }
+    }
+}
+
+// Each comparator also implements the Similarity trait for a pair of strings,
+// so it can be used generically alongside other comparable items.
+macro_rules! impl_similarity_for_comparator {
+    ($($comparator:ident),*) => {
+        $(
+            impl Similarity<(&str, &str), f64> for $comparator {
+                fn similarity(input: (&str, &str)) -> f64 {
+                    $comparator.compare(input.0, input.1)
+                }
+            }
+        )*
+    };
+}
+
+impl_similarity_for_comparator!(Jaro, JaroWinkler, Levenshtein, Osa, DamerauLevenshtein, SorensenDice, LongestCommonSubsequence, Exact);
+
+impl Similarity<(&str, &str), f64> for Jaccard {
+    /// Similarity of bigrams; use [Jaccard::compare] for other q-grams.
+    fn similarity(input: (&str, &str)) -> f64 {
+        Jaccard { q: 2 }.compare(input.0, input.1)
+    }
+}
+
+/// A registry of string comparators keyed by name.
+///
+/// The default registry has these names:
+///
+/// - "jaro"
+/// - "jaro_winkler"
+/// - "levenshtein"
+/// - "osa"
+/// - "damerau_levenshtein"
+/// - "sorensen_dice"
+/// - "jaccard" (bigrams)
+/// - "longest_common_subsequence"
+/// - "exact"
+/// - "welsh_name" (Welsh rules for names)
+/// - "place_name" (Welsh and English place names as equivalents)
+/// - "soundex", "nysiis", "metaphone", "double_metaphone" (1.0 when the
+///   phonetic codes agree, otherwise 0.0)
+///
+pub struct ComparatorRegistry {
+    comparators: HashMap<String, Arc<dyn StringComparator>>,
+}
+
+impl Default for ComparatorRegistry {
+    fn default() -> Self {
+        let mut registry = ComparatorRegistry { comparators: HashMap::new() };
+        registry.register("jaro", Arc::new(Jaro));
+        registry.register("jaro_winkler", Arc::new(JaroWinkler));
+        registry.register("levenshtein", Arc::new(Levenshtein));
+        registry.register("osa", Arc::new(Osa));
+        registry.register("damerau_levenshtein", Arc::new(DamerauLevenshtein));
+        registry.register("sorensen_dice", Arc::new(SorensenDice));
+        registry.register("jaccard", Arc::new(Jaccard { q: 2 }));
+        registry.register("longest_common_subsequence", Arc::new(LongestCommonSubsequence));
+        registry.register("exact", Arc::new(Exact));
+        registry.register("welsh_name", Arc::new(WelshName));
+        registry.register("place_name", Arc::new(PlaceName));
+        for encoder in PhoneticEncoder::ALL {
+            registry.register(encoder.name(), Arc::new(encoder));
+        }
+        registry
+    }
+}
+
+impl ComparatorRegistry {
+
+    /// Add a comparator, or replace the comparator with the same name.
+    pub fn register(&mut self, name: &str, comparator: Arc<dyn StringComparator>) {
+        self.comparators.insert(name.to_string(), comparator);
+    }
+
+    /// Get a comparator by name.
+    pub fn get(&self, name: &str) -> Option<Arc<dyn StringComparator>> {
+        self.comparators.get(name).cloned()
+    }
+
+    /// True when the registry has a comparator with the name.
+    pub fn contains(&self, name: &str) -> bool {
+        self.comparators.contains_key(name)
+    }
+
+    /// Compare two strings using the average of the named comparators.
+    ///
+    /// - If either string is blank, then return 0.0 meaning no similarity.
+    ///
+    /// - If the strings are equal, then return 1.0 meaning identical similarity.
+    ///
+    /// - Otherwise, return the average of the named comparators.
+    ///
+    /// Panics if a name is not in the registry; validate names beforehand.
+    ///
+    pub fn compare(&self, names: &[String], a: &str, b: &str) -> f64 {
+        if a.is_empty() || b.is_empty() {
+            0.0
+        }
+        else if a == b {
+            1.0
+        }
+        else {
+            let sum: f64 = names.iter().map(|name|
+                self.comparators.get(name)
+                    .unwrap_or_else(|| panic!("unknown comparator {:?}", name))
+                    .compare(a, b)
+            ).sum();
+            sum / names.len() as f64
+        }
+    }
+
+}
+
+// Create the comparator registry as a global variable with `LazyLock` and `RwLock`.
+//
+// This starts as the default registry; add custom comparators at startup,
+// before loading any similarity config that names them.
+
+pub static COMPARATORS: LazyLock<RwLock<ComparatorRegistry>> = LazyLock::new(|| {
+    RwLock::new(ComparatorRegistry::default())
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_identical() {
+        let registry = ComparatorRegistry::default();
+        for name in ["jaro", "jaro_winkler", "levenshtein", "osa", "damerau_levenshtein", "sorensen_dice", "jaccard", "longest_common_subsequence", "exact", "welsh_name", "place_name", "soundex", "nysiis", "metaphone", "double_metaphone"] {
+            assert_eq!(registry.get(name).unwrap().compare("Alice", "Alice"), 1.0, "{}", name);
+        }
+    }
+
+    #[test]
+    fn test_values() {
+        assert_eq!(Levenshtein.compare("kitten", "sitting"), 1.0 - 3.0 / 7.0);
+        assert_eq!(Osa.compare("ca", "ac"), 0.5);
+        assert_eq!(DamerauLevenshtein.compare("ca", "ac"), 0.5);
+        assert_eq!(Jaccard { q: 2 }.compare("night", "nacht"), 1.0 / 7.0);
+        assert_eq!(LongestCommonSubsequence.compare("Smith", "Smyth"), 0.8);
+        assert_eq!(Exact.compare("Smith", "Smyth"), 0.0);
+        assert!(Jaro.compare("Martha", "Marhta") < JaroWinkler.compare("Martha", "Marhta"));
+    }
+
+    #[test]
+    fn test_similarity_trait() {
+        assert_eq!(Exact::similarity(("Smith", "Smith")), 1.0);
+        assert_eq!(LongestCommonSubsequence::similarity(("Smith", "Smyth")), 0.8);
+        assert_eq!(Jaccard::similarity(("night", "nacht")), 1.0 / 7.0);
+    }
+
+    #[test]
+    fn test_compare() {
+        let registry = ComparatorRegistry::default();
+        let names = vec![String::from("exact"), String::from("longest_common_subsequence")];
+        assert_eq!(registry.compare(&names, "", "Smith"), 0.0);
+        assert_eq!(registry.compare(&names, "Smith", "Smith"), 1.0);
+        assert_eq!(registry.compare(&names, "Smith", "Smyth"), 0.4);
+    }
+
+    #[test]
+    fn test_register() {
+        struct Half;
+        impl StringComparator for Half {
+            fn compare(&self, _a: &str, _b: &str) -> f64 { 0.5 }
+        }
+        let mut registry = ComparatorRegistry::default();
+        assert!(!registry.contains("half"));
+        registry.register("half", Arc::new(Half));
+        assert_eq!(registry.compare(&[String::from("half")], "a", "b"), 0.5);
+    }
+
+}
@@ -0,0 +1,194 @@
+// Canonicalisation and structured comparison of email addresses.
+//
+// Comparing email addresses as plain text goes wrong both ways:
+// "Alice.Adams@Example.COM" and "aliceadams+nhs@example.com" score low,
+// yet two different people at the same large domain score high, because
+// they share the long suffix "@example.com".
+//
+// The structured comparison first canonicalises each address:
+//
+// - Lowercase, because domains are case-insensitive and providers
+//   treat local parts as case-insensitive in practice.
+// - Remove any plus tag, such as "alice+nhs" to "alice".
+// - Replace a domain alias with its canonical domain, such as
+//   "googlemail.com" to "gmail.com".
+// - Remove dots from the local part where the provider ignores them,
+//   such as "alice.adams@gmail.com" to "aliceadams@gmail.com".
+//
+// Then it compares the local parts and the domains separately, so that
+// a shared domain gives credit only in proportion to the local part.
+
+use serde::{Deserialize, Serialize};
+use similarity_trait::Similarity;
+
+/// The weight of the local part, by default.
+pub const EMAIL_LOCAL_WEIGHT: f64 = 0.8;
+
+/// The weight of the domain, by default.
+pub const EMAIL_DOMAIN_WEIGHT: f64 = 0.2;
+
+/// Domain aliases and their canonical domains.
+pub const DOMAIN_ALIASES: [(&str, &str); 3] = [
+    ("googlemail.com", "gmail.com"),
+    ("me.com", "icloud.com"),
+    ("mac.com", "icloud.com"),
+];
+
+/// Canonical domains that ignore dots in the local part.
+pub const DOTS_IGNORED: [&str; 1] = [
+    "gmail.com",
+];
+
+/// How email addresses are compared.
+///
+/// - `Structured`: canonicalise each address, then compare the local parts
+///   and the domains separately; see [compare_emails].
+///
+/// - `Text`: compare with the comparators of the field.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum EmailComparator {
+    #[default]
+    Structured,
+    Text,
+}
+
+/// The weights of the local part and the domain in a structured comparison.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct EmailWeights {
+    pub local: f64,
+    pub domain: f64,
+}
+
+impl Default for EmailWeights {
+    fn default() -> Self {
+        EmailWeights {
+            local: EMAIL_LOCAL_WEIGHT,
+            domain: EMAIL_DOMAIN_WEIGHT,
+        }
+    }
+}
+
+/// An email address split into its canonical local part and domain.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct CanonicalEmail {
+    pub local: String,
+    pub domain: String,
+}
+
+impl std::str::FromStr for CanonicalEmail {
+    type Err = String;
+
+    /// Parse and canonicalise an email address.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s = s.trim().to_lowercase();
+        let (local, domain) = s.rsplit_once('@').ok_or_else(|| format!("email {:?} must have an @", s))?;
+        let local = local.split('+').next().unwrap_or_default();
+        if local.is_empty() || domain.is_empty() {
+            return Err(format!("email {:?} must have a local part and a domain", s));
+        }
+        let domain = DOMAIN_ALIASES.iter()
+            .find(|(alias, _)| *alias == domain)
+            .map_or(domain, |(_, canonical)| canonical);
+        let local = if DOTS_IGNORED.contains(&domain) { local.replace('.', "") } else { local.to_string() };
+        Ok(CanonicalEmail { local, domain: domain.to_string() })
+    }
+}
+
+// Display the canonical email address, such as "aliceadams@gmail.com".
+impl std::fmt::Display for CanonicalEmail {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}@{}", self.local, self.domain)
+    }
+}
+
+/// Compare two email addresses by their canonical local parts and domains.
+///
+/// The local parts are compared with Jaro-Winkler similarity, and the
+/// domains by equality. The local part scales the whole score, so a shared
+/// domain adds credit only when the local parts are similar:
+///
+/// local × (local weight + domain weight × domain) / (local weight + domain weight)
+///
+/// When either address is blank, the score is 0.0. When either address is
+/// not valid, the addresses are compared as lowercase text with
+/// Jaro-Winkler similarity.
+///
+pub fn compare_emails(a: &str, b: &str, weights: &EmailWeights) -> f64 {
+    if a.trim().is_empty() || b.trim().is_empty() {
+        return 0.0;
+    }
+    match (a.parse::<CanonicalEmail>(), b.parse::<CanonicalEmail>()) {
+        (Ok(a), Ok(b)) => compare_canonical_emails(&a, &b, weights),
+        _ => strsim::jaro_winkler(&a.trim().to_lowercase(), &b.trim().to_lowercase()),
+    }
+}
+
+/// Compare two canonical email addresses; see [compare_emails].
+pub fn compare_canonical_emails(a: &CanonicalEmail, b: &CanonicalEmail, weights: &EmailWeights) -> f64 {
+    let local = strsim::jaro_winkler(&a.local, &b.local);
+    let domain = if a.domain == b.domain { 1.0 } else { 0.0 };
+    let total = weights.local + weights.domain;
+    if total > 0.0 { local * (weights.local + weights.domain * domain) / total } else { local }
+}
+
+impl Similarity<(&CanonicalEmail, &CanonicalEmail), f64> for CanonicalEmail {
+    /// Similarity with the default weights; see [compare_canonical_emails].
+    fn similarity(input: (&CanonicalEmail, &CanonicalEmail)) -> f64 {
+        compare_canonical_emails(input.0, input.1, &EmailWeights::default())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn canonical(s: &str) -> String {
+        s.parse::<CanonicalEmail>().unwrap().to_string()
+    }
+
+    #[test]
+    fn test_canonical() {
+        assert_eq!(canonical(" Alice.Adams@Example.COM "), "alice.adams@example.com");
+        assert_eq!(canonical("aliceadams+nhs@example.com"), "aliceadams@example.com");
+        assert_eq!(canonical("Alice.Adams+test@googlemail.com"), "aliceadams@gmail.com");
+        assert_eq!(canonical("alice@mac.com"), "alice@icloud.com");
+        assert!("alice".parse::<CanonicalEmail>().is_err());
+        assert!("+tag@example.com".parse::<CanonicalEmail>().is_err());
+        assert!("alice@".parse::<CanonicalEmail>().is_err());
+    }
+
+    #[test]
+    fn test_compare_emails() {
+        let weights = EmailWeights::default();
+        assert_eq!(compare_emails("alice.adams@googlemail.com", "AliceAdams+nhs@gmail.com", &weights), 1.0);
+        assert!(compare_emails("Alice.Adams@Example.COM", "aliceadams+nhs@example.com", &weights) > 0.9);
+        assert_eq!(compare_emails("alice@example.com", "alice@example.org", &weights), EMAIL_LOCAL_WEIGHT / (EMAIL_LOCAL_WEIGHT + EMAIL_DOMAIN_WEIGHT));
+        let same_domain = compare_emails("alice@example.com", "zoe@example.com", &weights);
+        assert_eq!(same_domain, strsim::jaro_winkler("alice", "zoe"));
+        assert!(same_domain < 0.5);
+        assert_eq!(compare_emails("aaa", "aaa", &weights), 1.0);
+        assert_eq!(compare_emails("aaa", "bbb", &weights), 0.0);
+        assert_eq!(compare_emails("", "", &weights), 0.0);
+    }
+
+    #[test]
+    fn test_weights() {
+        let weights = EmailWeights { local: 1.0, domain: 1.0 };
+        assert_eq!(compare_emails("alice@example.com", "alice@example.org", &weights), 0.5);
+        let weights = EmailWeights { local: 0.0, domain: 0.0 };
+        assert_eq!(compare_emails("alice@example.com", "alice@example.org", &weights), 1.0);
+    }
+
+    #[test]
+    fn test_similarity_trait() {
+        let email = |s: &str| s.parse::<CanonicalEmail>().unwrap();
+        let (a, b) = (email("alice.adams@googlemail.com"), email("AliceAdams+nhs@gmail.com"));
+        assert_eq!(CanonicalEmail::similarity((&a, &b)), 1.0);
+        let (a, b) = (email("alice@example.com"), email("zoe@example.org"));
+        assert_eq!(CanonicalEmail::similarity((&a, &b)), compare_emails("alice@example.com", "zoe@example.org", &EmailWeights::default()));
+    }
+
+}
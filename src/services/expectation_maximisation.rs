@@ -0,0 +1,266 @@
+// Unsupervised estimation of Fellegi–Sunter parameters by expectation–maximisation.
+//
+// Each candidate pair of records becomes a comparison vector: one comparison
+// level per field. Given no labels, the algorithm alternates two steps:
+//
+// - Expectation: for each pair, estimate the probability that it is a match,
+//   using the current m-probabilities, u-probabilities, and prior.
+//
+// - Maximisation: re-estimate the m-probabilities, u-probabilities, and prior,
+//   by counting comparison levels weighted by those match probabilities.
+//
+// The log-likelihood never decreases, so the algorithm stops when the change
+// in log-likelihood is less than the tolerance, or at the maximum iterations.
+
+use serde::Serialize;
+use crate::data::DATA;
+use crate::models::person::Person;
+use crate::services::blocking::{self, BlockingRule};
+use crate::services::fellegi_sunter::{ComparisonLevel, FellegiSunterParameters};
+use crate::services::similarity::{Field, PerField, sub_scores_of_persons};
+use crate::services::similarity_config::SimilarityProfile;
+
+/// The smallest probability that the trainer will estimate,
+/// so that every match weight stays finite.
+pub const PROBABILITY_MIN: f64 = 0.000001;
+
+/// Options for expectation–maximisation.
+///
+/// When there are blocking rules, the candidate pairs are the pairs that
+/// share a blocking key; otherwise they are all pairs.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct EmOptions {
+    pub max_iterations: usize,
+    pub tolerance: f64,
+    pub blocking: Vec<BlockingRule>,
+}
+
+impl Default for EmOptions {
+    fn default() -> Self {
+        EmOptions {
+            max_iterations: 100,
+            tolerance: 0.000001,
+            blocking: Vec::new(),
+        }
+    }
+}
+
+/// The result of expectation–maximisation, with convergence diagnostics.
+///
+/// - `parameters`: the estimated parameters.
+/// - `pairs`: the number of candidate pairs.
+/// - `iterations`: the number of iterations run.
+/// - `converged`: true when the log-likelihood change fell below the tolerance.
+/// - `log_likelihoods`: the log-likelihood after each iteration.
+///
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct EmResult {
+    pub parameters: FellegiSunterParameters,
+    pub pairs: usize,
+    pub iterations: usize,
+    pub converged: bool,
+    pub log_likelihoods: Vec<f64>,
+}
+
+/// Get the comparison vector of a pair of persons.
+///
+/// The comparators of each field come from the profile, and the close
+/// threshold of each field comes from the profile Fellegi–Sunter parameters.
+///
+pub fn comparison_vector_of_persons(input: (&Person, &Person), profile: &SimilarityProfile) -> PerField<ComparisonLevel> {
+    let sub_scores = sub_scores_of_persons(input, profile);
+    let parameters = &profile.fellegi_sunter;
+    let level = |field: Field| ComparisonLevel::of_value(*sub_scores.get(field), parameters.fields.get(field).close_threshold);
+    PerField {
+        given_name: level(Field::GivenName),
+        family_name: level(Field::FamilyName),
+        birth_date_year: level(Field::BirthDateYear),
+        birth_date_month: level(Field::BirthDateMonth),
+        birth_date_month_day: level(Field::BirthDateMonthDay),
+        primary_phone: level(Field::PrimaryPhone),
+        primary_email: level(Field::PrimaryEmail),
+        nhs_number: level(Field::NhsNumber),
+        address_lines: level(Field::AddressLines),
+        town: level(Field::Town),
+        postcode: level(Field::Postcode),
+    }
+}
+
+/// Get every candidate pair of persons, i.e. each unordered pair once.
+pub fn candidate_pairs(persons: &[Person]) -> Vec<(&Person, &Person)> {
+    let mut pairs = Vec::new();
+    for i in 0..persons.len() {
+        for j in (i + 1)..persons.len() {
+            pairs.push((&persons[i], &persons[j]));
+        }
+    }
+    pairs
+}
+
+/// Estimate parameters from comparison vectors, starting from the initial parameters.
+///
+/// The close thresholds are copied from the initial parameters.
+///
+pub fn train(vectors: &[PerField<ComparisonLevel>], initial: &FellegiSunterParameters, options: &EmOptions) -> EmResult {
+    let mut parameters = initial.clone();
+    let mut log_likelihoods: Vec<f64> = Vec::new();
+    let mut converged = false;
+    let mut iterations = 0;
+    if vectors.is_empty() {
+        return EmResult { parameters, pairs: 0, iterations, converged, log_likelihoods };
+    }
+    while iterations < options.max_iterations {
+        iterations += 1;
+
+        // Expectation: the match probability of each pair, and the log-likelihood.
+        let mut log_likelihood = 0.0;
+        let match_probabilities: Vec<f64> = vectors.iter().map(|vector| {
+            let mut m = parameters.prior;
+            let mut u = 1.0 - parameters.prior;
+            for field in Field::ALL {
+                let probabilities = parameters.fields.get(field).level(*vector.get(field));
+                m *= probabilities.m;
+                u *= probabilities.u;
+            }
+            log_likelihood += (m + u).ln();
+            m / (m + u)
+        }).collect();
+
+        // Maximisation: the prior, then the m/u-probabilities of each level.
+        let match_sum: f64 = match_probabilities.iter().sum();
+        let non_match_sum = vectors.len() as f64 - match_sum;
+        parameters.prior = clamp_probability(match_sum / vectors.len() as f64);
+        for field in Field::ALL {
+            let field_parameters = parameters.fields.get_mut(field);
+            for level in ComparisonLevel::ALL {
+                let mut m = 0.0;
+                let mut u = 0.0;
+                for (vector, g) in vectors.iter().zip(match_probabilities.iter()) {
+                    if *vector.get(field) == level {
+                        m += g;
+                        u += 1.0 - g;
+                    }
+                }
+                let probabilities = field_parameters.level_mut(level);
+                probabilities.m = clamp_probability(if match_sum > 0.0 { m / match_sum } else { 0.0 });
+                probabilities.u = clamp_probability(if non_match_sum > 0.0 { u / non_match_sum } else { 0.0 });
+            }
+        }
+
+        let change = log_likelihoods.last().map(|last| (log_likelihood - last).abs());
+        log_likelihoods.push(log_likelihood);
+        if let Some(change) = change && change < options.tolerance {
+            converged = true;
+            break;
+        }
+    }
+    EmResult { parameters, pairs: vectors.len(), iterations, converged, log_likelihoods }
+}
+
+/// Estimate parameters from the candidate pairs of all persons in our DATA.
+///
+/// The initial parameters are the profile Fellegi–Sunter parameters,
+/// and the candidate pairs come from the blocking rules of the options.
+///
+pub fn train_from_data(profile: &SimilarityProfile, options: &EmOptions) -> EmResult {
+    let persons: Vec<Person> = {
+        let data = DATA.lock().unwrap();
+        data.values().cloned().collect()
+    };
+    let pairs = if options.blocking.is_empty() {
+        candidate_pairs(&persons)
+    } else {
+        blocking::candidate_pairs(&persons, &options.blocking)
+    };
+    let vectors: Vec<PerField<ComparisonLevel>> = pairs
+        .into_iter()
+        .map(|pair| comparison_vector_of_persons(pair, profile))
+        .collect();
+    train(&vectors, &profile.fellegi_sunter, options)
+}
+
+fn clamp_probability(x: f64) -> f64 {
+    x.clamp(PROBABILITY_MIN, 1.0 - PROBABILITY_MIN)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn vector(level: ComparisonLevel, phone: ComparisonLevel) -> PerField<ComparisonLevel> {
+        PerField {
+            given_name: level,
+            family_name: level,
+            birth_date_year: level,
+            birth_date_month: level,
+            birth_date_month_day: level,
+            primary_phone: phone,
+            primary_email: level,
+            nhs_number: ComparisonLevel::Missing,
+            address_lines: ComparisonLevel::Missing,
+            town: ComparisonLevel::Missing,
+            postcode: ComparisonLevel::Missing,
+        }
+    }
+
+    // Synthetic population: 100 matches that mostly agree, and 900 non-matches that mostly disagree.
+    fn vectors() -> Vec<PerField<ComparisonLevel>> {
+        let mut vectors = Vec::new();
+        for i in 0..100 {
+            vectors.push(vector(ComparisonLevel::Exact, if i % 10 == 0 { ComparisonLevel::Missing } else { ComparisonLevel::Exact }));
+        }
+        for i in 0..900 {
+            vectors.push(vector(ComparisonLevel::Far, if i % 10 == 0 { ComparisonLevel::Missing } else { ComparisonLevel::Far }));
+        }
+        vectors
+    }
+
+    #[test]
+    fn test_train() {
+        let initial = FellegiSunterParameters { prior: 0.5, ..FellegiSunterParameters::default() };
+        let result = train(&vectors(), &initial, &EmOptions::default());
+        assert_eq!(result.pairs, 1000);
+        assert!(result.converged);
+        assert_eq!(result.iterations, result.log_likelihoods.len());
+        assert!((result.parameters.prior - 0.1).abs() < 0.001);
+        assert!(result.parameters.fields.given_name.exact.m > 0.99);
+        assert!(result.parameters.fields.given_name.exact.u < 0.01);
+        assert!((result.parameters.fields.primary_phone.missing.m - 0.1).abs() < 0.001);
+        assert!(result.parameters.validate().is_ok());
+    }
+
+    #[test]
+    fn test_train_log_likelihood_does_not_decrease() {
+        let initial = FellegiSunterParameters { prior: 0.2, ..FellegiSunterParameters::default() };
+        let result = train(&vectors(), &initial, &EmOptions::default());
+        for pair in result.log_likelihoods.windows(2) {
+            assert!(pair[1] >= pair[0] - 1e-9);
+        }
+    }
+
+    #[test]
+    fn test_train_empty() {
+        let result = train(&[], &FellegiSunterParameters::default(), &EmOptions::default());
+        assert_eq!(result.iterations, 0);
+        assert_eq!(result.parameters, FellegiSunterParameters::default());
+    }
+
+    #[test]
+    fn test_candidate_pairs() {
+        let persons: Vec<Person> = DATA.lock().unwrap().values().cloned().collect();
+        assert_eq!(candidate_pairs(&persons).len(), persons.len() * (persons.len() - 1) / 2);
+    }
+
+    #[test]
+    fn test_train_from_data_with_blocking() {
+        // Every family name in our DATA differs, so exact blocking gives no pairs.
+        let options = EmOptions {
+            blocking: vec![BlockingRule { field: Field::FamilyName, encoder: None }],
+            ..EmOptions::default()
+        };
+        let result = train_from_data(&SimilarityProfile::default(), &options);
+        assert_eq!(result.pairs, 0);
+    }
+
+}
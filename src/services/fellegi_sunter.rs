@@ -0,0 +1,389 @@
+// Fellegi–Sunter probabilistic record linkage.
+//
+// Each field comparison falls into one comparison level: exact, close, far,
+// or missing. Each level has two probabilities:
+//
+// - m: the probability of the level when the two records are a match.
+//
+// - u: the probability of the level when the two records are not a match.
+//
+// The match weight of a level is log₂(m / u). The match weight of a pair
+// is the prior weight log₂(λ / (1 - λ)), where λ is the prior probability
+// that two records match, plus the sum of the field match weights.
+// The posterior match probability is 2ʷ / (1 + 2ʷ).
+//
+// See <https://en.wikipedia.org/wiki/Record_linkage#Probabilistic_record_linkage>
+
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+use crate::services::similarity::{Field, FieldReport, PerField, SimilarityReport};
+use crate::services::similarity_config::SimilarityConfigError;
+use crate::services::postcode::POSTCODE_SECTOR;
+
+/// The comparison level of one field of a pair of records.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ComparisonLevel {
+    Exact,
+    Close,
+    Far,
+    Missing,
+}
+
+impl ComparisonLevel {
+    pub const ALL: [ComparisonLevel; 4] = [
+        ComparisonLevel::Exact,
+        ComparisonLevel::Close,
+        ComparisonLevel::Far,
+        ComparisonLevel::Missing,
+    ];
+
+    /// Get the comparison level of a comparator value.
+    ///
+    /// - `None` is missing.
+    /// - 1.0 is exact.
+    /// - At least the close threshold is close.
+    /// - Anything else is far.
+    ///
+    pub fn of_value(value: Option<f64>, close_threshold: f64) -> Self {
+        match value {
+            None => ComparisonLevel::Missing,
+            Some(x) if x >= 1.0 => ComparisonLevel::Exact,
+            Some(x) if x >= close_threshold => ComparisonLevel::Close,
+            Some(_) => ComparisonLevel::Far,
+        }
+    }
+}
+
+/// The m-probability and u-probability of one comparison level.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct LevelProbabilities {
+    pub m: f64,
+    pub u: f64,
+}
+
+impl LevelProbabilities {
+    /// The match weight of the level: log₂(m / u).
+    pub fn match_weight(&self) -> f64 {
+        (self.m / self.u).log2()
+    }
+}
+
+/// The comparison levels of one field.
+///
+/// The m-probabilities of the levels should add up to 1.0,
+/// and the u-probabilities of the levels should add up to 1.0.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct FieldParameters {
+    pub close_threshold: f64,
+    pub exact: LevelProbabilities,
+    pub close: LevelProbabilities,
+    pub far: LevelProbabilities,
+    pub missing: LevelProbabilities,
+}
+
+impl FieldParameters {
+    /// Get the probabilities of one comparison level.
+    pub fn level(&self, level: ComparisonLevel) -> &LevelProbabilities {
+        match level {
+            ComparisonLevel::Exact => &self.exact,
+            ComparisonLevel::Close => &self.close,
+            ComparisonLevel::Far => &self.far,
+            ComparisonLevel::Missing => &self.missing,
+        }
+    }
+
+    /// Get the probabilities of one comparison level, for changing them.
+    pub fn level_mut(&mut self, level: ComparisonLevel) -> &mut LevelProbabilities {
+        match level {
+            ComparisonLevel::Exact => &mut self.exact,
+            ComparisonLevel::Close => &mut self.close,
+            ComparisonLevel::Far => &mut self.far,
+            ComparisonLevel::Missing => &mut self.missing,
+        }
+    }
+
+    /// Create parameters from (m, u) pairs for exact, close, far, missing.
+    const fn new(close_threshold: f64, levels: [(f64, f64); 4]) -> Self {
+        FieldParameters {
+            close_threshold,
+            exact: LevelProbabilities { m: levels[0].0, u: levels[0].1 },
+            close: LevelProbabilities { m: levels[1].0, u: levels[1].1 },
+            far: LevelProbabilities { m: levels[2].0, u: levels[2].1 },
+            missing: LevelProbabilities { m: levels[3].0, u: levels[3].1 },
+        }
+    }
+}
+
+/// The parameters of the Fellegi–Sunter model.
+///
+/// - `prior`: the prior probability that two records match, λ.
+///
+/// - `fields`: the comparison levels of each field.
+///
+/// When deserializing, any omitted item uses the value from [Default].
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FellegiSunterParameters {
+    pub prior: f64,
+    pub fields: PerField<FieldParameters>,
+}
+
+/// The default prior probability that two records match.
+pub const PRIOR: f64 = 0.0001;
+
+/// The default comparison levels of each field.
+///
+/// These are starting points for estimation, such as by expectation–maximisation,
+/// and are not estimates from any population.
+///
+/// The close level of each birth date field is a near miss, such as a year
+/// off by one or a day and month transposed, which scores from 0.6;
+/// see [crate::services::birth_date::compare_birth_dates].
+///
+impl Default for PerField<FieldParameters> {
+    fn default() -> Self {
+        let name = FieldParameters::new(0.85, [(0.85, 0.005), (0.08, 0.015), (0.02, 0.93), (0.05, 0.05)]);
+        PerField {
+            given_name: name,
+            family_name: name,
+            birth_date_year: FieldParameters::new(0.6, [(0.85, 0.02), (0.07, 0.05), (0.03, 0.88), (0.05, 0.05)]),
+            birth_date_month: FieldParameters::new(0.6, [(0.87, 0.083), (0.05, 0.01), (0.03, 0.857), (0.05, 0.05)]),
+            birth_date_month_day: FieldParameters::new(0.6, [(0.87, 0.033), (0.05, 0.005), (0.03, 0.912), (0.05, 0.05)]),
+            primary_phone: FieldParameters::new(0.9, [(0.7, 0.0001), (0.05, 0.0009), (0.05, 0.799), (0.2, 0.2)]),
+            primary_email: FieldParameters::new(0.9, [(0.7, 0.0001), (0.05, 0.0009), (0.05, 0.799), (0.2, 0.2)]),
+            nhs_number: FieldParameters::new(1.0, [(0.495, 0.0000005), (0.0001, 0.0001), (0.0049, 0.4999), (0.5, 0.5)]),
+            address_lines: FieldParameters::new(0.85, [(0.75, 0.001), (0.1, 0.009), (0.05, 0.89), (0.1, 0.1)]),
+            town: FieldParameters::new(0.9, [(0.83, 0.05), (0.02, 0.01), (0.05, 0.84), (0.1, 0.1)]),
+            postcode: FieldParameters::new(POSTCODE_SECTOR, [(0.8, 0.0001), (0.05, 0.001), (0.05, 0.8989), (0.1, 0.1)]),
+        }
+    }
+}
+
+impl Default for FellegiSunterParameters {
+    fn default() -> Self {
+        FellegiSunterParameters {
+            prior: PRIOR,
+            fields: PerField::default(),
+        }
+    }
+}
+
+impl FellegiSunterParameters {
+
+    /// Validate the parameters.
+    ///
+    /// - The prior must be more than 0.0 and less than 1.0.
+    ///
+    /// - Each close threshold must be from 0.0 to 1.0.
+    ///
+    /// - Each m-probability and u-probability must be more than 0.0 and at most 1.0,
+    ///   so that every match weight is finite.
+    ///
+    pub fn validate(&self) -> Result<(), String> {
+        if !(self.prior > 0.0 && self.prior < 1.0) {
+            return Err(format!("prior {} must be more than 0 and less than 1", self.prior));
+        }
+        for field in Field::ALL {
+            let parameters = self.fields.get(field);
+            if !(0.0..=1.0).contains(&parameters.close_threshold) {
+                return Err(format!("field {:?} close threshold must be from 0 to 1", field));
+            }
+            for level in ComparisonLevel::ALL {
+                let p = parameters.level(level);
+                if !(p.m > 0.0 && p.m <= 1.0 && p.u > 0.0 && p.u <= 1.0) {
+                    return Err(format!("field {:?} level {:?} m and u must be more than 0 and at most 1", field, level));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Load parameters from a TOML file or JSON file, then validate them.
+    ///
+    /// The file format is chosen by the file extension: ".toml" or ".json".
+    /// This is the format that [FellegiSunterParameters::to_path] writes.
+    ///
+    pub fn from_path(path: &Path) -> Result<Self, SimilarityConfigError> {
+        let text = std::fs::read_to_string(path).map_err(SimilarityConfigError::Io)?;
+        let parameters: FellegiSunterParameters = match path.extension().and_then(|x| x.to_str()) {
+            Some("toml") => toml::from_str(&text).map_err(|e| SimilarityConfigError::Parse(e.to_string()))?,
+            Some("json") => serde_json::from_str(&text).map_err(|e| SimilarityConfigError::Parse(e.to_string()))?,
+            _ => return Err(SimilarityConfigError::Parse(format!("unknown file extension for {}; expected .toml or .json", path.display()))),
+        };
+        parameters.validate().map_err(SimilarityConfigError::Invalid)?;
+        Ok(parameters)
+    }
+
+    /// Save parameters to a TOML file or JSON file.
+    ///
+    /// The file format is chosen by the file extension: ".toml" or ".json".
+    ///
+    pub fn to_path(&self, path: &Path) -> Result<(), SimilarityConfigError> {
+        let text = match path.extension().and_then(|x| x.to_str()) {
+            Some("toml") => toml::to_string_pretty(self).map_err(|e| SimilarityConfigError::Parse(e.to_string()))?,
+            Some("json") => serde_json::to_string_pretty(self).map_err(|e| SimilarityConfigError::Parse(e.to_string()))?,
+            _ => return Err(SimilarityConfigError::Parse(format!("unknown file extension for {}; expected .toml or .json", path.display()))),
+        };
+        std::fs::write(path, text).map_err(SimilarityConfigError::Io)
+    }
+
+    /// The prior match weight: log₂(λ / (1 - λ)).
+    pub fn prior_weight(&self) -> f64 {
+        (self.prior / (1.0 - self.prior)).log2()
+    }
+
+}
+
+/// Convert a match weight to a posterior match probability.
+pub fn probability_of_match_weight(match_weight: f64) -> f64 {
+    1.0 / (1.0 + (-match_weight).exp2())
+}
+
+/// Combine sub-scores into a Fellegi–Sunter match weight and match probability.
+///
+/// The report score is the posterior match probability. Each field weight is
+/// the match weight of its comparison level, and the field contributions add
+/// up to the match weight minus the prior weight.
+///
+/// The returned report has a blank profile name and version.
+///
+pub fn similarity_report_of_sub_scores(sub_scores: &PerField<Option<f64>>, parameters: &FellegiSunterParameters) -> SimilarityReport {
+    let mut match_weight = parameters.prior_weight();
+    let fields = Field::ALL.iter().map(|&field| {
+        let value = *sub_scores.get(field);
+        let field_parameters = parameters.fields.get(field);
+        let level = ComparisonLevel::of_value(value, field_parameters.close_threshold);
+        let weight = field_parameters.level(level).match_weight();
+        match_weight += weight;
+        FieldReport {
+            field,
+            present: value.is_some(),
+            value,
+            weight,
+            contribution: weight,
+            level: Some(level),
+            note: None,
+            values: None,
+        }
+    }).collect();
+    SimilarityReport {
+        score: probability_of_match_weight(match_weight),
+        match_weight: Some(match_weight),
+        fields,
+        ..SimilarityReport::default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::birth_date::compare_birth_dates;
+
+    #[test]
+    fn test_comparison_level() {
+        assert_eq!(ComparisonLevel::of_value(None, 0.9), ComparisonLevel::Missing);
+        assert_eq!(ComparisonLevel::of_value(Some(1.0), 0.9), ComparisonLevel::Exact);
+        assert_eq!(ComparisonLevel::of_value(Some(0.95), 0.9), ComparisonLevel::Close);
+        assert_eq!(ComparisonLevel::of_value(Some(0.5), 0.9), ComparisonLevel::Far);
+    }
+
+    #[test]
+    fn test_default_is_valid() {
+        assert!(FellegiSunterParameters::default().validate().is_ok());
+    }
+
+    #[test]
+    fn test_default_probabilities_add_up() {
+        let parameters = FellegiSunterParameters::default();
+        for field in Field::ALL {
+            let f = parameters.fields.get(field);
+            let m: f64 = ComparisonLevel::ALL.iter().map(|&level| f.level(level).m).sum();
+            let u: f64 = ComparisonLevel::ALL.iter().map(|&level| f.level(level).u).sum();
+            assert!((m - 1.0).abs() < 0.001, "{:?} m {}", field, m);
+            assert!((u - 1.0).abs() < 0.001, "{:?} u {}", field, u);
+        }
+    }
+
+    #[test]
+    fn test_invalid() {
+        let mut parameters = FellegiSunterParameters { prior: 1.0, ..FellegiSunterParameters::default() };
+        assert!(parameters.validate().is_err());
+        parameters.prior = 0.5;
+        parameters.fields.given_name.exact.u = 0.0;
+        assert!(parameters.validate().is_err());
+    }
+
+    #[test]
+    fn test_to_path_from_path() {
+        let parameters = FellegiSunterParameters { prior: 0.02, ..FellegiSunterParameters::default() };
+        for extension in ["toml", "json"] {
+            let path = std::env::temp_dir().join(format!("fellegi_sunter_test_{}.{}", std::process::id(), extension));
+            parameters.to_path(&path).unwrap();
+            let loaded = FellegiSunterParameters::from_path(&path).unwrap();
+            std::fs::remove_file(&path).unwrap();
+            assert_eq!(loaded, parameters);
+        }
+    }
+
+    #[test]
+    fn test_probability_of_match_weight() {
+        assert_eq!(probability_of_match_weight(0.0), 0.5);
+        assert_eq!(probability_of_match_weight(1.0), 2.0 / 3.0);
+        assert!(probability_of_match_weight(-20.0) < 0.000001);
+    }
+
+    #[test]
+    fn test_similarity_report_of_sub_scores() {
+        let parameters = FellegiSunterParameters { prior: 0.5, ..FellegiSunterParameters::default() };
+        let sub_scores = PerField {
+            given_name: Some(1.0),
+            family_name: Some(0.9),
+            birth_date_year: Some(0.0),
+            birth_date_month: None,
+            birth_date_month_day: None,
+            primary_phone: None,
+            primary_email: None,
+            nhs_number: None,
+            address_lines: None,
+            town: None,
+            postcode: None,
+        };
+        let report = similarity_report_of_sub_scores(&sub_scores, &parameters);
+        let expect = (0.85f64 / 0.005).log2() + (0.08f64 / 0.015).log2() + (0.03f64 / 0.88).log2();
+        assert!((report.match_weight.unwrap() - expect).abs() < 1e-9);
+        assert_eq!(report.score, probability_of_match_weight(report.match_weight.unwrap()));
+        assert_eq!(report.fields[0].level, Some(ComparisonLevel::Exact));
+        assert_eq!(report.fields[1].level, Some(ComparisonLevel::Close));
+        assert_eq!(report.fields[2].level, Some(ComparisonLevel::Far));
+        assert_eq!(report.fields[3].level, Some(ComparisonLevel::Missing));
+        assert_eq!(report.fields[3].weight, 0.0);
+    }
+
+    #[test]
+    fn test_birth_date_near_miss() {
+        let parameters = FellegiSunterParameters::default();
+        let match_weight = |a: &str, b: &str| {
+            let comparison = compare_birth_dates(&a.parse().unwrap(), &b.parse().unwrap());
+            let sub_scores = PerField {
+                birth_date_year: comparison.year,
+                birth_date_month: comparison.month,
+                birth_date_month_day: comparison.month_day,
+                ..PerField::default()
+            };
+            similarity_report_of_sub_scores(&sub_scores, &parameters).match_weight.unwrap()
+        };
+        let exact = match_weight("1990-03-04", "1990-03-04");
+        let transposed = match_weight("1990-03-04", "1990-04-03");
+        let year_off_by_one = match_weight("1990-03-04", "1991-03-04");
+        let far = match_weight("1990-03-04", "1990-05-20");
+        assert!(exact > transposed && transposed > far, "{} {} {}", exact, transposed, far);
+        assert!(exact > year_off_by_one && year_off_by_one > far, "{} {} {}", exact, year_off_by_one, far);
+        assert!(transposed > parameters.prior_weight());
+    }
+
+}
@@ -0,0 +1,358 @@
+// Parse free-text names into components.
+//
+// Source systems give us names such as "Dr. Alice M. Adams Jr" in one field,
+// or titles mixed into the given name. The parser splits a full name into:
+//
+// - Title: leading words such as "Dr", "Mrs", or "Parch" (Welsh "Reverend").
+//
+// - Given name: the first remaining word.
+//
+// - Middle names: the words between the given name and the family name,
+//   which can be initials such as "M.".
+//
+// - Family name: the last remaining word, with any particles before it,
+//   such as "ap Hywel", "ferch Rhys", or "van der Berg".
+//
+// - Suffix: trailing generational words such as "Jr", "Sr", or "III".
+//
+// Similarity uses the parts too: an initial is compatible with a full given
+// name, such as "A." and "Alice", and suffixes that differ, such as "Jr" and
+// "Sr", are evidence of different people; see [NameParts].
+
+use serde::{Deserialize, Serialize};
+use crate::models::person::Person;
+
+/// Titles, lowercase and without dots.
+pub const TITLES: [&str; 20] = [
+    "mr", "mrs", "ms", "miss", "mx", "master", "dr", "doctor", "prof", "professor",
+    "rev", "revd", "reverend", "sir", "dame", "lady", "lord", "parch", "capt", "fr",
+];
+
+/// Generational suffixes, lowercase and without dots, each with its canonical form.
+pub const SUFFIXES: [(&str, &str); 9] = [
+    ("jr", "Jr"),
+    ("jnr", "Jr"),
+    ("junior", "Jr"),
+    ("sr", "Sr"),
+    ("snr", "Sr"),
+    ("senior", "Sr"),
+    ("ii", "II"),
+    ("iii", "III"),
+    ("iv", "IV"),
+];
+
+/// Particles that start a family name, lowercase.
+pub const FAMILY_NAME_PARTICLES: [&str; 15] = [
+    "ap", "ab", "ferch", "verch", "van", "von", "der", "den", "de", "da", "di", "du", "del", "le", "la",
+];
+
+/// The least given name sub-score when one given name is the initial of the other, by default.
+pub const INITIAL_CREDIT: f64 = 0.8;
+
+/// The factor of the odds of the score when the suffixes differ, by default.
+pub const SUFFIX_DISAGREEMENT: f64 = 0.1;
+
+/// The least and most score before adjusting its odds, so that a score of
+/// 0.0 or 1.0 still moves.
+pub const SUFFIX_SCORE_CLAMP: (f64, f64) = (0.01, 0.99);
+
+/// The components of a full name.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ParsedName {
+    pub title: Option<String>,
+    pub given_name: Option<String>,
+    pub middle_names: Vec<String>,
+    pub family_name: Option<String>,
+    pub suffix: Option<String>,
+}
+
+// The lookup key of a word: lowercase, without dots and commas.
+fn key(word: &str) -> String {
+    word.chars().filter(|c| *c != '.' && *c != ',').collect::<String>().to_lowercase()
+}
+
+// Remove leading titles from the words, and return them joined.
+fn take_titles(words: &mut Vec<&str>) -> Option<String> {
+    let count = words.iter().take_while(|word| TITLES.contains(&key(word).as_str())).count();
+    // A lone word is a name, not a title, such as the family name "Lord".
+    let count = if count == words.len() { count.saturating_sub(1) } else { count };
+    let titles: Vec<&str> = words.drain(..count).collect();
+    (!titles.is_empty()).then(|| titles.join(" "))
+}
+
+// Remove trailing suffixes from the words, and return the last one in canonical form.
+fn take_suffix(words: &mut Vec<&str>) -> Option<String> {
+    let mut suffix = None;
+    while words.len() > 1 && let Some(canonical) = canonical_suffix(words[words.len() - 1]) {
+        suffix = suffix.or(Some(canonical.to_string()));
+        words.pop();
+    }
+    suffix
+}
+
+/// Get the canonical form of a suffix, such as "Jr" for "jnr." or "Junior".
+pub fn canonical_suffix(word: &str) -> Option<&'static str> {
+    let key = key(word);
+    SUFFIXES.iter().find(|(x, _)| *x == key).map(|(_, canonical)| *canonical)
+}
+
+// Trim trailing commas and surrounding whitespace from a word.
+fn clean(word: &str) -> &str {
+    word.trim_end_matches(',').trim()
+}
+
+/// Parse a free-text full name.
+///
+/// A comma after the first word means the family name comes first,
+/// such as "Adams, Alice M.". A single word is a family name.
+///
+pub fn parse_name(full_name: &str) -> ParsedName {
+    let (family_first, full_name) = match full_name.split_once(',') {
+        Some((family, rest)) if !family.trim().contains(' ') && canonical_suffix(rest.trim()).is_none() => (Some(family.trim()), rest),
+        _ => (None, full_name),
+    };
+    let mut words: Vec<&str> = full_name.split_whitespace().map(clean).filter(|word| !word.is_empty()).collect();
+    let title = take_titles(&mut words);
+    let suffix = if family_first.is_some() && words.len() == 1 { None } else { take_suffix(&mut words) };
+    let family_name = match family_first {
+        Some(family) => Some(family.to_string()),
+        None if words.is_empty() => None,
+        None => {
+            // Particles before the last word belong to the family name,
+            // but the first word is always the given name.
+            let mut start = words.len() - 1;
+            while start > 1 && FAMILY_NAME_PARTICLES.contains(&key(words[start - 1]).as_str()) {
+                start -= 1;
+            }
+            let family = words.split_off(start).join(" ");
+            Some(family)
+        }
+    };
+    let given_name = if words.is_empty() { None } else { Some(words.remove(0).to_string()) };
+    ParsedName {
+        title,
+        given_name,
+        middle_names: words.into_iter().map(String::from).collect(),
+        family_name,
+        suffix,
+    }
+}
+
+/// Parse the name fields of a person, and return a copy with each component in its field.
+///
+/// When the person has a family name, the given name field loses any titles,
+/// and any words after the first become middle names; the family name field
+/// loses any suffixes. Otherwise, the given name field is parsed as a full name.
+/// Components that the person already has are kept.
+///
+pub fn parse_person_names(person: &Person) -> Person {
+    let given = person.given_name.as_deref().unwrap_or("");
+    let parsed = match person.family_name.as_deref() {
+        Some(family) if !family.trim().is_empty() => {
+            let mut given_words: Vec<&str> = given.split_whitespace().map(clean).filter(|word| !word.is_empty()).collect();
+            let title = take_titles(&mut given_words);
+            let mut family_words: Vec<&str> = family.split_whitespace().map(clean).filter(|word| !word.is_empty()).collect();
+            let suffix = take_suffix(&mut family_words);
+            let given_name = if given_words.is_empty() { None } else { Some(given_words.remove(0).to_string()) };
+            ParsedName {
+                title,
+                given_name,
+                middle_names: given_words.into_iter().map(String::from).collect(),
+                family_name: Some(family_words.join(" ")),
+                suffix,
+            }
+        }
+        _ => parse_name(given),
+    };
+    let middle_names = (!parsed.middle_names.is_empty()).then(|| parsed.middle_names.join(" "));
+    Person {
+        title: person.title.clone().or(parsed.title),
+        given_name: parsed.given_name.or(person.given_name.clone().filter(|x| !x.trim().is_empty())),
+        middle_names: person.middle_names.clone().or(middle_names),
+        family_name: parsed.family_name.or(person.family_name.clone()),
+        suffix: person.suffix.clone().or(parsed.suffix),
+        ..person.clone()
+    }
+}
+
+/// True when the name is an initial, such as "A" or "A.".
+pub fn is_initial(name: &str) -> bool {
+    let mut letters = name.trim().trim_end_matches('.').chars();
+    matches!((letters.next(), letters.next()), (Some(c), None) if c.is_alphabetic())
+}
+
+/// True when one name is an initial and the other name starts with it,
+/// such as "A." and "Alice".
+pub fn initial_agrees(a: &str, b: &str) -> bool {
+    let first = |name: &str| name.trim().chars().next().map(|c| c.to_lowercase().collect::<String>());
+    (is_initial(a) || is_initial(b)) && first(a).is_some() && first(a) == first(b)
+}
+
+/// Get the suffix of a person: the suffix field, else any trailing suffix of the family name.
+pub fn suffix_of_person(person: &Person) -> Option<&'static str> {
+    match person.suffix.as_deref() {
+        Some(suffix) if !suffix.trim().is_empty() => canonical_suffix(suffix.trim()),
+        _ => {
+            let words: Vec<&str> = person.family_name.as_deref()?.split_whitespace().collect();
+            if words.len() < 2 {
+                return None;
+            }
+            canonical_suffix(words[words.len() - 1])
+        }
+    }
+}
+
+/// Settings of the name components in similarity.
+///
+/// - `initial_credit`: the least given name sub-score when one given name
+///   is the initial of the other, such as "A." and "Alice"; 0.0 turns it off.
+///
+/// - `suffix_disagreement`: the factor of the odds of the score when both
+///   persons have suffixes that differ, such as "Jr" and "Sr", which must be
+///   more than 0.0 and at most 1.0; 1.0 turns it off.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NameParts {
+    pub initial_credit: f64,
+    pub suffix_disagreement: f64,
+}
+
+impl Default for NameParts {
+    fn default() -> Self {
+        NameParts {
+            initial_credit: INITIAL_CREDIT,
+            suffix_disagreement: SUFFIX_DISAGREEMENT,
+        }
+    }
+}
+
+impl NameParts {
+
+    /// Apply the initial credit to a given name sub-score of two names.
+    pub fn apply_initial(&self, value: f64, a: &str, b: &str) -> f64 {
+        if value < self.initial_credit && initial_agrees(a, b) {
+            self.initial_credit
+        } else {
+            value
+        }
+    }
+
+    /// True when both persons have suffixes, and the suffixes differ.
+    pub fn suffixes_disagree(&self, a: &Person, b: &Person) -> bool {
+        matches!((suffix_of_person(a), suffix_of_person(b)), (Some(x), Some(y)) if x != y)
+    }
+
+    /// Adjust a score when the suffixes of the persons disagree.
+    ///
+    /// The odds of the score, score / (1 - score), are multiplied by the
+    /// suffix disagreement factor. The score is first clamped to
+    /// [SUFFIX_SCORE_CLAMP], because a score of 0.0 or 1.0 has no odds.
+    ///
+    pub fn apply_suffix(&self, score: f64, a: &Person, b: &Person) -> f64 {
+        if !self.suffixes_disagree(a, b) {
+            return score;
+        }
+        let score = score.clamp(SUFFIX_SCORE_CLAMP.0, SUFFIX_SCORE_CLAMP.1);
+        let odds = score / (1.0 - score) * self.suffix_disagreement;
+        odds / (1.0 + odds)
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parsed(title: Option<&str>, given_name: Option<&str>, middle_names: &[&str], family_name: Option<&str>, suffix: Option<&str>) -> ParsedName {
+        ParsedName {
+            title: title.map(String::from),
+            given_name: given_name.map(String::from),
+            middle_names: middle_names.iter().map(|x| x.to_string()).collect(),
+            family_name: family_name.map(String::from),
+            suffix: suffix.map(String::from),
+        }
+    }
+
+    fn person(given_name: Option<&str>, family_name: Option<&str>) -> Person {
+        Person {
+            id: String::from("0"),
+            nhs_number: None,
+            title: None,
+            given_name: given_name.map(String::from),
+            middle_names: None,
+            family_name: family_name.map(String::from),
+            suffix: None,
+            other_names: Vec::new(),
+            birth_date: None,
+            primary_email: None,
+            other_emails: Vec::new(),
+            primary_phone: None,
+            other_phones: Vec::new(),
+            address: None,
+            note: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_name() {
+        assert_eq!(parse_name("Dr. Alice M. Adams Jr"), parsed(Some("Dr."), Some("Alice"), &["M."], Some("Adams"), Some("Jr")));
+        assert_eq!(parse_name("Alice Adams"), parsed(None, Some("Alice"), &[], Some("Adams"), None));
+        assert_eq!(parse_name("Prof Sir Tom Jones, III"), parsed(Some("Prof Sir"), Some("Tom"), &[], Some("Jones"), Some("III")));
+        assert_eq!(parse_name("Dafydd ap Hywel"), parsed(None, Some("Dafydd"), &[], Some("ap Hywel"), None));
+        assert_eq!(parse_name("Mary Jane van der Berg"), parsed(None, Some("Mary"), &["Jane"], Some("van der Berg"), None));
+        assert_eq!(parse_name("Adams, Alice M."), parsed(None, Some("Alice"), &["M."], Some("Adams"), None));
+        assert_eq!(parse_name("Adams"), parsed(None, None, &[], Some("Adams"), None));
+        assert_eq!(parse_name("Lord"), parsed(None, None, &[], Some("Lord"), None));
+        assert_eq!(parse_name("  "), ParsedName::default());
+    }
+
+    #[test]
+    fn test_parse_person_names() {
+        let parsed = parse_person_names(&person(Some("Dr. Alice M. Adams Jr"), None));
+        assert_eq!(parsed.title.as_deref(), Some("Dr."));
+        assert_eq!(parsed.given_name.as_deref(), Some("Alice"));
+        assert_eq!(parsed.middle_names.as_deref(), Some("M."));
+        assert_eq!(parsed.family_name.as_deref(), Some("Adams"));
+        assert_eq!(parsed.suffix.as_deref(), Some("Jr"));
+        let parsed = parse_person_names(&person(Some("Mrs Mary Jane"), Some("Rhys Jones Snr")));
+        assert_eq!(parsed.title.as_deref(), Some("Mrs"));
+        assert_eq!(parsed.given_name.as_deref(), Some("Mary"));
+        assert_eq!(parsed.middle_names.as_deref(), Some("Jane"));
+        assert_eq!(parsed.family_name.as_deref(), Some("Rhys Jones"));
+        assert_eq!(parsed.suffix.as_deref(), Some("Sr"));
+        let plain = person(Some("Alice"), Some("Adams"));
+        assert_eq!(parse_person_names(&plain), plain);
+    }
+
+    #[test]
+    fn test_initial() {
+        assert!(is_initial("A."));
+        assert!(is_initial("a"));
+        assert!(!is_initial("Al"));
+        assert!(!is_initial(""));
+        assert!(initial_agrees("A.", "Alice"));
+        assert!(initial_agrees("alice", "A"));
+        assert!(!initial_agrees("B.", "Alice"));
+        assert!(!initial_agrees("Alice", "Alison"));
+        let parts = NameParts::default();
+        assert_eq!(parts.apply_initial(0.3, "A.", "Alice"), INITIAL_CREDIT);
+        assert_eq!(parts.apply_initial(0.3, "B.", "Alice"), 0.3);
+    }
+
+    #[test]
+    fn test_suffix() {
+        let (junior, senior, plain) = (person(None, Some("Adams Jr.")), Person { suffix: Some(String::from("Senior")), ..person(None, Some("Adams")) }, person(None, Some("Adams")));
+        assert_eq!(suffix_of_person(&junior), Some("Jr"));
+        assert_eq!(suffix_of_person(&senior), Some("Sr"));
+        assert_eq!(suffix_of_person(&plain), None);
+        let parts = NameParts::default();
+        assert!(parts.suffixes_disagree(&junior, &senior));
+        assert!(!parts.suffixes_disagree(&junior, &plain));
+        assert!(!parts.suffixes_disagree(&junior, &junior));
+        assert!((parts.apply_suffix(0.5, &junior, &senior) - 0.1 / 1.1).abs() < 1e-12);
+        assert_eq!(parts.apply_suffix(0.5, &junior, &plain), 0.5);
+        assert!(parts.apply_suffix(1.0, &junior, &senior) < 0.95);
+    }
+
+}
@@ -0,0 +1,86 @@
+// Detection of given and family names that are swapped.
+//
+// Registration clerks often enter the given name as the family name and the
+// family name as the given name, such as "Thomas Owen" and "Owen Thomas".
+// Comparing given to given and family to family scores such a pair low.
+//
+// The detection also compares each given name to the other family name.
+// When this swapped pairing scores much higher than the straight pairing,
+// the names are probably swapped, so the given name and family name each
+// get credit for their swapped sub-score.
+
+use serde::{Deserialize, Serialize};
+
+/// The credit of a swapped name, by default.
+pub const NAME_SWAP_CREDIT: f64 = 0.9;
+
+/// How much higher the swapped pairing must score, by default.
+pub const NAME_SWAP_MARGIN: f64 = 0.3;
+
+/// Settings of name swap detection.
+///
+/// - `credit`: each name sub-score is at least the credit times its swapped
+///   sub-score, when the names are swapped; 0.0 turns detection off.
+///
+/// - `margin`: the names are swapped when the average swapped sub-score is
+///   at least the margin more than the average straight sub-score.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NameSwap {
+    pub credit: f64,
+    pub margin: f64,
+}
+
+impl Default for NameSwap {
+    fn default() -> Self {
+        NameSwap {
+            credit: NAME_SWAP_CREDIT,
+            margin: NAME_SWAP_MARGIN,
+        }
+    }
+}
+
+impl NameSwap {
+
+    /// True when the swapped pairing scores much higher than the straight pairing.
+    ///
+    /// Each pairing is (given name sub-score, family name sub-score).
+    ///
+    pub fn is_swapped(&self, straight: (f64, f64), swapped: (f64, f64)) -> bool {
+        self.credit > 0.0 && (swapped.0 + swapped.1) / 2.0 - (straight.0 + straight.1) / 2.0 >= self.margin
+    }
+
+    /// Credit the straight pairing when the names are swapped, else `None`.
+    pub fn apply(&self, straight: (f64, f64), swapped: (f64, f64)) -> Option<(f64, f64)> {
+        if !self.is_swapped(straight, swapped) {
+            return None;
+        }
+        Some((
+            straight.0.max(self.credit * swapped.0),
+            straight.1.max(self.credit * swapped.1),
+        ))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_apply() {
+        let swap = NameSwap::default();
+        assert_eq!(swap.apply((0.5, 0.4), (1.0, 1.0)), Some((NAME_SWAP_CREDIT, NAME_SWAP_CREDIT)));
+        assert_eq!(swap.apply((0.5, 0.4), (1.0, 0.8)), Some((NAME_SWAP_CREDIT, 0.8 * NAME_SWAP_CREDIT)));
+        assert_eq!(swap.apply((0.9, 0.4), (0.9, 0.5)), None);
+        assert_eq!(swap.apply((1.0, 1.0), (1.0, 1.0)), None);
+    }
+
+    #[test]
+    fn test_off() {
+        let swap = NameSwap { credit: 0.0, ..NameSwap::default() };
+        assert_eq!(swap.apply((0.0, 0.0), (1.0, 1.0)), None);
+    }
+
+}
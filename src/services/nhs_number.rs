@@ -0,0 +1,137 @@
+// Comparison of NHS numbers, the main identifier of a person in our domain.
+//
+// Two valid NHS numbers that agree are very strong evidence of a match, and
+// two valid NHS numbers that disagree are strong evidence against a match.
+// Either way the other fields still count, because NHS numbers are sometimes
+// entered for the wrong person.
+//
+// In Fellegi–Sunter mode, the NHS number is a field like any other, whose
+// default parameters give a high match weight for agreement and a low match
+// weight for disagreement. In weighted mode, the NHS number has no weight in
+// the weighted average, so persons without NHS numbers score as before;
+// instead, an agreement or disagreement multiplies the odds of the score.
+
+use serde::{Deserialize, Serialize};
+use similarity_trait::Similarity;
+use crate::models::nhs_number::NhsNumber;
+
+/// The factor of the odds of the weighted score when NHS numbers agree, by default.
+pub const NHS_NUMBER_AGREEMENT: f64 = 1000.0;
+
+/// The factor of the odds of the weighted score when NHS numbers disagree, by default.
+pub const NHS_NUMBER_DISAGREEMENT: f64 = 0.01;
+
+/// The least and most score before adjusting its odds, so that a score of
+/// 0.0 or 1.0 still moves.
+pub const NHS_NUMBER_SCORE_CLAMP: (f64, f64) = (0.01, 0.99);
+
+/// Settings of the NHS number evidence.
+///
+/// - `agreement`: the factor of the odds of the weighted score when the
+///   numbers agree, which must be at least 1.0.
+///
+/// - `disagreement`: the factor of the odds of the weighted score when the
+///   numbers disagree, which must be more than 0.0 and at most 1.0.
+///
+/// - `reject_test`: treat numbers in the range reserved for testing as
+///   missing; see [NhsNumber::is_test].
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct NhsNumberEvidence {
+    pub agreement: f64,
+    pub disagreement: f64,
+    pub reject_test: bool,
+}
+
+impl Default for NhsNumberEvidence {
+    fn default() -> Self {
+        NhsNumberEvidence {
+            agreement: NHS_NUMBER_AGREEMENT,
+            disagreement: NHS_NUMBER_DISAGREEMENT,
+            reject_test: false,
+        }
+    }
+}
+
+impl NhsNumberEvidence {
+
+    /// Compare two NHS numbers: 1.0 when they agree, 0.0 when they disagree,
+    /// or `None` when either is missing or rejected as a test number.
+    pub fn compare(&self, a: Option<&NhsNumber>, b: Option<&NhsNumber>) -> Option<f64> {
+        let rejected = |x: &NhsNumber| self.reject_test && x.is_test();
+        let (a, b) = (a?, b?);
+        if rejected(a) || rejected(b) {
+            return None;
+        }
+        Some(if a == b { 1.0 } else { 0.0 })
+    }
+
+    /// Adjust a weighted score by the NHS number sub-score, if any.
+    ///
+    /// The odds of the score, score / (1 - score), are multiplied by the
+    /// agreement factor for a sub-score of 1.0, or by the disagreement
+    /// factor for a sub-score of 0.0. The score is first clamped to
+    /// [NHS_NUMBER_SCORE_CLAMP], because a score of 0.0 or 1.0 has no odds.
+    ///
+    pub fn apply(&self, score: f64, sub_score: Option<f64>) -> f64 {
+        let factor = match sub_score {
+            Some(x) if x >= 1.0 => self.agreement,
+            Some(_) => self.disagreement,
+            None => return score,
+        };
+        let score = score.clamp(NHS_NUMBER_SCORE_CLAMP.0, NHS_NUMBER_SCORE_CLAMP.1);
+        let odds = score / (1.0 - score) * factor;
+        odds / (1.0 + odds)
+    }
+
+}
+
+impl Similarity<(&NhsNumber, &NhsNumber), f64> for NhsNumber {
+    /// 1.0 when the numbers agree, otherwise 0.0; see [NhsNumberEvidence::compare].
+    fn similarity(input: (&NhsNumber, &NhsNumber)) -> f64 {
+        NhsNumberEvidence::default().compare(Some(input.0), Some(input.1)).unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn number(s: &str) -> NhsNumber {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_compare() {
+        let evidence = NhsNumberEvidence::default();
+        let (a, b, test) = (number("943 476 5919"), number("401 023 2137"), number("999 000 0018"));
+        assert_eq!(evidence.compare(Some(&a), Some(&a)), Some(1.0));
+        assert_eq!(evidence.compare(Some(&a), Some(&b)), Some(0.0));
+        assert_eq!(evidence.compare(Some(&a), None), None);
+        assert_eq!(evidence.compare(Some(&test), Some(&test)), Some(1.0));
+        let evidence = NhsNumberEvidence { reject_test: true, ..NhsNumberEvidence::default() };
+        assert_eq!(evidence.compare(Some(&test), Some(&test)), None);
+        assert_eq!(evidence.compare(Some(&a), Some(&a)), Some(1.0));
+    }
+
+    #[test]
+    fn test_apply() {
+        let evidence = NhsNumberEvidence::default();
+        assert_eq!(evidence.apply(0.5, None), 0.5);
+        assert!((evidence.apply(0.5, Some(1.0)) - 1000.0 / 1001.0).abs() < 1e-12);
+        assert!((evidence.apply(0.5, Some(0.0)) - 0.01 / 1.01).abs() < 1e-12);
+        assert!(evidence.apply(0.1, Some(1.0)) > 0.99);
+        assert!(evidence.apply(0.95, Some(0.0)) < 0.2);
+        assert!(evidence.apply(1.0, Some(0.0)) < 0.5);
+        assert!(evidence.apply(0.0, Some(1.0)) > 0.9);
+    }
+
+    #[test]
+    fn test_similarity_trait() {
+        let (a, b) = (number("943 476 5919"), number("401 023 2137"));
+        assert_eq!(NhsNumber::similarity((&a, &a)), 1.0);
+        assert_eq!(NhsNumber::similarity((&a, &b)), 0.0);
+    }
+
+}
@@ -0,0 +1,192 @@
+// Nickname and diminutive equivalences for given names.
+//
+// "Bob" and "Robert" are the same given name, as are "Dai" and "Dafydd",
+// but edit distance scores them low. The dictionary records groups of
+// equivalent names, and the given name comparison treats any two names
+// in the same group as near-exact; see [crate::services::similarity_config::SimilarityProfile].
+//
+// The bundled dictionary covers common English and Welsh forms, from the
+// file "config/nicknames.csv". Add more groups at startup with the
+// environment variable `NICKNAMES_CSV`, which names a CSV file of the same format.
+
+// Use LazyLock for creating a thread-safe global variable e.g. our NICKNAMES.
+use std::sync::LazyLock;
+
+// Use RwLock because the dictionary is read by every comparison and written rarely.
+use std::sync::RwLock;
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+use serde::{Deserialize, Serialize};
+
+/// The similarity of two given names that are nickname equivalents, by default.
+pub const NICKNAME_EQUIVALENT: f64 = 0.95;
+
+/// The least sub-score of a field when the values are nickname equivalents.
+///
+/// In a profile, this is a number, such as `given_name = 0.9`.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct NicknameCredit(pub f64);
+
+/// The bundled dictionary, as CSV.
+pub const BUNDLED_CSV: &str = include_str!("../../config/nicknames.csv");
+
+/// A dictionary of groups of equivalent given names.
+///
+/// The CSV format is one group per line, with names separated by commas.
+/// Lines that are blank or start with "#" are ignored.
+///
+/// ```csv
+/// Robert,Bob,Bobby,Rob
+/// Dafydd,David,Dai,Dewi
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct NicknameDictionary {
+    groups: HashMap<String, BTreeSet<usize>>,
+    count: usize,
+}
+
+impl NicknameDictionary {
+
+    /// Get the bundled dictionary.
+    pub fn bundled() -> Self {
+        let mut dictionary = NicknameDictionary::default();
+        dictionary.extend_from_csv(BUNDLED_CSV).expect("bundled nicknames CSV");
+        dictionary
+    }
+
+    /// Add one group of equivalent names.
+    ///
+    /// A group needs at least two names; blank names are ignored.
+    ///
+    pub fn add_group<S: AsRef<str>>(&mut self, names: &[S]) -> Result<(), String> {
+        let names: BTreeSet<String> = names.iter()
+            .map(|name| key(name.as_ref()))
+            .filter(|name| !name.is_empty())
+            .collect();
+        if names.len() < 2 {
+            return Err(format!("a group needs at least two names: {:?}", names));
+        }
+        for name in names {
+            self.groups.entry(name).or_default().insert(self.count);
+        }
+        self.count += 1;
+        Ok(())
+    }
+
+    /// Add groups from CSV text.
+    pub fn extend_from_csv(&mut self, text: &str) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let names: Vec<&str> = line.split(',').collect();
+            self.add_group(&names).map_err(|e| format!("line {}: {}", i + 1, e))?;
+        }
+        Ok(())
+    }
+
+    /// Add groups from a CSV file.
+    pub fn extend_from_path(&mut self, path: &Path) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.extend_from_csv(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// The number of groups.
+    pub fn len(&self) -> usize {
+        self.count
+    }
+
+    /// True when there are no groups.
+    pub fn is_empty(&self) -> bool {
+        self.count == 0
+    }
+
+    /// True when two different names share a group.
+    pub fn are_equivalent(&self, a: &str, b: &str) -> bool {
+        let (a, b) = (key(a), key(b));
+        if a == b {
+            return false;
+        }
+        match (self.groups.get(&a), self.groups.get(&b)) {
+            (Some(a), Some(b)) => !a.is_disjoint(b),
+            _ => false,
+        }
+    }
+
+}
+
+// The dictionary key of a name: trimmed and lowercase.
+fn key(name: &str) -> String {
+    name.trim().to_lowercase()
+}
+
+// Create the nickname dictionary as a global variable with `LazyLock` and `RwLock`.
+//
+// This starts as the bundled dictionary; `main` extends it at startup
+// when the environment variable `NICKNAMES_CSV` names a CSV file.
+
+pub static NICKNAMES: LazyLock<RwLock<NicknameDictionary>> = LazyLock::new(|| {
+    RwLock::new(NicknameDictionary::bundled())
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled() {
+        let dictionary = NicknameDictionary::bundled();
+        assert!(!dictionary.is_empty());
+        for (a, b) in [
+            ("Bob", "Robert"),
+            ("Bill", "William"),
+            ("Dai", "Dafydd"),
+            ("Dai", "David"),
+            ("Dafydd", "David"),
+            ("Siôn", "John"),
+            ("Gwen", "Gwendoline"),
+            ("Rice", "Rhys"),
+            ("Evan", "Ifan"),
+            ("Owen", "Owain"),
+            ("Hugh", "Huw"),
+            ("Harry", "Harri"),
+            ("Richard", "Rhisiart"),
+            ("Floyd", "Llwyd"),
+        ] {
+            assert!(dictionary.are_equivalent(a, b), "{} {}", a, b);
+            assert!(dictionary.are_equivalent(b, a), "{} {}", b, a);
+        }
+        assert!(dictionary.are_equivalent(" BOB ", "robert"));
+        assert!(!dictionary.are_equivalent("Bob", "Bob"));
+        assert!(!dictionary.are_equivalent("Bob", "William"));
+        assert!(!dictionary.are_equivalent("Zebedee", "Robert"));
+    }
+
+    #[test]
+    fn test_extend_from_csv() {
+        let mut dictionary = NicknameDictionary::default();
+        dictionary.extend_from_csv("# comment\n\nZebedee, Zeb\n").unwrap();
+        assert_eq!(dictionary.len(), 1);
+        assert!(dictionary.are_equivalent("Zeb", "Zebedee"));
+        assert!(dictionary.extend_from_csv("Zeb\n").is_err());
+    }
+
+    #[test]
+    fn test_extend_from_path() {
+        let path = std::env::temp_dir().join(format!("nicknames_test_{}.csv", std::process::id()));
+        std::fs::write(&path, "Zebedee,Zeb\n").unwrap();
+        let mut dictionary = NicknameDictionary::bundled();
+        let result = dictionary.extend_from_path(&path);
+        std::fs::remove_file(&path).unwrap();
+        assert!(result.is_ok());
+        assert!(dictionary.are_equivalent("Zeb", "Zebedee"));
+        assert!(dictionary.are_equivalent("Bob", "Robert"));
+        assert!(dictionary.extend_from_path(Path::new("/nonexistent/nicknames.csv")).is_err());
+    }
+
+}
@@ -0,0 +1,130 @@
+// Text normalization before comparison.
+//
+// Comparators see the exact text of each field, so "Siân" and "Sian",
+// "ALICE" and "Alice", and the NFC and NFD encodings of the same name
+// would all differ. Normalization makes such text equal before comparison.
+//
+// A normalizer is any type that implements [Normalizer]. Each step is a
+// normalizer, and a slice of steps is a normalizer that applies each step
+// in order. Each field of a similarity profile names its steps; see
+// [crate::services::similarity_config::SimilarityProfile].
+
+use serde::{Deserialize, Serialize};
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+
+/// Normalize text before comparison.
+pub trait Normalizer: Send + Sync {
+    fn normalize(&self, s: &str) -> String;
+}
+
+/// One step of normalization.
+///
+/// - `Nfkc`: Unicode compatibility composition, so that equivalent encodings
+///   are equal, such as "a" with a combining circumflex and "â", or "ﬁ" and "fi".
+///
+/// - `CaseFold`: lowercase, so that "ALICE" and "Alice" are equal.
+///
+/// - `StripDiacritics`: remove accents and other combining marks,
+///   so that "Siân" and "Sian" are equal.
+///
+/// - `CollapseWhitespace`: trim, and replace each run of whitespace with one space.
+///
+/// - `RemovePunctuation`: remove each character that is not a letter, digit,
+///   mark, or whitespace, so that "O'Brien" and "OBrien" are equal.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum NormalizationStep {
+    Nfkc,
+    CaseFold,
+    StripDiacritics,
+    CollapseWhitespace,
+    RemovePunctuation,
+}
+
+impl Normalizer for NormalizationStep {
+    fn normalize(&self, s: &str) -> String {
+        match self {
+            NormalizationStep::Nfkc => s.nfkc().collect(),
+            NormalizationStep::CaseFold => s.to_lowercase(),
+            NormalizationStep::StripDiacritics => s.nfd().filter(|&c| !is_combining_mark(c)).nfc().collect(),
+            NormalizationStep::CollapseWhitespace => s.split_whitespace().collect::<Vec<&str>>().join(" "),
+            NormalizationStep::RemovePunctuation => s.chars().filter(|&c| c.is_alphanumeric() || c.is_whitespace() || is_combining_mark(c)).collect(),
+        }
+    }
+}
+
+/// A sequence of normalizers, applied in order.
+impl<N: Normalizer> Normalizer for [N] {
+    fn normalize(&self, s: &str) -> String {
+        self.iter().fold(s.to_string(), |s, normalizer| normalizer.normalize(&s))
+    }
+}
+
+/// The default steps of name fields.
+///
+/// Names keep punctuation, because hyphens and apostrophes separate words.
+///
+pub const NAME_STEPS: [NormalizationStep; 4] = [
+    NormalizationStep::Nfkc,
+    NormalizationStep::CaseFold,
+    NormalizationStep::StripDiacritics,
+    NormalizationStep::CollapseWhitespace,
+];
+
+/// The default steps of other text fields.
+pub const TEXT_STEPS: [NormalizationStep; 3] = [
+    NormalizationStep::Nfkc,
+    NormalizationStep::CaseFold,
+    NormalizationStep::CollapseWhitespace,
+];
+
+/// The default steps of numeric fields, which is none.
+pub const NUMBER_STEPS: [NormalizationStep; 0] = [];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_nfkc() {
+        let nfd = "Sia\u{0302}n";
+        assert_ne!(nfd, "Siân");
+        assert_eq!(NormalizationStep::Nfkc.normalize(nfd), "Siân");
+        assert_eq!(NormalizationStep::Nfkc.normalize("ﬁona"), "fiona");
+    }
+
+    #[test]
+    fn test_case_fold() {
+        assert_eq!(NormalizationStep::CaseFold.normalize("ALICE"), "alice");
+        assert_eq!(NormalizationStep::CaseFold.normalize("ŴYN"), "ŵyn");
+    }
+
+    #[test]
+    fn test_strip_diacritics() {
+        assert_eq!(NormalizationStep::StripDiacritics.normalize("Siân"), "Sian");
+        assert_eq!(NormalizationStep::StripDiacritics.normalize("Sia\u{0302}n"), "Sian");
+        assert_eq!(NormalizationStep::StripDiacritics.normalize("Zoë Brontë"), "Zoe Bronte");
+    }
+
+    #[test]
+    fn test_collapse_whitespace() {
+        assert_eq!(NormalizationStep::CollapseWhitespace.normalize("  Mary \t Ann\n"), "Mary Ann");
+    }
+
+    #[test]
+    fn test_remove_punctuation() {
+        assert_eq!(NormalizationStep::RemovePunctuation.normalize("O'Brien-Smith, Jr."), "OBrienSmith Jr");
+        assert_eq!(NormalizationStep::RemovePunctuation.normalize("(01234) 567-890"), "01234 567890");
+    }
+
+    #[test]
+    fn test_steps() {
+        assert_eq!(NAME_STEPS.normalize("  SIA\u{0302}N   Jones "), "sian jones");
+        assert_eq!(NAME_STEPS.normalize("Siân"), NAME_STEPS.normalize("SIAN"));
+        assert_eq!(TEXT_STEPS.normalize(" Alice@Example.com "), "alice@example.com");
+        assert_eq!(NUMBER_STEPS.normalize(" 1999 "), " 1999 ");
+    }
+
+}
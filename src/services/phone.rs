@@ -0,0 +1,318 @@
+// Parsing and comparison of phone numbers.
+//
+// Comparing phone numbers as text fails on formatting alone: "+44 29 2074 7747",
+// "029 2074 7747" and "02920747747" are the same number. The phone comparison
+// first parses each number into E.164 format, such as "+442920747747", using a
+// default region for national numbers, then compares:
+//
+// - Equal numbers score 1.0.
+// - A mobile number and a landline number score 0.0, because one line cannot be both.
+// - Numbers that differ by two adjacent digits swapped score [PHONE_TRANSPOSED].
+// - Numbers that share their last [PHONE_LAST_DIGITS] digits score [PHONE_LAST_DIGITS_MATCH],
+//   such as the same local number with a mistaken area code.
+// - Other numbers score 0.0.
+//
+// A number that cannot be parsed is invalid, and its comparison has no score
+// and a note that says so, rather than a low score from comparing text.
+
+use serde::{Deserialize, Serialize};
+use similarity_trait::Similarity;
+
+/// The sub-score of two numbers that differ by two adjacent digits swapped.
+pub const PHONE_TRANSPOSED: f64 = 0.8;
+
+/// How many final digits must match for [PHONE_LAST_DIGITS_MATCH].
+pub const PHONE_LAST_DIGITS: usize = 6;
+
+/// The sub-score of two numbers that share only their last digits.
+pub const PHONE_LAST_DIGITS_MATCH: f64 = 0.5;
+
+/// The default region of national numbers.
+pub const DEFAULT_PHONE_REGION: &str = "GB";
+
+/// A region for phone numbers.
+///
+/// - `code`: the ISO 3166 code, such as "GB".
+///
+/// - `country_code`: the international calling code, such as 44.
+///
+/// - `trunk_prefix`: the national dialling prefix, such as "0".
+///
+/// - `national_lengths`: the least and most digits of a national significant
+///   number, which is the number without the country code or trunk prefix.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PhoneRegion {
+    pub code: &'static str,
+    pub country_code: &'static str,
+    pub trunk_prefix: &'static str,
+    pub national_lengths: (usize, usize),
+}
+
+/// The supported regions.
+pub const PHONE_REGIONS: [PhoneRegion; 5] = [
+    PhoneRegion { code: "GB", country_code: "44", trunk_prefix: "0", national_lengths: (9, 10) },
+    PhoneRegion { code: "IE", country_code: "353", trunk_prefix: "0", national_lengths: (7, 9) },
+    PhoneRegion { code: "US", country_code: "1", trunk_prefix: "1", national_lengths: (10, 10) },
+    PhoneRegion { code: "FR", country_code: "33", trunk_prefix: "0", national_lengths: (9, 9) },
+    PhoneRegion { code: "DE", country_code: "49", trunk_prefix: "0", national_lengths: (6, 11) },
+];
+
+impl PhoneRegion {
+    /// Get a region by its ISO 3166 code, case-insensitive.
+    pub fn from_code(code: &str) -> Option<PhoneRegion> {
+        PHONE_REGIONS.into_iter().find(|region| region.code.eq_ignore_ascii_case(code))
+    }
+}
+
+/// How phone numbers are compared.
+///
+/// - `E164`: parse each number into E.164 format, then compare with credit
+///   for near misses; see [compare_phones].
+///
+/// - `Text`: compare with the comparators of the field.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneComparator {
+    #[default]
+    E164,
+    Text,
+}
+
+/// The kind of line of a phone number, where the region defines it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneLine {
+    Mobile,
+    Landline,
+    Other,
+}
+
+/// A phone number in E.164 format, such as "+442920747747".
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct PhoneNumber {
+    pub e164: String,
+    pub line: PhoneLine,
+}
+
+impl PhoneNumber {
+
+    /// Parse a phone number, using the region for national numbers.
+    ///
+    /// The number may have spaces, hyphens, dots, and parentheses, and an
+    /// international number may start with "+" or "00". A "(0)" after the
+    /// country code is ignored, as in "+44 (0)29 2074 7747".
+    ///
+    pub fn parse(s: &str, region: &PhoneRegion) -> Result<Self, String> {
+        let text = s.trim().replace("(0)", "");
+        if text.chars().any(|c| !(c.is_ascii_digit() || " -.()+".contains(c))) || text.rfind('+').is_some_and(|i| i > 0) {
+            return Err(format!("phone {:?} must have only digits, spaces, hyphens, dots, parentheses, and a leading +", s));
+        }
+        let digits: String = text.chars().filter(|c| c.is_ascii_digit()).collect();
+        if text.starts_with('+') {
+            PhoneNumber::parse_international(s, &digits)
+        }
+        else if let Some(digits) = digits.strip_prefix("00") {
+            PhoneNumber::parse_international(s, digits)
+        }
+        else {
+            let national = digits.strip_prefix(region.trunk_prefix)
+                .filter(|x| region.national_lengths.0 <= x.len() && x.len() <= region.national_lengths.1)
+                .unwrap_or(&digits);
+            PhoneNumber::of_national(s, region, national)
+        }
+    }
+
+    // Parse the digits of an international number, after the "+" or "00".
+    fn parse_international(s: &str, digits: &str) -> Result<Self, String> {
+        let mut regions = PHONE_REGIONS;
+        regions.sort_by_key(|region| std::cmp::Reverse(region.country_code.len()));
+        match regions.iter().find(|region| digits.starts_with(region.country_code)) {
+            Some(region) => PhoneNumber::of_national(s, region, &digits[region.country_code.len()..]),
+            None if (8..=15).contains(&digits.len()) => Ok(PhoneNumber { e164: format!("+{}", digits), line: PhoneLine::Other }),
+            None => Err(format!("phone {:?} must have from 8 to 15 digits", s)),
+        }
+    }
+
+    // Make a number from a national significant number in a region.
+    fn of_national(s: &str, region: &PhoneRegion, national: &str) -> Result<Self, String> {
+        let (min, max) = region.national_lengths;
+        if national.len() < min || national.len() > max || national.starts_with('0') {
+            return Err(format!("phone {:?} is not a valid {} number", s, region.code));
+        }
+        Ok(PhoneNumber {
+            e164: format!("+{}{}", region.country_code, national),
+            line: line_of_national(region, national),
+        })
+    }
+
+}
+
+// Display the number in E.164 format.
+impl std::fmt::Display for PhoneNumber {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{}", self.e164)
+    }
+}
+
+// Get the line of a national significant number. Only GB defines lines here:
+// mobiles start with 7, except 70 personal numbers and 76 pagers, and
+// landlines start with 1 or 2.
+fn line_of_national(region: &PhoneRegion, national: &str) -> PhoneLine {
+    if region.code != "GB" {
+        return PhoneLine::Other;
+    }
+    match national.as_bytes() {
+        [b'7', b'0' | b'6', ..] => PhoneLine::Other,
+        [b'7', ..] => PhoneLine::Mobile,
+        [b'1' | b'2', ..] => PhoneLine::Landline,
+        _ => PhoneLine::Other,
+    }
+}
+
+/// A note on the comparison of two phone numbers.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneNote {
+    Transposition,
+    LastDigits,
+    MobileLandline,
+    Invalid,
+}
+
+impl PhoneNote {
+    /// Get the name of the note, as in the explanation.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PhoneNote::Transposition => "phone_transposition",
+            PhoneNote::LastDigits => "phone_last_digits",
+            PhoneNote::MobileLandline => "phone_mobile_landline",
+            PhoneNote::Invalid => "phone_invalid",
+        }
+    }
+}
+
+/// The comparison of two phone numbers.
+///
+/// - `value`: the sub-score, which is `None` when either number is invalid.
+///
+/// - `note`: the note, if any.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PhoneComparison {
+    pub value: Option<f64>,
+    pub note: Option<PhoneNote>,
+}
+
+/// Compare two phone numbers, using the region for national numbers.
+pub fn compare_phones(a: &str, b: &str, region: &PhoneRegion) -> PhoneComparison {
+    let (a, b) = match (PhoneNumber::parse(a, region), PhoneNumber::parse(b, region)) {
+        (Ok(a), Ok(b)) => (a, b),
+        _ => return PhoneComparison { value: None, note: Some(PhoneNote::Invalid) },
+    };
+    compare_phone_numbers(&a, &b)
+}
+
+/// Compare two parsed phone numbers; see [compare_phones].
+pub fn compare_phone_numbers(a: &PhoneNumber, b: &PhoneNumber) -> PhoneComparison {
+    let (value, note) = if a.e164 == b.e164 {
+        (1.0, None)
+    }
+    else if matches!((a.line, b.line), (PhoneLine::Mobile, PhoneLine::Landline) | (PhoneLine::Landline, PhoneLine::Mobile)) {
+        (0.0, Some(PhoneNote::MobileLandline))
+    }
+    else if is_transposition(&a.e164, &b.e164) {
+        (PHONE_TRANSPOSED, Some(PhoneNote::Transposition))
+    }
+    else if a.e164.len() >= PHONE_LAST_DIGITS && b.e164.len() >= PHONE_LAST_DIGITS && a.e164[a.e164.len() - PHONE_LAST_DIGITS..] == b.e164[b.e164.len() - PHONE_LAST_DIGITS..] {
+        (PHONE_LAST_DIGITS_MATCH, Some(PhoneNote::LastDigits))
+    }
+    else {
+        (0.0, None)
+    };
+    PhoneComparison { value: Some(value), note }
+}
+
+impl Similarity<(&PhoneNumber, &PhoneNumber), f64> for PhoneNumber {
+    /// The sub-score of two parsed phone numbers; see [compare_phone_numbers].
+    fn similarity(input: (&PhoneNumber, &PhoneNumber)) -> f64 {
+        compare_phone_numbers(input.0, input.1).value.unwrap_or(0.0)
+    }
+}
+
+// True when two strings differ by exactly two adjacent characters swapped.
+fn is_transposition(a: &str, b: &str) -> bool {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    if a.len() != b.len() {
+        return false;
+    }
+    let differences: Vec<usize> = (0..a.len()).filter(|&i| a[i] != b[i]).collect();
+    matches!(differences[..], [i, j] if j == i + 1 && a[i] == b[j] && a[j] == b[i])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gb() -> PhoneRegion {
+        PhoneRegion::from_code("GB").unwrap()
+    }
+
+    fn e164(s: &str) -> String {
+        PhoneNumber::parse(s, &gb()).unwrap().e164
+    }
+
+    #[test]
+    fn test_parse() {
+        for s in ["+44 29 2074 7747", "029 2074 7747", "02920747747", "+44 (0)29 2074 7747", "0044 29 2074 7747", "(029) 2074-7747", "2920747747"] {
+            assert_eq!(e164(s), "+442920747747", "{}", s);
+        }
+        assert_eq!(e164("+1 (212) 555-0100"), "+12125550100");
+        assert_eq!(e164("+86 10 1234 5678"), "+861012345678");
+        assert_eq!(PhoneNumber::parse("(212) 555-0100", &PhoneRegion::from_code("us").unwrap()).unwrap().e164, "+12125550100");
+        for s in ["", "111", "029 2074 77", "+44 29 2074 77477", "02920747747 ext 12", "029+2074", "+123"] {
+            assert!(PhoneNumber::parse(s, &gb()).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_line() {
+        assert_eq!(PhoneNumber::parse("07700 900123", &gb()).unwrap().line, PhoneLine::Mobile);
+        assert_eq!(PhoneNumber::parse("029 2074 7747", &gb()).unwrap().line, PhoneLine::Landline);
+        assert_eq!(PhoneNumber::parse("0300 123 4567", &gb()).unwrap().line, PhoneLine::Other);
+        assert_eq!(PhoneNumber::parse("070 1234 5678", &gb()).unwrap().line, PhoneLine::Other);
+        assert_eq!(PhoneNumber::parse("+33 6 12 34 56 78", &gb()).unwrap().line, PhoneLine::Other);
+    }
+
+    #[test]
+    fn test_compare_phones() {
+        for (a, b, value, note) in [
+            ("+44 29 2074 7747", "02920747747", Some(1.0), None),
+            ("029 2074 7747", "029 2074 7774", Some(PHONE_TRANSPOSED), Some(PhoneNote::Transposition)),
+            ("029 2074 7747", "020 7074 7747", Some(PHONE_LAST_DIGITS_MATCH), Some(PhoneNote::LastDigits)),
+            ("029 2074 7747", "07700 900123", Some(0.0), Some(PhoneNote::MobileLandline)),
+            ("029 2074 7747", "0300 123 4567", Some(0.0), None),
+            ("029 2074 7747", "111", None, Some(PhoneNote::Invalid)),
+        ] {
+            assert_eq!(compare_phones(a, b, &gb()), PhoneComparison { value, note }, "{} {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_similarity_trait() {
+        let phone = |s: &str| PhoneNumber::parse(s, &gb()).unwrap();
+        assert_eq!(PhoneNumber::similarity((&phone("+44 29 2074 7747"), &phone("02920747747"))), 1.0);
+        assert_eq!(PhoneNumber::similarity((&phone("029 2074 7747"), &phone("029 2074 7774"))), PHONE_TRANSPOSED);
+        assert_eq!(PhoneNumber::similarity((&phone("029 2074 7747"), &phone("07700 900123"))), 0.0);
+    }
+
+    #[test]
+    fn test_is_transposition() {
+        assert!(is_transposition("12345", "12435"));
+        assert!(!is_transposition("12345", "12345"));
+        assert!(!is_transposition("12345", "15342"));
+        assert!(!is_transposition("12345", "1234"));
+    }
+
+}
@@ -0,0 +1,951 @@
+// Phonetic encoders for names.
+//
+// A phonetic encoder turns a name into a code that sounds alike for names
+// that sound alike, such as "Smith" and "Smyth". Codes are used two ways:
+//
+// - Comparison: a field can award partial credit when the codes agree,
+//   even when the spelling differs; see [PhoneticCredit].
+//
+// - Blocking: records that share a code are candidate pairs;
+//   see [crate::services::blocking].
+//
+// The encoders work on the letters A to Z; other characters are ignored,
+// except that Double Metaphone keeps spaces, Ç, and Ñ.
+
+use serde::{Deserialize, Serialize};
+use crate::services::comparators::StringComparator;
+
+/// A phonetic encoder.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PhoneticEncoder {
+    Soundex,
+    Nysiis,
+    Metaphone,
+    DoubleMetaphone,
+}
+
+impl PhoneticEncoder {
+    pub const ALL: [PhoneticEncoder; 4] = [
+        PhoneticEncoder::Soundex,
+        PhoneticEncoder::Nysiis,
+        PhoneticEncoder::Metaphone,
+        PhoneticEncoder::DoubleMetaphone,
+    ];
+
+    /// Get the name of the encoder, as in the config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PhoneticEncoder::Soundex => "soundex",
+            PhoneticEncoder::Nysiis => "nysiis",
+            PhoneticEncoder::Metaphone => "metaphone",
+            PhoneticEncoder::DoubleMetaphone => "double_metaphone",
+        }
+    }
+
+    /// Get an encoder by name, as in the config.
+    pub fn from_name(name: &str) -> Option<Self> {
+        PhoneticEncoder::ALL.into_iter().find(|encoder| encoder.name() == name)
+    }
+
+    /// Encode a name into its codes, without blank codes or duplicates.
+    ///
+    /// Double Metaphone can give two codes: primary and alternate.
+    /// The other encoders give one code. A name without letters gives none.
+    ///
+    pub fn encode(&self, name: &str) -> Vec<String> {
+        let mut codes = match self {
+            PhoneticEncoder::Soundex => vec![soundex(name)],
+            PhoneticEncoder::Nysiis => vec![nysiis(name)],
+            PhoneticEncoder::Metaphone => vec![metaphone(name)],
+            PhoneticEncoder::DoubleMetaphone => {
+                let (primary, alternate) = double_metaphone(name);
+                vec![primary, alternate]
+            }
+        };
+        codes.retain(|code| !code.is_empty());
+        codes.dedup();
+        codes
+    }
+
+    /// True when any code of one name equals any code of the other name.
+    pub fn codes_agree(&self, a: &str, b: &str) -> bool {
+        let b = self.encode(b);
+        self.encode(a).iter().any(|code| b.contains(code))
+    }
+}
+
+/// Partial credit for a field when the phonetic codes agree.
+///
+/// The field value is the greater of the comparator value and the credit.
+///
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct PhoneticCredit {
+    pub encoder: PhoneticEncoder,
+    pub credit: f64,
+}
+
+impl PhoneticCredit {
+    /// Apply the credit to a comparator value of two names.
+    pub fn apply(&self, value: f64, a: &str, b: &str) -> f64 {
+        if value < self.credit && self.encoder.codes_agree(a, b) {
+            self.credit
+        } else {
+            value
+        }
+    }
+}
+
+/// A string comparator that is 1.0 when the phonetic codes agree, otherwise 0.0.
+impl StringComparator for PhoneticEncoder {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        if self.codes_agree(a, b) { 1.0 } else { 0.0 }
+    }
+}
+
+fn letters(name: &str) -> Vec<char> {
+    name.chars()
+        .filter(|c| c.is_ascii_alphabetic())
+        .map(|c| c.to_ascii_uppercase())
+        .collect()
+}
+
+/// [Soundex](https://en.wikipedia.org/wiki/Soundex), American variant:
+/// the first letter, then three digits.
+pub fn soundex(name: &str) -> String {
+    fn digit(c: char) -> char {
+        match c {
+            'B' | 'F' | 'P' | 'V' => '1',
+            'C' | 'G' | 'J' | 'K' | 'Q' | 'S' | 'X' | 'Z' => '2',
+            'D' | 'T' => '3',
+            'L' => '4',
+            'M' | 'N' => '5',
+            'R' => '6',
+            _ => '0',
+        }
+    }
+    let chars = letters(name);
+    let Some(&first) = chars.first() else { return String::new() };
+    let mut code = String::from(first);
+    let mut last = digit(first);
+    for &c in chars[1..].iter() {
+        // H and W do not separate letters with the same digit; vowels do.
+        if c == 'H' || c == 'W' {
+            continue;
+        }
+        let d = digit(c);
+        if d != '0' && d != last {
+            code.push(d);
+        }
+        last = d;
+        if code.len() == 4 {
+            break;
+        }
+    }
+    format!("{:0<4}", code)
+}
+
+/// [NYSIIS](https://en.wikipedia.org/wiki/New_York_State_Identification_and_Intelligence_System),
+/// the New York State Identification and Intelligence System code,
+/// truncated to six characters.
+pub fn nysiis(name: &str) -> String {
+    fn is_vowel(c: char) -> bool {
+        matches!(c, 'A' | 'E' | 'I' | 'O' | 'U')
+    }
+    let mut s: String = letters(name).into_iter().collect();
+    if s.is_empty() {
+        return s;
+    }
+    for (from, to) in [("MAC", "MCC"), ("KN", "NN"), ("K", "C"), ("PH", "FF"), ("PF", "FF"), ("SCH", "SSS")] {
+        if s.starts_with(from) {
+            s.replace_range(0..from.len(), to);
+            break;
+        }
+    }
+    for (from, to) in [("EE", "Y"), ("IE", "Y"), ("DT", "D"), ("RT", "D"), ("RD", "D"), ("NT", "D"), ("ND", "D")] {
+        if s.ends_with(from) {
+            s.replace_range((s.len() - from.len()).., to);
+            break;
+        }
+    }
+    let mut chars: Vec<char> = s.chars().collect();
+    let mut key = vec![chars[0]];
+    let mut i = 1;
+    while i < chars.len() {
+        let next = chars.get(i + 1).copied();
+        match chars[i] {
+            'E' if next == Some('V') => {
+                chars[i] = 'A';
+                chars[i + 1] = 'F';
+            }
+            c if is_vowel(c) => chars[i] = 'A',
+            'Q' => chars[i] = 'G',
+            'Z' => chars[i] = 'S',
+            'M' => chars[i] = 'N',
+            'K' if next == Some('N') => chars[i] = 'N',
+            'K' => chars[i] = 'C',
+            'S' if next == Some('C') && chars.get(i + 2) == Some(&'H') => {
+                chars[i + 1] = 'S';
+                chars[i + 2] = 'S';
+            }
+            'P' if next == Some('H') => {
+                chars[i] = 'F';
+                chars[i + 1] = 'F';
+            }
+            'H' if !is_vowel(chars[i - 1]) || !next.is_some_and(is_vowel) => chars[i] = chars[i - 1],
+            'W' if is_vowel(chars[i - 1]) => chars[i] = chars[i - 1],
+            _ => {}
+        }
+        if key.last() != Some(&chars[i]) {
+            key.push(chars[i]);
+        }
+        i += 1;
+    }
+    if key.len() > 1 && key.last() == Some(&'S') {
+        key.pop();
+    }
+    if key.len() > 2 && key.ends_with(&['A', 'Y']) {
+        key.remove(key.len() - 2);
+    }
+    if key.len() > 1 && key.last() == Some(&'A') {
+        key.pop();
+    }
+    key.into_iter().take(6).collect()
+}
+
+/// [Metaphone](https://en.wikipedia.org/wiki/Metaphone), the original algorithm
+/// by Lawrence Philips, without truncation. The code uses '0' for "th".
+pub fn metaphone(name: &str) -> String {
+    fn is_vowel(w: &[char], i: usize) -> bool {
+        matches!(w.get(i), Some('A' | 'E' | 'I' | 'O' | 'U'))
+    }
+    fn is_front_vowel(w: &[char], i: usize) -> bool {
+        matches!(w.get(i), Some('E' | 'I' | 'Y'))
+    }
+    fn at(w: &[char], i: usize, s: &str) -> bool {
+        s.chars().enumerate().all(|(k, c)| w.get(i + k) == Some(&c))
+    }
+    let chars = letters(name);
+    if chars.len() <= 1 {
+        return chars.into_iter().collect();
+    }
+
+    // Initial letter exceptions.
+    let w: Vec<char> = match (chars[0], chars[1]) {
+        ('K' | 'G' | 'P', 'N') | ('A', 'E') | ('W', 'R') => chars[1..].to_vec(),
+        ('W', 'H') => [&['W'], &chars[2..]].concat(),
+        ('X', _) => [&['S'], &chars[1..]].concat(),
+        _ => chars,
+    };
+
+    let mut code = String::new();
+    let len = w.len();
+    let mut n = 0;
+    while n < len {
+        let c = w[n];
+        let previous = if n > 0 { Some(w[n - 1]) } else { None };
+        let next = w.get(n + 1).copied();
+        let last = n + 1 == len;
+        // Skip a doubled letter, except C.
+        if c != 'C' && previous == Some(c) {
+            n += 1;
+            continue;
+        }
+        match c {
+            'A' | 'E' | 'I' | 'O' | 'U' => if n == 0 { code.push(c) },
+            'B' => if !(previous == Some('M') && last) { code.push('B') },
+            'C' => {
+                if previous == Some('S') && is_front_vowel(&w, n + 1) {
+                    // "SCI", "SCE", "SCY" are silent.
+                }
+                else if at(&w, n, "CIA") {
+                    code.push('X');
+                }
+                else if is_front_vowel(&w, n + 1) {
+                    code.push('S');
+                }
+                else if previous == Some('S') && next == Some('H') {
+                    code.push('K');
+                }
+                else if next == Some('H') {
+                    code.push(if n == 0 && is_vowel(&w, 2) { 'K' } else { 'X' });
+                }
+                else {
+                    code.push('K');
+                }
+            }
+            'D' => {
+                if next == Some('G') && is_front_vowel(&w, n + 2) {
+                    code.push('J');
+                    n += 2;
+                }
+                else {
+                    code.push('T');
+                }
+            }
+            'G' => {
+                let silent = (next == Some('H') && (n + 2 == len || !is_vowel(&w, n + 2)))
+                    || (n > 0 && at(&w, n, "GN") && (n + 2 == len || at(&w, n, "GNED")));
+                if !silent {
+                    code.push(if is_front_vowel(&w, n + 1) && previous != Some('G') { 'J' } else { 'K' });
+                }
+            }
+            'H' => {
+                if !last && !matches!(previous, Some('C' | 'S' | 'P' | 'T' | 'G')) && is_vowel(&w, n + 1) {
+                    code.push('H');
+                }
+            }
+            'K' => if previous != Some('C') { code.push('K') },
+            'P' => code.push(if next == Some('H') { 'F' } else { 'P' }),
+            'Q' => code.push('K'),
+            'S' => code.push(if at(&w, n, "SH") || at(&w, n, "SIO") || at(&w, n, "SIA") { 'X' } else { 'S' }),
+            'T' => {
+                if at(&w, n, "TIA") || at(&w, n, "TIO") {
+                    code.push('X');
+                }
+                else if at(&w, n, "TCH") {
+                    // Silent, because the "CH" gives the sound.
+                }
+                else if next == Some('H') {
+                    code.push('0');
+                }
+                else {
+                    code.push('T');
+                }
+            }
+            'V' => code.push('F'),
+            'W' | 'Y' => if is_vowel(&w, n + 1) { code.push(c) },
+            'X' => code.push_str("KS"),
+            'Z' => code.push('S'),
+            _ => code.push(c),
+        }
+        n += 1;
+    }
+    code
+}
+
+/// The maximum length of a Double Metaphone code.
+pub const DOUBLE_METAPHONE_LEN: usize = 4;
+
+/// [Double Metaphone](https://en.wikipedia.org/wiki/Metaphone#Double_Metaphone)
+/// by Lawrence Philips, which returns a primary code and an alternate code,
+/// each up to four characters. The code uses '0' for "th".
+///
+/// Unlike the original, "MPS" before a vowel has a silent P in the primary
+/// code, so that "Thompson" and "Thomson" agree.
+///
+pub fn double_metaphone(name: &str) -> (String, String) {
+    let w: Vec<char> = name.trim()
+        .to_uppercase()
+        .chars()
+        .filter(|c| c.is_ascii_uppercase() || matches!(c, ' ' | 'Ç' | 'Ñ'))
+        .collect();
+    DoubleMetaphone::new(w).encode()
+}
+
+struct DoubleMetaphone {
+    w: Vec<char>,
+    primary: String,
+    alternate: String,
+    slavo_germanic: bool,
+}
+
+impl DoubleMetaphone {
+
+    fn new(w: Vec<char>) -> Self {
+        let s: String = w.iter().collect();
+        let slavo_germanic = s.contains('W') || s.contains('K') || s.contains("CZ") || s.contains("WITZ");
+        DoubleMetaphone { w, primary: String::new(), alternate: String::new(), slavo_germanic }
+    }
+
+    fn len(&self) -> usize {
+        self.w.len()
+    }
+
+    fn char_at(&self, i: isize) -> char {
+        if i < 0 { '\0' } else { self.w.get(i as usize).copied().unwrap_or('\0') }
+    }
+
+    fn is_vowel_at(&self, i: isize) -> bool {
+        matches!(self.char_at(i), 'A' | 'E' | 'I' | 'O' | 'U' | 'Y')
+    }
+
+    /// True when the substring at `start` is any of the options, which all have the same length.
+    fn at(&self, start: isize, options: &[&str]) -> bool {
+        let len = options[0].len() as isize;
+        if start < 0 || start + len > self.len() as isize {
+            return false;
+        }
+        let s: String = self.w[start as usize..(start + len) as usize].iter().collect();
+        options.contains(&s.as_str())
+    }
+
+    fn add(&mut self, primary: &str, alternate: &str) {
+        self.primary.push_str(primary);
+        self.alternate.push_str(alternate);
+    }
+
+    fn add_both(&mut self, code: &str) {
+        self.add(code, code);
+    }
+
+    fn encode(mut self) -> (String, String) {
+        let len = self.len() as isize;
+        let mut i: isize = 0;
+        if self.at(0, &["GN", "KN", "PN", "WR", "PS"]) {
+            i = 1;
+        }
+        if self.char_at(0) == 'X' {
+            self.add_both("S");
+            i = 1;
+        }
+        while i < len && (self.primary.len() < DOUBLE_METAPHONE_LEN || self.alternate.len() < DOUBLE_METAPHONE_LEN) {
+            i = match self.char_at(i) {
+                'A' | 'E' | 'I' | 'O' | 'U' | 'Y' => {
+                    if i == 0 {
+                        self.add_both("A");
+                    }
+                    i + 1
+                }
+                'B' => {
+                    self.add_both("P");
+                    if self.char_at(i + 1) == 'B' { i + 2 } else { i + 1 }
+                }
+                'Ç' => {
+                    self.add_both("S");
+                    i + 1
+                }
+                'C' => self.c(i),
+                'D' => self.d(i),
+                'F' => {
+                    self.add_both("F");
+                    if self.char_at(i + 1) == 'F' { i + 2 } else { i + 1 }
+                }
+                'G' => self.g(i),
+                'H' => {
+                    if (i == 0 || self.is_vowel_at(i - 1)) && self.is_vowel_at(i + 1) {
+                        self.add_both("H");
+                        i + 2
+                    } else {
+                        i + 1
+                    }
+                }
+                'J' => self.j(i),
+                'K' => {
+                    self.add_both("K");
+                    if self.char_at(i + 1) == 'K' { i + 2 } else { i + 1 }
+                }
+                'L' => self.l(i),
+                'M' => {
+                    self.add_both("M");
+                    let umb = self.at(i - 1, &["UMB"]) && (i + 1 == len - 1 || self.at(i + 2, &["ER"]));
+                    if self.char_at(i + 1) == 'M' || umb { i + 2 } else { i + 1 }
+                }
+                'N' => {
+                    self.add_both("N");
+                    if self.char_at(i + 1) == 'N' { i + 2 } else { i + 1 }
+                }
+                'Ñ' => {
+                    self.add_both("N");
+                    i + 1
+                }
+                'P' => {
+                    if self.char_at(i + 1) == 'H' {
+                        self.add_both("F");
+                        i + 2
+                    } else if self.char_at(i - 1) == 'M' && self.char_at(i + 1) == 'S' && self.is_vowel_at(i + 2) {
+                        // "Thompson", "Simpson": the P is often silent.
+                        self.add("", "P");
+                        i + 1
+                    } else {
+                        self.add_both("P");
+                        if self.at(i + 1, &["P", "B"]) { i + 2 } else { i + 1 }
+                    }
+                }
+                'Q' => {
+                    self.add_both("K");
+                    if self.char_at(i + 1) == 'Q' { i + 2 } else { i + 1 }
+                }
+                'R' => {
+                    if i == len - 1 && !self.slavo_germanic && self.at(i - 2, &["IE"]) && !self.at(i - 4, &["ME", "MA"]) {
+                        self.add("", "R");
+                    } else {
+                        self.add_both("R");
+                    }
+                    if self.char_at(i + 1) == 'R' { i + 2 } else { i + 1 }
+                }
+                'S' => self.s(i),
+                'T' => self.t(i),
+                'V' => {
+                    self.add_both("F");
+                    if self.char_at(i + 1) == 'V' { i + 2 } else { i + 1 }
+                }
+                'W' => self.w(i),
+                'X' => {
+                    if i == 0 {
+                        self.add_both("S");
+                        i + 1
+                    } else {
+                        if !(i == len - 1 && (self.at(i - 3, &["IAU", "EAU"]) || self.at(i - 2, &["AU", "OU"]))) {
+                            self.add_both("KS");
+                        }
+                        if self.at(i + 1, &["C", "X"]) { i + 2 } else { i + 1 }
+                    }
+                }
+                'Z' => {
+                    if self.char_at(i + 1) == 'H' {
+                        self.add_both("J");
+                        i + 2
+                    } else {
+                        if self.at(i + 1, &["ZO", "ZI", "ZA"]) || (self.slavo_germanic && i > 0 && self.char_at(i - 1) != 'T') {
+                            self.add("S", "TS");
+                        } else {
+                            self.add_both("S");
+                        }
+                        if self.char_at(i + 1) == 'Z' { i + 2 } else { i + 1 }
+                    }
+                }
+                _ => i + 1,
+            };
+        }
+        let primary = self.primary.chars().take(DOUBLE_METAPHONE_LEN).collect();
+        let alternate = self.alternate.chars().take(DOUBLE_METAPHONE_LEN).collect();
+        (primary, alternate)
+    }
+
+    fn c(&mut self, i: isize) -> isize {
+        if self.c_is_k_in_ach(i) {
+            self.add_both("K");
+            i + 2
+        }
+        else if i == 0 && self.at(i, &["CAESAR"]) {
+            self.add_both("S");
+            i + 2
+        }
+        else if self.at(i, &["CH"]) {
+            self.ch(i)
+        }
+        else if self.at(i, &["CZ"]) && !self.at(i - 2, &["WICZ"]) {
+            self.add("S", "X");
+            i + 2
+        }
+        else if self.at(i + 1, &["CIA"]) {
+            self.add_both("X");
+            i + 3
+        }
+        else if self.at(i, &["CC"]) && !(i == 1 && self.char_at(0) == 'M') {
+            if self.at(i + 2, &["I", "E", "H"]) && !self.at(i + 2, &["HU"]) {
+                if (i == 1 && self.char_at(i - 1) == 'A') || self.at(i - 1, &["UCCEE", "UCCES"]) {
+                    self.add_both("KS");
+                } else {
+                    self.add_both("X");
+                }
+                i + 3
+            } else {
+                self.add_both("K");
+                i + 2
+            }
+        }
+        else if self.at(i, &["CK", "CG", "CQ"]) {
+            self.add_both("K");
+            i + 2
+        }
+        else if self.at(i, &["CI", "CE", "CY"]) {
+            if self.at(i, &["CIO", "CIE", "CIA"]) {
+                self.add("S", "X");
+            } else {
+                self.add_both("S");
+            }
+            i + 2
+        }
+        else {
+            self.add_both("K");
+            if self.at(i + 1, &[" C", " Q", " G"]) {
+                i + 3
+            } else if self.at(i + 1, &["C", "K", "Q"]) && !self.at(i + 1, &["CE", "CI"]) {
+                i + 2
+            } else {
+                i + 1
+            }
+        }
+    }
+
+    // Germanic "ACH", such as "BACHER" and "MACHER", but not "MACHINE".
+    fn c_is_k_in_ach(&self, i: isize) -> bool {
+        if self.at(i, &["CHIA"]) {
+            true
+        } else if i <= 1 || self.is_vowel_at(i - 2) || !self.at(i - 1, &["ACH"]) {
+            false
+        } else {
+            let c = self.char_at(i + 2);
+            (c != 'I' && c != 'E') || self.at(i - 2, &["BACHER", "MACHER"])
+        }
+    }
+
+    fn ch(&mut self, i: isize) -> isize {
+        let len = self.len() as isize;
+        if i > 0 && self.at(i, &["CHAE"]) {
+            self.add("K", "X");
+        }
+        else if i == 0
+            && (self.at(i + 1, &["HARAC", "HARIS"]) || self.at(i + 1, &["HOR", "HYM", "HIA", "HEM"]))
+            && !self.at(0, &["CHORE"]) {
+            // Greek roots, such as "CHEMISTRY" and "CHORUS".
+            self.add_both("K");
+        }
+        else if self.at(0, &["VAN ", "VON "]) || self.at(0, &["SCH"])
+            || self.at(i - 2, &["ORCHES", "ARCHIT", "ORCHID"])
+            || self.at(i + 2, &["T", "S"])
+            || ((self.at(i - 1, &["A", "O", "U", "E"]) || i == 0)
+                && (self.at(i + 2, &["L", "R", "N", "M", "B", "H", "F", "V", "W", " "]) || i + 1 == len - 1)) {
+            // Germanic, such as "ORCHESTRA" and "SCHMIDT".
+            self.add_both("K");
+        }
+        else if i > 0 {
+            if self.at(0, &["MC"]) {
+                self.add_both("K");
+            } else {
+                self.add("X", "K");
+            }
+        }
+        else {
+            self.add_both("X");
+        }
+        i + 2
+    }
+
+    fn d(&mut self, i: isize) -> isize {
+        if self.at(i, &["DG"]) {
+            if self.at(i + 2, &["I", "E", "Y"]) {
+                self.add_both("J");
+                i + 3
+            } else {
+                self.add_both("TK");
+                i + 2
+            }
+        }
+        else if self.at(i, &["DT", "DD"]) {
+            self.add_both("T");
+            i + 2
+        }
+        else {
+            self.add_both("T");
+            i + 1
+        }
+    }
+
+    fn g(&mut self, i: isize) -> isize {
+        let next = self.char_at(i + 1);
+        if next == 'H' {
+            self.gh(i)
+        }
+        else if next == 'N' {
+            if i == 1 && self.is_vowel_at(0) && !self.slavo_germanic {
+                self.add("KN", "N");
+            } else if !self.at(i + 2, &["EY"]) && next != 'Y' && !self.slavo_germanic {
+                self.add("N", "KN");
+            } else {
+                self.add_both("KN");
+            }
+            i + 2
+        }
+        else if self.at(i + 1, &["LI"]) && !self.slavo_germanic {
+            self.add("KL", "L");
+            i + 2
+        }
+        else if (i == 0 && (next == 'Y' || self.at(i + 1, &["ES", "EP", "EB", "EL", "EY", "IB", "IL", "IN", "IE", "EI", "ER"])))
+            || ((self.at(i + 1, &["ER"]) || next == 'Y')
+                && !self.at(0, &["DANGER", "RANGER", "MANGER"])
+                && !self.at(i - 1, &["E", "I"])
+                && !self.at(i - 1, &["RGY", "OGY"])) {
+            self.add("K", "J");
+            i + 2
+        }
+        else if self.at(i + 1, &["E", "I", "Y"]) || self.at(i - 1, &["AGGI", "OGGI"]) {
+            if self.at(0, &["VAN ", "VON "]) || self.at(0, &["SCH"]) || self.at(i + 1, &["ET"]) {
+                self.add_both("K");
+            } else if self.at(i + 1, &["IER"]) {
+                self.add_both("J");
+            } else {
+                self.add("J", "K");
+            }
+            i + 2
+        }
+        else if next == 'G' {
+            self.add_both("K");
+            i + 2
+        }
+        else {
+            self.add_both("K");
+            i + 1
+        }
+    }
+
+    fn gh(&mut self, i: isize) -> isize {
+        if i > 0 && !self.is_vowel_at(i - 1) {
+            self.add_both("K");
+        }
+        else if i == 0 {
+            if self.char_at(i + 2) == 'I' {
+                self.add_both("J");
+            } else {
+                self.add_both("K");
+            }
+        }
+        else if (i > 1 && self.at(i - 2, &["B", "H", "D"]))
+            || (i > 2 && self.at(i - 3, &["B", "H", "D"]))
+            || (i > 3 && self.at(i - 4, &["B", "H"])) {
+            // Silent, such as "HUGH" and "BOUGH".
+        }
+        else if i > 2 && self.char_at(i - 1) == 'U' && self.at(i - 3, &["C", "G", "L", "R", "T"]) {
+            // Such as "LAUGH" and "TOUGH".
+            self.add_both("F");
+        }
+        else if i > 0 && self.char_at(i - 1) != 'I' {
+            self.add_both("K");
+        }
+        i + 2
+    }
+
+    fn j(&mut self, i: isize) -> isize {
+        let len = self.len() as isize;
+        if self.at(i, &["JOSE"]) || self.at(0, &["SAN "]) {
+            if (i == 0 && self.char_at(i + 4) == ' ') || len == 4 || self.at(0, &["SAN "]) {
+                self.add_both("H");
+            } else {
+                self.add("J", "H");
+            }
+            return i + 1;
+        }
+        if i == 0 {
+            self.add("J", "A");
+        }
+        else if self.is_vowel_at(i - 1) && !self.slavo_germanic && matches!(self.char_at(i + 1), 'A' | 'O') {
+            self.add("J", "H");
+        }
+        else if i == len - 1 {
+            self.add("J", "");
+        }
+        else if !self.at(i + 1, &["L", "T", "K", "S", "N", "M", "B", "Z"]) && !self.at(i - 1, &["S", "K", "L"]) {
+            self.add_both("J");
+        }
+        if self.char_at(i + 1) == 'J' { i + 2 } else { i + 1 }
+    }
+
+    fn l(&mut self, i: isize) -> isize {
+        let len = self.len() as isize;
+        if self.char_at(i + 1) == 'L' {
+            // Spanish, such as "CABRILLO" and "GALLEGOS".
+            let spanish = (i == len - 3 && self.at(i - 1, &["ILLO", "ILLA", "ALLE"]))
+                || ((self.at(len - 2, &["AS", "OS"]) || self.at(len - 1, &["A", "O"])) && self.at(i - 1, &["ALLE"]));
+            if spanish {
+                self.add("L", "");
+            } else {
+                self.add_both("L");
+            }
+            i + 2
+        } else {
+            self.add_both("L");
+            i + 1
+        }
+    }
+
+    fn s(&mut self, i: isize) -> isize {
+        let len = self.len() as isize;
+        if self.at(i - 1, &["ISL", "YSL"]) {
+            // Silent, such as "ISLAND" and "CARLYSLE".
+            i + 1
+        }
+        else if i == 0 && self.at(i, &["SUGAR"]) {
+            self.add("X", "S");
+            i + 1
+        }
+        else if self.at(i, &["SH"]) {
+            if self.at(i + 1, &["HEIM", "HOEK", "HOLM", "HOLZ"]) {
+                self.add_both("S");
+            } else {
+                self.add_both("X");
+            }
+            i + 2
+        }
+        else if self.at(i, &["SIO", "SIA"]) || self.at(i, &["SIAN"]) {
+            if self.slavo_germanic {
+                self.add_both("S");
+            } else {
+                self.add("S", "X");
+            }
+            i + 3
+        }
+        else if (i == 0 && self.at(i + 1, &["M", "N", "L", "W"])) || self.at(i + 1, &["Z"]) {
+            self.add("S", "X");
+            if self.at(i + 1, &["Z"]) { i + 2 } else { i + 1 }
+        }
+        else if self.at(i, &["SC"]) {
+            if self.char_at(i + 2) == 'H' {
+                if self.at(i + 3, &["OO", "ER", "EN", "UY", "ED", "EM"]) {
+                    if self.at(i + 3, &["ER", "EN"]) {
+                        self.add("X", "SK");
+                    } else {
+                        self.add_both("SK");
+                    }
+                } else if i == 0 && !self.is_vowel_at(3) && self.char_at(3) != 'W' {
+                    self.add("X", "S");
+                } else {
+                    self.add_both("X");
+                }
+            } else if self.at(i + 2, &["I", "E", "Y"]) {
+                self.add_both("S");
+            } else {
+                self.add_both("SK");
+            }
+            i + 3
+        }
+        else {
+            if i == len - 1 && self.at(i - 2, &["AI", "OI"]) {
+                self.add("", "S");
+            } else {
+                self.add_both("S");
+            }
+            if self.at(i + 1, &["S", "Z"]) { i + 2 } else { i + 1 }
+        }
+    }
+
+    fn t(&mut self, i: isize) -> isize {
+        if self.at(i, &["TION"]) || self.at(i, &["TIA", "TCH"]) {
+            self.add_both("X");
+            i + 3
+        }
+        else if self.at(i, &["TH"]) || self.at(i, &["TTH"]) {
+            if self.at(i + 2, &["OM", "AM"]) || self.at(0, &["VAN ", "VON "]) || self.at(0, &["SCH"]) {
+                self.add_both("T");
+            } else {
+                self.add("0", "T");
+            }
+            i + 2
+        }
+        else {
+            self.add_both("T");
+            if self.at(i + 1, &["T", "D"]) { i + 2 } else { i + 1 }
+        }
+    }
+
+    fn w(&mut self, i: isize) -> isize {
+        let len = self.len() as isize;
+        if self.at(i, &["WR"]) {
+            self.add_both("R");
+            i + 2
+        }
+        else if i == 0 && (self.is_vowel_at(i + 1) || self.at(i, &["WH"])) {
+            if self.is_vowel_at(i + 1) {
+                self.add("A", "F");
+            } else {
+                self.add_both("A");
+            }
+            i + 1
+        }
+        else if (i == len - 1 && self.is_vowel_at(i - 1))
+            || self.at(i - 1, &["EWSKI", "EWSKY", "OWSKI", "OWSKY"])
+            || self.at(0, &["SCH"]) {
+            self.add("", "F");
+            i + 1
+        }
+        else if self.at(i, &["WICZ", "WITZ"]) {
+            self.add("TS", "FX");
+            i + 4
+        }
+        else {
+            i + 1
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_soundex() {
+        assert_eq!(soundex("Robert"), "R163");
+        assert_eq!(soundex("Rupert"), "R163");
+        assert_eq!(soundex("Ashcraft"), "A261");
+        assert_eq!(soundex("Tymczak"), "T522");
+        assert_eq!(soundex("Pfister"), "P236");
+        assert_eq!(soundex("Lee"), "L000");
+        assert_eq!(soundex("Smith"), soundex("Smyth"));
+        assert_eq!(soundex(""), "");
+    }
+
+    #[test]
+    fn test_nysiis() {
+        for name in ["Brian", "Brown", "Brun"] {
+            assert_eq!(nysiis(name), "BRAN", "{}", name);
+        }
+        for name in ["Capp", "Cope", "Copp", "Kipp"] {
+            assert_eq!(nysiis(name), "CAP", "{}", name);
+        }
+        for name in ["Dane", "Dean", "Dionne"] {
+            assert_eq!(nysiis(name), "DAN", "{}", name);
+        }
+        for name in ["Smith", "Schmit"] {
+            assert_eq!(nysiis(name), "SNAT", "{}", name);
+        }
+        assert_eq!(nysiis("Schmidt"), "SNAD");
+        assert_eq!(nysiis("Trueman"), nysiis("Truman"));
+        assert_eq!(nysiis(""), "");
+    }
+
+    #[test]
+    fn test_metaphone() {
+        assert_eq!(metaphone("Smith"), "SM0");
+        assert_eq!(metaphone("Smyth"), "SM0");
+        assert_eq!(metaphone("Knight"), "NT");
+        assert_eq!(metaphone("Phillips"), "FLPS");
+        assert_eq!(metaphone("Xavier"), "SFR");
+        assert_eq!(metaphone("Thomas"), "0MS");
+    }
+
+    #[test]
+    fn test_double_metaphone() {
+        assert_eq!(double_metaphone("Smith"), (String::from("SM0"), String::from("XMT")));
+        assert_eq!(double_metaphone("Schmidt"), (String::from("XMT"), String::from("SMT")));
+        assert_eq!(double_metaphone("Jones"), (String::from("JNS"), String::from("ANS")));
+        assert_eq!(double_metaphone("Williams"), (String::from("ALMS"), String::from("FLMS")));
+        assert_eq!(double_metaphone("Thomas"), (String::from("TMS"), String::from("TMS")));
+        assert_eq!(double_metaphone("Caesar"), (String::from("SSR"), String::from("SSR")));
+        assert_eq!(double_metaphone("Hugh"), (String::from("H"), String::from("H")));
+        assert_eq!(double_metaphone("Laugh"), (String::from("LF"), String::from("LF")));
+        assert_eq!(double_metaphone("Thompson"), (String::from("TMSN"), String::from("TMPS")));
+        assert_eq!(double_metaphone("Thomson"), (String::from("TMSN"), String::from("TMSN")));
+        assert_eq!(double_metaphone("Kempster"), (String::from("KMPS"), String::from("KMPS")));
+        assert_eq!(double_metaphone(""), (String::new(), String::new()));
+    }
+
+    #[test]
+    fn test_codes_agree() {
+        assert!(PhoneticEncoder::DoubleMetaphone.codes_agree("Smith", "Smyth"));
+        assert!(PhoneticEncoder::DoubleMetaphone.codes_agree("Smith", "Schmidt"));
+        assert!(!PhoneticEncoder::DoubleMetaphone.codes_agree("Smith", "Jones"));
+        assert!(PhoneticEncoder::DoubleMetaphone.codes_agree("Thomson", "Thompson"));
+        assert!(PhoneticEncoder::DoubleMetaphone.codes_agree("Simpson", "Simson"));
+        assert!(PhoneticEncoder::Soundex.codes_agree("Thomson", "Tomson"));
+        assert!(!PhoneticEncoder::Soundex.codes_agree("", ""));
+    }
+
+    #[test]
+    fn test_phonetic_credit() {
+        let credit = PhoneticCredit { encoder: PhoneticEncoder::Metaphone, credit: 0.9 };
+        assert_eq!(credit.apply(0.7, "Smith", "Smyth"), 0.9);
+        assert_eq!(credit.apply(0.95, "Smith", "Smyth"), 0.95);
+        assert_eq!(credit.apply(0.2, "Smith", "Jones"), 0.2);
+    }
+
+    #[test]
+    fn test_name() {
+        for encoder in PhoneticEncoder::ALL {
+            assert_eq!(PhoneticEncoder::from_name(encoder.name()), Some(encoder));
+        }
+    }
+
+}
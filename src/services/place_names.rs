@@ -0,0 +1,171 @@
+// Welsh and English place names.
+//
+// Many places in Wales have a Welsh name and an English name, such as
+// "Caerdydd" and "Cardiff", or "Abertawe" and "Swansea", and a record can
+// use either. The names often share few letters, so edit distance scores
+// them as different places.
+//
+// The comparator [PlaceName] scores two names of the same place as 1.0,
+// and otherwise uses [similarity_of_strings].
+
+use unicode_normalization::UnicodeNormalization;
+use unicode_normalization::char::is_combining_mark;
+use crate::services::comparators::StringComparator;
+use crate::services::similarity::similarity_of_strings;
+
+// Welsh place names, each with its English name, as keys; see [place_key].
+const BILINGUAL: &[(&str, &str)] = &[
+    ("caerdydd", "cardiff"),
+    ("abertawe", "swansea"),
+    ("casnewydd", "newport"),
+    ("wrecsam", "wrexham"),
+    ("caerfyrddin", "carmarthen"),
+    ("aberhonddu", "brecon"),
+    ("caergybi", "holyhead"),
+    ("y fenni", "abergavenny"),
+    ("pen y bont ar ogwr", "bridgend"),
+    ("castell nedd", "neath"),
+    ("caerffili", "caerphilly"),
+    ("y barri", "barry"),
+    ("merthyr tudful", "merthyr tydfil"),
+    ("glynebwy", "ebbw vale"),
+    ("pont y pwl", "pontypool"),
+    ("trefynwy", "monmouth"),
+    ("cas gwent", "chepstow"),
+    ("caerllion", "caerleon"),
+    ("abertyleri", "abertillery"),
+    ("hwlffordd", "haverfordwest"),
+    ("penfro", "pembroke"),
+    ("dinbych y pysgod", "tenby"),
+    ("aberdaugleddau", "milford haven"),
+    ("abergwaun", "fishguard"),
+    ("tyddewi", "st davids"),
+    ("aberteifi", "cardigan"),
+    ("llanbedr pont steffan", "lampeter"),
+    ("castellnewydd emlyn", "newcastle emlyn"),
+    ("llanymddyfri", "llandovery"),
+    ("llanfair ym muallt", "builth wells"),
+    ("y gelli gandryll", "hay on wye"),
+    ("llandrindod", "llandrindod wells"),
+    ("y drenewydd", "newtown"),
+    ("y trallwng", "welshpool"),
+    ("y bala", "bala"),
+    ("dinbych", "denbigh"),
+    ("rhuthun", "ruthin"),
+    ("llanelwy", "st asaph"),
+    ("y fflint", "flint"),
+    ("yr wyddgrug", "mold"),
+    ("treffynnon", "holywell"),
+    ("caernarfon", "caernarvon"),
+    ("ynys mon", "anglesey"),
+    ("sir benfro", "pembrokeshire"),
+    ("sir gaerfyrddin", "carmarthenshire"),
+    ("sir ddinbych", "denbighshire"),
+    ("sir y fflint", "flintshire"),
+    ("sir fynwy", "monmouthshire"),
+    ("bro morgannwg", "vale of glamorgan"),
+    ("morgannwg", "glamorgan"),
+];
+
+/// Get the key of a place name for finding its other name.
+///
+/// - Lowercase, without accents such as the circumflex in "Ynys Môn".
+///
+/// - Words are separated by single spaces; hyphens separate words, and
+///   apostrophes and full stops are removed, so "St. David's" is "st davids".
+///
+pub fn place_key(name: &str) -> String {
+    let folded: String = name.to_lowercase()
+        .nfd()
+        .filter(|&c| !is_combining_mark(c) && !matches!(c, '\'' | '’' | '.'))
+        .map(|c| if c == '-' { ' ' } else { c })
+        .collect();
+    folded.split_whitespace().collect::<Vec<&str>>().join(" ")
+}
+
+/// Get the English name of a place as a key, or the key of the name itself
+/// when it has no English name.
+pub fn english_place_key(name: &str) -> String {
+    let key = place_key(name);
+    BILINGUAL.iter()
+        .find(|(welsh, _)| *welsh == key)
+        .map_or(key, |(_, english)| english.to_string())
+}
+
+/// True when two different names are the Welsh name and the English name of
+/// the same place, such as "Caerdydd" and "Cardiff".
+pub fn are_bilingual_equivalents(a: &str, b: &str) -> bool {
+    place_key(a) != place_key(b) && english_place_key(a) == english_place_key(b)
+}
+
+/// Calculate the similarity of two place names.
+///
+/// - If either name is blank, then return 0.0 meaning no similarity.
+///
+/// - If the names are the same place, such as "Caerdydd" and "Cardiff",
+///   or "Pen-y-bont ar Ogwr" and "bridgend", then return 1.0.
+///
+/// - Otherwise, return the [similarity_of_strings] of the names.
+///
+pub fn similarity_of_place_names(input: (&str, &str)) -> f64 {
+    let (a, b) = input;
+    if a.is_empty() || b.is_empty() {
+        0.0
+    }
+    else if english_place_key(a) == english_place_key(b) {
+        1.0
+    }
+    else {
+        similarity_of_strings((a, b))
+    }
+}
+
+/// Place name comparator; see [similarity_of_place_names].
+pub struct PlaceName;
+
+impl StringComparator for PlaceName {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        similarity_of_place_names((a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_place_key() {
+        assert_eq!(place_key(" Ynys  Môn "), "ynys mon");
+        assert_eq!(place_key("Pen-y-bont ar Ogwr"), "pen y bont ar ogwr");
+        assert_eq!(place_key("St. David's"), "st davids");
+    }
+
+    #[test]
+    fn test_english_place_key() {
+        assert_eq!(english_place_key("Caerdydd"), "cardiff");
+        assert_eq!(english_place_key("CARDIFF"), "cardiff");
+        assert_eq!(english_place_key("Pont-y-pŵl"), "pontypool");
+        assert_eq!(english_place_key("Aberystwyth"), "aberystwyth");
+    }
+
+    #[test]
+    fn test_are_bilingual_equivalents() {
+        assert!(are_bilingual_equivalents("Caerdydd", "Cardiff"));
+        assert!(are_bilingual_equivalents("Swansea", "Abertawe"));
+        assert!(are_bilingual_equivalents("Tyddewi", "St. David's"));
+        assert!(!are_bilingual_equivalents("Cardiff", "cardiff"));
+        assert!(!are_bilingual_equivalents("Caerdydd", "Swansea"));
+    }
+
+    #[test]
+    fn test_similarity_of_place_names() {
+        assert_eq!(similarity_of_place_names(("", "Cardiff")), 0.0);
+        assert_eq!(similarity_of_place_names(("Cardiff", "Cardiff")), 1.0);
+        assert_eq!(similarity_of_place_names(("Caerdydd", "Cardiff")), 1.0);
+        assert_eq!(similarity_of_place_names(("Abertawe", "swansea")), 1.0);
+        assert_eq!(similarity_of_place_names(("Y Fenni", "Abergavenny")), 1.0);
+        assert_eq!(similarity_of_place_names(("Cardiff", "Cardif")), similarity_of_strings(("Cardiff", "Cardif")));
+        assert!(similarity_of_place_names(("Caerdydd", "Swansea")) < 0.5);
+    }
+
+}
@@ -0,0 +1,250 @@
+// Parsing and comparison of UK postcodes.
+//
+// A UK postcode has an outward code and an inward code, such as "CF10 1AA".
+// Its parts nest, from the largest to the smallest:
+//
+// - Area: the letters that start the outward code, such as "CF".
+// - District: the outward code, such as "CF10".
+// - Sector: the district and the digit of the inward code, such as "CF10 1".
+// - Unit: the whole postcode, such as "CF10 1AA".
+//
+// Comparing postcodes as text fails on formatting alone: "CF10 1AA", "cf101aa"
+// and "CF10  1AA" are the same postcode. The postcode comparison first parses
+// each postcode, then compares by the smallest part that the postcodes share:
+//
+// - Equal units score 1.0.
+// - Equal sectors score [POSTCODE_SECTOR], such as a neighbour or a typo in the last letters.
+// - Equal districts score [POSTCODE_DISTRICT].
+// - Equal areas score [POSTCODE_AREA].
+// - Other postcodes score 0.0.
+//
+// A postcode that cannot be parsed is invalid, and its comparison has no score
+// and a note that says so, rather than a low score from comparing text.
+
+use serde::{Deserialize, Serialize};
+use similarity_trait::Similarity;
+
+/// The sub-score of two postcodes in the same sector, such as "CF10 1AA" and "CF10 1AB".
+pub const POSTCODE_SECTOR: f64 = 0.8;
+
+/// The sub-score of two postcodes in the same district, such as "CF10 1AA" and "CF10 3AT".
+pub const POSTCODE_DISTRICT: f64 = 0.6;
+
+/// The sub-score of two postcodes in the same area, such as "CF10 1AA" and "CF24 4HQ".
+pub const POSTCODE_AREA: f64 = 0.3;
+
+/// How postcodes are compared.
+///
+/// - `Levels`: parse each postcode, then compare by unit, sector, district,
+///   and area; see [compare_postcodes].
+///
+/// - `Text`: compare with the comparators of the field.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostcodeComparator {
+    #[default]
+    Levels,
+    Text,
+}
+
+/// A UK postcode, as an outward code and an inward code, in uppercase.
+///
+/// Parsing ignores case and whitespace, so "cf101aa" is "CF10 1AA".
+///
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Postcode {
+    pub outward: String,
+    pub inward: String,
+}
+
+impl Postcode {
+
+    /// Get the area, such as "CF" of "CF10 1AA".
+    pub fn area(&self) -> &str {
+        let letters = self.outward.bytes().take_while(|b| b.is_ascii_uppercase()).count();
+        &self.outward[..letters]
+    }
+
+    /// Get the district, such as "CF10" of "CF10 1AA".
+    pub fn district(&self) -> &str {
+        &self.outward
+    }
+
+    /// Get the sector, such as "CF10 1" of "CF10 1AA".
+    pub fn sector(&self) -> String {
+        format!("{} {}", self.outward, &self.inward[..1])
+    }
+
+}
+
+impl std::str::FromStr for Postcode {
+    type Err = String;
+
+    /// Parse a postcode, such as "CF10 1AA", "cf101aa", or "GIR 0AA".
+    ///
+    /// The outward code is one or two letters, a digit, then an optional
+    /// digit or letter; the inward code is a digit then two letters.
+    ///
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let text: String = s.chars().filter(|c| !c.is_whitespace()).collect::<String>().to_ascii_uppercase();
+        if !(5..=7).contains(&text.len()) || !text.is_ascii() {
+            return Err(format!("postcode {:?} must have five to seven letters and digits", s));
+        }
+        let (outward, inward) = text.split_at(text.len() - 3);
+        if !(is_outward(outward) || (outward, inward) == ("GIR", "0AA")) || !is_inward(inward) {
+            return Err(format!("postcode {:?} is not a UK postcode", s));
+        }
+        Ok(Postcode { outward: outward.to_string(), inward: inward.to_string() })
+    }
+}
+
+// Display the postcode in the usual form, such as "CF10 1AA".
+impl std::fmt::Display for Postcode {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "{} {}", self.outward, self.inward)
+    }
+}
+
+// True when the text is an outward code: one or two letters, a digit,
+// then an optional digit or letter.
+fn is_outward(s: &str) -> bool {
+    let letters = s.bytes().take_while(|b| b.is_ascii_uppercase()).count();
+    (1..=2).contains(&letters) && match s.as_bytes()[letters..] {
+        [digit] => digit.is_ascii_digit(),
+        [digit, x] => digit.is_ascii_digit() && x.is_ascii_alphanumeric(),
+        _ => false,
+    }
+}
+
+// True when the text is an inward code: a digit then two letters.
+fn is_inward(s: &str) -> bool {
+    matches!(s.as_bytes(), [digit, x, y] if digit.is_ascii_digit() && x.is_ascii_uppercase() && y.is_ascii_uppercase())
+}
+
+/// A note on the comparison of two postcodes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum PostcodeNote {
+    Sector,
+    District,
+    Area,
+    Invalid,
+}
+
+impl PostcodeNote {
+    /// Get the name of the note, as in the explanation.
+    pub fn name(&self) -> &'static str {
+        match self {
+            PostcodeNote::Sector => "postcode_sector",
+            PostcodeNote::District => "postcode_district",
+            PostcodeNote::Area => "postcode_area",
+            PostcodeNote::Invalid => "postcode_invalid",
+        }
+    }
+}
+
+/// The comparison of two postcodes.
+///
+/// - `value`: the sub-score, which is `None` when either postcode is invalid.
+///
+/// - `note`: the note, if any, which names the level of a partial agreement.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct PostcodeComparison {
+    pub value: Option<f64>,
+    pub note: Option<PostcodeNote>,
+}
+
+/// Compare two postcodes by unit, sector, district, and area.
+pub fn compare_postcodes(a: &str, b: &str) -> PostcodeComparison {
+    let (a, b) = match (a.parse::<Postcode>(), b.parse::<Postcode>()) {
+        (Ok(a), Ok(b)) => (a, b),
+        _ => return PostcodeComparison { value: None, note: Some(PostcodeNote::Invalid) },
+    };
+    compare_parsed_postcodes(&a, &b)
+}
+
+/// Compare two parsed postcodes; see [compare_postcodes].
+pub fn compare_parsed_postcodes(a: &Postcode, b: &Postcode) -> PostcodeComparison {
+    let (value, note) = if a == b {
+        (1.0, None)
+    }
+    else if a.sector() == b.sector() {
+        (POSTCODE_SECTOR, Some(PostcodeNote::Sector))
+    }
+    else if a.district() == b.district() {
+        (POSTCODE_DISTRICT, Some(PostcodeNote::District))
+    }
+    else if a.area() == b.area() {
+        (POSTCODE_AREA, Some(PostcodeNote::Area))
+    }
+    else {
+        (0.0, None)
+    };
+    PostcodeComparison { value: Some(value), note }
+}
+
+impl Similarity<(&Postcode, &Postcode), f64> for Postcode {
+    /// The sub-score of two parsed postcodes; see [compare_parsed_postcodes].
+    fn similarity(input: (&Postcode, &Postcode)) -> f64 {
+        compare_parsed_postcodes(input.0, input.1).value.unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        for (s, expect) in [
+            ("CF10 1AA", "CF10 1AA"),
+            ("cf101aa", "CF10 1AA"),
+            (" SA1  1AA ", "SA1 1AA"),
+            ("W1A 1AA", "W1A 1AA"),
+            ("EC1A 1BB", "EC1A 1BB"),
+            ("LL57 2DG", "LL57 2DG"),
+            ("GIR 0AA", "GIR 0AA"),
+        ] {
+            assert_eq!(s.parse::<Postcode>().unwrap().to_string(), expect, "{}", s);
+        }
+        for s in ["", "CF10", "CF10 1A", "CF10 1AAA", "1F10 1AA", "CFX10 1AA", "CF10 AAA", "CF10 11A", "CF10-1AA", "CF10 1ÄA"] {
+            assert!(s.parse::<Postcode>().is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn test_parts() {
+        let postcode: Postcode = "EC1A 1BB".parse().unwrap();
+        assert_eq!(postcode.area(), "EC");
+        assert_eq!(postcode.district(), "EC1A");
+        assert_eq!(postcode.sector(), "EC1A 1");
+        let postcode: Postcode = "W1A 1AA".parse().unwrap();
+        assert_eq!(postcode.area(), "W");
+    }
+
+    #[test]
+    fn test_compare_postcodes() {
+        for (a, b, value, note) in [
+            ("CF10 1AA", "cf101aa", Some(1.0), None),
+            ("CF10 1AA", "CF10 1AB", Some(POSTCODE_SECTOR), Some(PostcodeNote::Sector)),
+            ("CF10 1AA", "CF10 3AT", Some(POSTCODE_DISTRICT), Some(PostcodeNote::District)),
+            ("CF10 1AA", "CF24 4HQ", Some(POSTCODE_AREA), Some(PostcodeNote::Area)),
+            ("CF10 1AA", "C10 1AA", Some(0.0), None),
+            ("CF10 1AA", "SA1 1AA", Some(0.0), None),
+            ("CF10 1AA", "CF10", None, Some(PostcodeNote::Invalid)),
+        ] {
+            assert_eq!(compare_postcodes(a, b), PostcodeComparison { value, note }, "{} {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_similarity_trait() {
+        let postcode = |s: &str| s.parse::<Postcode>().unwrap();
+        assert_eq!(Postcode::similarity((&postcode("CF10 1AA"), &postcode("cf101aa"))), 1.0);
+        assert_eq!(Postcode::similarity((&postcode("CF10 1AA"), &postcode("CF10 3AT"))), POSTCODE_DISTRICT);
+        assert_eq!(Postcode::similarity((&postcode("CF10 1AA"), &postcode("SA1 1AA"))), 0.0);
+    }
+
+}
@@ -0,0 +1,220 @@
+// Estimate u-probabilities by random sampling of record pairs.
+//
+// The u-probability of a comparison level is the chance that two records
+// that are not a match fall into that level. Almost every random pair of
+// records is not a match, so the level frequencies among random pairs are
+// good estimates of the u-probabilities.
+//
+// Enumerating every pair grows with the square of the record count, so
+// instead we draw a fixed number of random pairs. The random number generator
+// is ChaCha8 with a fixed seed, so the same seed and records give the same
+// sample on every run and every platform.
+
+use rand::{Rng, SeedableRng};
+use rand_chacha::ChaCha8Rng;
+use serde::Serialize;
+use crate::data::DATA;
+use crate::models::person::Person;
+use crate::services::expectation_maximisation::{PROBABILITY_MIN, comparison_vector_of_persons};
+use crate::services::fellegi_sunter::{ComparisonLevel, FellegiSunterParameters};
+use crate::services::similarity::{Field, PerField};
+use crate::services::similarity_config::SimilarityProfile;
+
+/// The number of sampled pairs at each comparison level of one field.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize)]
+pub struct LevelCounts {
+    pub exact: usize,
+    pub close: usize,
+    pub far: usize,
+    pub missing: usize,
+}
+
+impl LevelCounts {
+    /// Get the count of one comparison level.
+    pub fn get(&self, level: ComparisonLevel) -> usize {
+        match level {
+            ComparisonLevel::Exact => self.exact,
+            ComparisonLevel::Close => self.close,
+            ComparisonLevel::Far => self.far,
+            ComparisonLevel::Missing => self.missing,
+        }
+    }
+
+    /// Add one to the count of one comparison level.
+    pub fn increment(&mut self, level: ComparisonLevel) {
+        match level {
+            ComparisonLevel::Exact => self.exact += 1,
+            ComparisonLevel::Close => self.close += 1,
+            ComparisonLevel::Far => self.far += 1,
+            ComparisonLevel::Missing => self.missing += 1,
+        }
+    }
+}
+
+impl Default for PerField<LevelCounts> {
+    fn default() -> Self {
+        PerField {
+            given_name: LevelCounts::default(),
+            family_name: LevelCounts::default(),
+            birth_date_year: LevelCounts::default(),
+            birth_date_month: LevelCounts::default(),
+            birth_date_month_day: LevelCounts::default(),
+            primary_phone: LevelCounts::default(),
+            primary_email: LevelCounts::default(),
+            nhs_number: LevelCounts::default(),
+            address_lines: LevelCounts::default(),
+            town: LevelCounts::default(),
+            postcode: LevelCounts::default(),
+        }
+    }
+}
+
+/// The result of sampling random pairs.
+///
+/// - `seed`: the random number generator seed.
+/// - `pairs`: the number of sampled pairs.
+/// - `frequencies`: the count of each comparison level of each field.
+///
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct USample {
+    pub seed: u64,
+    pub pairs: usize,
+    pub frequencies: PerField<LevelCounts>,
+}
+
+impl USample {
+
+    /// The estimated u-probability of one comparison level of one field.
+    ///
+    /// This is at least [PROBABILITY_MIN], so that every match weight stays finite.
+    ///
+    pub fn u(&self, field: Field, level: ComparisonLevel) -> f64 {
+        if self.pairs == 0 {
+            return PROBABILITY_MIN;
+        }
+        (self.frequencies.get(field).get(level) as f64 / self.pairs as f64).clamp(PROBABILITY_MIN, 1.0)
+    }
+
+    /// Copy parameters, replacing the u-probability of each level with the
+    /// estimate, except the missing level.
+    ///
+    /// The missing level keeps its m-probability and u-probability, so that
+    /// a missing value stays neutral even when most sampled pairs lack it.
+    ///
+    pub fn apply(&self, parameters: &FellegiSunterParameters) -> FellegiSunterParameters {
+        let mut parameters = parameters.clone();
+        for field in Field::ALL {
+            for level in ComparisonLevel::ALL {
+                if level == ComparisonLevel::Missing {
+                    continue;
+                }
+                parameters.fields.get_mut(field).level_mut(level).u = self.u(field, level);
+            }
+        }
+        parameters
+    }
+
+}
+
+/// Draw random pairs of distinct indexes from 0 to `len`, with a fixed seed.
+///
+/// Pairs are drawn with replacement, so the count of pairs can exceed the
+/// count of distinct pairs. Fewer than two items gives no pairs.
+///
+pub fn random_pairs(len: usize, count: usize, seed: u64) -> Vec<(usize, usize)> {
+    if len < 2 {
+        return Vec::new();
+    }
+    let mut rng = ChaCha8Rng::seed_from_u64(seed);
+    (0..count).map(|_| {
+        let i = rng.random_range(0..len);
+        let mut j = rng.random_range(0..len - 1);
+        if j >= i {
+            j += 1;
+        }
+        (i, j)
+    }).collect()
+}
+
+/// Sample random pairs of persons and count the comparison levels of each field.
+///
+/// The comparators and close thresholds come from the profile.
+///
+pub fn sample(persons: &[Person], count: usize, seed: u64, profile: &SimilarityProfile) -> USample {
+    let mut frequencies: PerField<LevelCounts> = PerField::default();
+    let pairs = random_pairs(persons.len(), count, seed);
+    for &(i, j) in pairs.iter() {
+        let vector = comparison_vector_of_persons((&persons[i], &persons[j]), profile);
+        for field in Field::ALL {
+            frequencies.get_mut(field).increment(*vector.get(field));
+        }
+    }
+    USample { seed, pairs: pairs.len(), frequencies }
+}
+
+/// Sample random pairs of persons in our DATA.
+///
+/// The persons are sorted by id first, because the order of a `HashMap`
+/// varies between runs, and the sample must not.
+///
+pub fn sample_from_data(count: usize, seed: u64, profile: &SimilarityProfile) -> USample {
+    let mut persons: Vec<Person> = {
+        let data = DATA.lock().unwrap();
+        data.values().cloned().collect()
+    };
+    persons.sort_by(|a, b| a.id.cmp(&b.id));
+    sample(&persons, count, seed, profile)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_random_pairs() {
+        let pairs = random_pairs(10, 1000, 42);
+        assert_eq!(pairs.len(), 1000);
+        assert!(pairs.iter().all(|&(i, j)| i < 10 && j < 10 && i != j));
+        assert_eq!(pairs, random_pairs(10, 1000, 42));
+        assert_ne!(pairs, random_pairs(10, 1000, 43));
+        assert!(random_pairs(1, 1000, 42).is_empty());
+    }
+
+    #[test]
+    fn test_sample_from_data() {
+        let profile = SimilarityProfile::default();
+        let sample = sample_from_data(500, 7, &profile);
+        assert_eq!(sample.pairs, 500);
+        assert_eq!(sample, sample_from_data(500, 7, &profile));
+        for field in Field::ALL {
+            let total: usize = ComparisonLevel::ALL.iter().map(|&level| sample.frequencies.get(field).get(level)).sum();
+            assert_eq!(total, 500);
+        }
+        // Our DATA has no birth dates, and every given name differs.
+        assert_eq!(sample.frequencies.birth_date_year.missing, 500);
+        assert_eq!(sample.frequencies.given_name.exact, 0);
+        assert_eq!(sample.u(Field::BirthDateYear, ComparisonLevel::Missing), 1.0);
+        assert_eq!(sample.u(Field::GivenName, ComparisonLevel::Exact), PROBABILITY_MIN);
+    }
+
+    #[test]
+    fn test_apply() {
+        let parameters = FellegiSunterParameters::default();
+        let sample = sample_from_data(100, 7, &SimilarityProfile::default());
+        let applied = sample.apply(&parameters);
+        assert!(applied.validate().is_ok());
+        assert_eq!(applied.prior, parameters.prior);
+        assert_eq!(applied.fields.given_name.exact.m, parameters.fields.given_name.exact.m);
+        assert_eq!(applied.fields.given_name.far.u, sample.u(Field::GivenName, ComparisonLevel::Far));
+    }
+
+    #[test]
+    fn test_apply_missing() {
+        // Our DATA has no birth dates, so every sampled pair is missing them.
+        let parameters = FellegiSunterParameters::default();
+        let applied = sample_from_data(100, 7, &SimilarityProfile::default()).apply(&parameters);
+        assert_eq!(applied.fields.birth_date_year.missing, parameters.fields.birth_date_year.missing);
+        assert_eq!(applied.fields.birth_date_year.missing.match_weight(), 0.0);
+    }
+
+}
@@ -1,4 +1,19 @@
+use serde::{Deserialize, Serialize};
+use similarity_trait::Similarity;
+use crate::models::other_name::NameKind;
 use crate::models::person::Person;
+use crate::services::similarity_config::SimilarityProfile;
+use crate::services::fellegi_sunter::{self, ComparisonLevel};
+use crate::services::birth_date::{BirthDateComparator, DateNearMiss, compare_birth_dates_of_persons};
+use crate::services::comparators::{COMPARATORS, ComparatorRegistry};
+use crate::services::email::{EmailComparator, compare_emails};
+use crate::services::phone::{DEFAULT_PHONE_REGION, PhoneComparator, PhoneComparison, PhoneRegion, compare_phones};
+use crate::services::postcode::{PostcodeComparator, PostcodeComparison, compare_postcodes};
+use crate::services::place_names::are_bilingual_equivalents;
+use crate::services::nicknames::{NICKNAMES, NicknameCredit, NicknameDictionary};
+use crate::services::normalization::Normalizer;
+use crate::services::term_frequency::term_frequencies;
+use crate::services::name_parser::initial_agrees;
 
 pub const GIVEN_NAME_EQ: f64 = 0.8;
 pub const FAMILY_NAME_EQ: f64 = 1.0;
@@ -8,6 +23,18 @@ pub const BIRTH_DATE_MONTH_DAY_EQ: f64 = 0.1;
 pub const PRIMARY_PHONE_EQ: f64 = 0.6;
 pub const PRIMARY_EMAIL_EQ: f64 = 0.7;
 
+/// The NHS number has no weight in the weighted average, so that persons
+/// without NHS numbers score as before; instead, an agreement or disagreement
+/// adjusts the odds of the score; see [crate::services::nhs_number::NhsNumberEvidence].
+pub const NHS_NUMBER_EQ: f64 = 0.0;
+
+/// The address fields are not in [SIMILARITY_MAX], because by default a
+/// missing address is ignored rather than a half penalty, so that persons
+/// without addresses score as before; see [MissingPolicy::Ignore].
+pub const ADDRESS_LINES_EQ: f64 = 0.5;
+pub const TOWN_EQ: f64 = 0.2;
+pub const POSTCODE_EQ: f64 = 0.6;
+
 pub const SIMILARITY_MAX: f64 = 
     GIVEN_NAME_EQ +
     FAMILY_NAME_EQ +
@@ -15,65 +42,1083 @@ pub const SIMILARITY_MAX: f64 =
     BIRTH_DATE_MONTH_EQ +
     BIRTH_DATE_MONTH_DAY_EQ +
     PRIMARY_PHONE_EQ +
-    PRIMARY_EMAIL_EQ;
+    PRIMARY_EMAIL_EQ +
+    NHS_NUMBER_EQ;
+
+/// The fields of a person that take part in similarity.
+/// 
+/// The order of [Field::ALL] is the order in which scores are summed.
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Field {
+    GivenName,
+    FamilyName,
+    BirthDateYear,
+    BirthDateMonth,
+    BirthDateMonthDay,
+    PrimaryPhone,
+    PrimaryEmail,
+    NhsNumber,
+    AddressLines,
+    Town,
+    Postcode,
+}
+
+impl Field {
+    pub const ALL: [Field; 11] = [
+        Field::GivenName,
+        Field::FamilyName,
+        Field::BirthDateYear,
+        Field::BirthDateMonth,
+        Field::BirthDateMonthDay,
+        Field::PrimaryPhone,
+        Field::PrimaryEmail,
+        Field::NhsNumber,
+        Field::AddressLines,
+        Field::Town,
+        Field::Postcode,
+    ];
+
+    /// Get the name of the field, as in the config.
+    pub fn name(&self) -> &'static str {
+        match self {
+            Field::GivenName => "given_name",
+            Field::FamilyName => "family_name",
+            Field::BirthDateYear => "birth_date_year",
+            Field::BirthDateMonth => "birth_date_month",
+            Field::BirthDateMonthDay => "birth_date_month_day",
+            Field::PrimaryPhone => "primary_phone",
+            Field::PrimaryEmail => "primary_email",
+            Field::NhsNumber => "nhs_number",
+            Field::AddressLines => "address_lines",
+            Field::Town => "town",
+            Field::Postcode => "postcode",
+        }
+    }
+
+    /// Get a field by name, as in the config.
+    pub fn from_name(name: &str) -> Option<Self> {
+        Field::ALL.into_iter().find(|field| field.name() == name)
+    }
+}
+
+/// Get the value of one field of a person as text, if the person has the field.
+/// 
+/// The numeric fields are formatted as decimal numbers,
+/// and the address lines are joined by spaces.
+/// 
+pub fn field_text(person: &Person, field: Field) -> Option<String> {
+    match field {
+        Field::GivenName => person.given_name.clone(),
+        Field::FamilyName => person.family_name.clone(),
+        Field::BirthDateYear => person.birth_date.map(|x| x.get_year().to_string()),
+        Field::BirthDateMonth => person.birth_date.and_then(|x| x.get_month()).map(|x| x.to_string()),
+        Field::BirthDateMonthDay => person.birth_date.and_then(|x| x.get_day()).map(|x| x.to_string()),
+        Field::PrimaryPhone => person.primary_phone.clone(),
+        Field::PrimaryEmail => person.primary_email.clone(),
+        Field::NhsNumber => person.nhs_number.as_ref().map(|x| x.as_str().to_string()),
+        Field::AddressLines => person.address.as_ref().map(|x| x.lines.join(" ")).filter(|x| !x.is_empty()),
+        Field::Town => person.address.as_ref().and_then(|x| x.town.clone()),
+        Field::Postcode => person.address.as_ref().and_then(|x| x.postcode.clone()),
+    }
+}
+
+/// One value for each field of a person that takes part in similarity,
+/// such as the weight of each field, or the sub-score of each field.
+/// 
+/// When deserializing, any omitted field uses the value from [Default].
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields, bound(deserialize = "T: Deserialize<'de>, PerField<T>: Default"))]
+pub struct PerField<T> {
+    pub given_name: T,
+    pub family_name: T,
+    pub birth_date_year: T,
+    pub birth_date_month: T,
+    pub birth_date_month_day: T,
+    pub primary_phone: T,
+    pub primary_email: T,
+    pub nhs_number: T,
+    pub address_lines: T,
+    pub town: T,
+    pub postcode: T,
+}
+
+impl<T> PerField<T> {
+    /// Get the value for one field.
+    pub fn get(&self, field: Field) -> &T {
+        match field {
+            Field::GivenName => &self.given_name,
+            Field::FamilyName => &self.family_name,
+            Field::BirthDateYear => &self.birth_date_year,
+            Field::BirthDateMonth => &self.birth_date_month,
+            Field::BirthDateMonthDay => &self.birth_date_month_day,
+            Field::PrimaryPhone => &self.primary_phone,
+            Field::PrimaryEmail => &self.primary_email,
+            Field::NhsNumber => &self.nhs_number,
+            Field::AddressLines => &self.address_lines,
+            Field::Town => &self.town,
+            Field::Postcode => &self.postcode,
+        }
+    }
+
+    /// Get the value for one field, for changing it.
+    pub fn get_mut(&mut self, field: Field) -> &mut T {
+        match field {
+            Field::GivenName => &mut self.given_name,
+            Field::FamilyName => &mut self.family_name,
+            Field::BirthDateYear => &mut self.birth_date_year,
+            Field::BirthDateMonth => &mut self.birth_date_month,
+            Field::BirthDateMonthDay => &mut self.birth_date_month_day,
+            Field::PrimaryPhone => &mut self.primary_phone,
+            Field::PrimaryEmail => &mut self.primary_email,
+            Field::NhsNumber => &mut self.nhs_number,
+            Field::AddressLines => &mut self.address_lines,
+            Field::Town => &mut self.town,
+            Field::Postcode => &mut self.postcode,
+        }
+    }
+}
+
+/// The default comparators of name fields, which use Welsh rules;
+/// see [crate::services::welsh::similarity_of_welsh_names].
+pub const NAME_COMPARATORS: [&str; 1] = ["welsh_name"];
+
+/// The default comparators of the town, which count Welsh and English
+/// place names as equivalents; see [crate::services::place_names::similarity_of_place_names].
+pub const PLACE_COMPARATORS: [&str; 1] = ["place_name"];
+
+/// The default comparators of other text fields, whose average is [similarity_of_strings].
+pub const TEXT_COMPARATORS: [&str; 3] = ["jaro_winkler", "damerau_levenshtein", "sorensen_dice"];
+
+/// The default comparators of numeric fields, which is equality.
+pub const NUMBER_COMPARATORS: [&str; 1] = ["exact"];
+
+/// The weight of each field, using the constants above.
+/// 
+/// These are the weights of the built-in profile; see [SimilarityProfile].
+/// 
+pub const WEIGHTS: PerField<f64> = PerField {
+    given_name: GIVEN_NAME_EQ,
+    family_name: FAMILY_NAME_EQ,
+    birth_date_year: BIRTH_DATE_YEAR_EQ,
+    birth_date_month: BIRTH_DATE_MONTH_EQ,
+    birth_date_month_day: BIRTH_DATE_MONTH_DAY_EQ,
+    primary_phone: PRIMARY_PHONE_EQ,
+    primary_email: PRIMARY_EMAIL_EQ,
+    nhs_number: NHS_NUMBER_EQ,
+    address_lines: ADDRESS_LINES_EQ,
+    town: TOWN_EQ,
+    postcode: POSTCODE_EQ,
+};
+
+impl Similarity<(&Person, &Person), f64> for Person {
+    /// Calculate the similarity probability of two persons.
+    /// 
+    /// This function compares these fields:
+    /// 
+    /// - Given name
+    /// - Family name
+    /// - Birth date year, month, month day
+    /// - Primary email
+    /// - Primary phone
+    /// - Address lines, town, and postcode
+    /// 
+    /// This is a thin wrapper over [similarity_report_of_persons],
+    /// using the built-in profile.
+    /// 
+    fn similarity(input: (&Person, &Person)) -> f64 {
+        similarity_report_of_persons(input, &SimilarityProfile::default()).score
+    }
+}
+
+impl Similarity<(&Person, &Person, &SimilarityProfile), SimilarityReport> for Person {
+    /// Calculate the similarity of two persons using a profile, with an explanation per field.
+    /// 
+    /// This is a thin wrapper over [similarity_report_of_persons].
+    /// 
+    fn similarity(input: (&Person, &Person, &SimilarityProfile)) -> SimilarityReport {
+        let (a, b, profile) = input;
+        similarity_report_of_persons((a, b), profile)
+    }
+}
+
+/// How field sub-scores are combined into one score.
+/// 
+/// - `Weighted`: a weighted average of sub-scores; see [similarity_report_of_sub_scores].
+/// 
+/// - `FellegiSunter`: a probabilistic match weight and match probability;
+///   see [fellegi_sunter::similarity_report_of_sub_scores].
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ScoringMode {
+    #[default]
+    Weighted,
+    FellegiSunter,
+}
+
+/// How a field counts in weighted mode when either person lacks it.
+/// 
+/// - `HalfPenalty`: count half the weight of the field as a sub-score of 0.0;
+///   this is the legacy formula `x / ((max + SIMILARITY_MAX) / 2.0)`.
+/// 
+/// - `Ignore`: leave the field out, so it neither helps nor hurts.
+/// 
+/// - `Neutral`: count the field as a sub-score of 0.5 with its full weight.
+/// 
+/// - `Penalise`: count the field as a sub-score of 0.0 with its full weight.
+/// 
+/// Fellegi–Sunter mode uses the missing comparison level instead.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MissingPolicy {
+    #[default]
+    HalfPenalty,
+    Ignore,
+    Neutral,
+    Penalise,
+}
+
+impl MissingPolicy {
+    /// Get the sub-score that a missing field counts as with its full weight, if any.
+    pub fn counted_sub_score(&self) -> Option<f64> {
+        match self {
+            MissingPolicy::Neutral => Some(0.5),
+            MissingPolicy::Penalise => Some(0.0),
+            MissingPolicy::HalfPenalty | MissingPolicy::Ignore => None,
+        }
+    }
+}
+
+/// The factor of the token score of two names with different numbers of
+/// tokens, by default, such as when a middle name is dropped.
+pub const TOKEN_SUBSET_CREDIT: f64 = 0.9;
+
+/// The most tokens in either name for best assignment;
+/// longer names use Monge–Elkan instead, which is faster.
+pub const TOKEN_ASSIGNMENT_MAX: usize = 8;
+
+/// How the tokens of two names are paired.
+/// 
+/// - `MongeElkan`: each token of the name with fewer tokens takes its most
+///   similar token of the other name, so a token can be taken more than once.
+/// 
+/// - `Assignment`: each token of the name with fewer tokens takes a different
+///   token of the other name, choosing the pairing with the greatest total.
+/// 
+/// - `Whole`: compare whole names only, which turns token comparison off.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenMethod {
+    MongeElkan,
+    #[default]
+    Assignment,
+    Whole,
+}
+
+/// Token-aware comparison of a multi-part name, such as "Mary Jane",
+/// "Rhys-Jones", or "ap Hywel Evans".
+/// 
+/// Each name splits into tokens on spaces and hyphens; see [name_tokens].
+/// The token score is the average similarity of the paired tokens, by the
+/// method. When the names have different numbers of tokens, such as when a
+/// middle name is dropped, the token score is multiplied by the subset credit,
+/// so a name that is a subset of the other gets strong partial credit.
+/// 
+/// The field value is the greater of the comparator value and the token score.
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenComparison {
+    pub method: TokenMethod,
+    pub subset_credit: f64,
+}
+
+impl Default for TokenComparison {
+    fn default() -> Self {
+        TokenComparison {
+            method: TokenMethod::default(),
+            subset_credit: TOKEN_SUBSET_CREDIT,
+        }
+    }
+}
+
+impl TokenComparison {
+
+    /// Compare the tokens of two names, using a comparator of two tokens.
+    /// 
+    /// If either name has no tokens, or the method is whole, then return 0.0
+    /// meaning no similarity.
+    /// 
+    pub fn compare(&self, a: &str, b: &str, compare: impl Fn(&str, &str) -> f64) -> f64 {
+        let (mut a, mut b) = (name_tokens(a), name_tokens(b));
+        if a.is_empty() || b.is_empty() || self.method == TokenMethod::Whole {
+            return 0.0;
+        }
+        if a.len() > b.len() {
+            std::mem::swap(&mut a, &mut b);
+        }
+        let values: Vec<Vec<f64>> = a.iter().map(|x| b.iter().map(|y| compare(x, y)).collect()).collect();
+        let total = match self.method {
+            TokenMethod::Assignment if b.len() <= TOKEN_ASSIGNMENT_MAX => best_assignment(&values, 0, 0),
+            _ => values.iter().map(|row| row.iter().cloned().fold(0.0, f64::max)).sum(),
+        };
+        let score = total / a.len() as f64;
+        if a.len() == b.len() { score } else { score * self.subset_credit }
+    }
+
+    /// Apply the token score to a comparator value of two names.
+    pub fn apply(&self, value: f64, a: &str, b: &str, compare: impl Fn(&str, &str) -> f64) -> f64 {
+        value.max(self.compare(a, b, compare))
+    }
+
+}
+
+/// Split a name into tokens on spaces and hyphens, such as "Rhys-Jones"
+/// into "Rhys" and "Jones".
+pub fn name_tokens(name: &str) -> Vec<&str> {
+    name.split(|c: char| c.is_whitespace() || c == '-')
+        .filter(|token| !token.is_empty())
+        .collect()
+}
+
+// The greatest total of a pairing of rows, from the row onwards, with
+// different columns, where `used` marks the columns taken by earlier rows.
+fn best_assignment(values: &[Vec<f64>], row: usize, used: u64) -> f64 {
+    if row == values.len() {
+        return 0.0;
+    }
+    values[row].iter().enumerate()
+        .filter(|&(column, _)| used & (1 << column) == 0)
+        .map(|(column, value)| value + best_assignment(values, row + 1, used | (1 << column)))
+        .fold(0.0, f64::max)
+}
+
+/// The decision for a pair of persons, from the score and the profile thresholds.
+/// 
+/// - `Match`: the score is at least the match threshold; decide automatically.
+/// 
+/// - `PossibleMatch`: the score is at least the possible match threshold;
+///   send to clerical review.
+/// 
+/// - `NonMatch`: the score is below both thresholds; decide automatically.
+/// 
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MatchDecision {
+    Match,
+    PossibleMatch,
+    #[default]
+    NonMatch,
+}
+
+/// The score thresholds for each match decision.
+/// 
+/// These apply to the report score, so in Fellegi–Sunter mode they are
+/// posterior match probabilities.
+/// 
+/// When deserializing, any omitted threshold uses the value from [Default].
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct MatchThresholds {
+    pub match_min: f64,
+    pub possible_match_min: f64,
+}
+
+pub const MATCH_MIN: f64 = 0.9;
+pub const POSSIBLE_MATCH_MIN: f64 = 0.7;
+
+impl Default for MatchThresholds {
+    fn default() -> Self {
+        MatchThresholds {
+            match_min: MATCH_MIN,
+            possible_match_min: POSSIBLE_MATCH_MIN,
+        }
+    }
+}
+
+impl MatchThresholds {
+    /// Decide a score.
+    pub fn decide(&self, score: f64) -> MatchDecision {
+        if score >= self.match_min {
+            MatchDecision::Match
+        }
+        else if score >= self.possible_match_min {
+            MatchDecision::PossibleMatch
+        }
+        else {
+            MatchDecision::NonMatch
+        }
+    }
+}
+
+/// Explanation of a similarity score, with one entry per field.
+/// 
+/// The profile name and version record which settings produced the score,
+/// and the decision comes from the score and the profile thresholds.
+/// 
+/// In Fellegi–Sunter mode, the score is the posterior match probability,
+/// and the match weight is the log₂ odds of a match.
+/// 
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct SimilarityReport {
+    pub score: f64,
+    pub decision: MatchDecision,
+    pub mode: ScoringMode,
+    pub match_weight: Option<f64>,
+    pub profile: String,
+    pub profile_version: String,
+    pub fields: Vec<FieldReport>,
+}
+
+/// Explanation of one field's part in a similarity score.
+/// 
+/// - `present`: true when both persons have the field.
+/// - `value`: the raw comparator value, from 0.0 to 1.0, if present.
+/// - `weight`: the weight applied to the field.
+/// - `contribution`: the amount that the field adds to the score.
+/// - `level`: the comparison level, in Fellegi–Sunter mode.
+/// - `note`: the kind of near miss, if any, such as "day_month_transposed".
+/// - `values`: the values that produced the sub-score, when either person
+///   has more than one value, such as a previous family name.
+/// 
+/// In weighted mode, the contributions of all fields add up to the score.
+/// 
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct FieldReport {
+    pub field: Field,
+    pub present: bool,
+    pub value: Option<f64>,
+    pub weight: f64,
+    pub contribution: f64,
+    pub level: Option<ComparisonLevel>,
+    pub note: Option<String>,
+    pub values: Option<ValuePair>,
+}
+
+/// Where a value of a multi-valued part of a person comes from.
+/// 
+/// - `Primary`: the primary value, such as the given name and family name.
+/// 
+/// - `Previous`, `Alias`, `Preferred`: another name of that kind;
+///   see [crate::models::other_name::NameKind].
+/// 
+/// - `Other`: another email or phone.
+/// 
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ValueOrigin {
+    Primary,
+    Previous,
+    Alias,
+    Preferred,
+    Other,
+}
+
+impl From<NameKind> for ValueOrigin {
+    fn from(kind: NameKind) -> Self {
+        match kind {
+            NameKind::Previous => ValueOrigin::Previous,
+            NameKind::Alias => ValueOrigin::Alias,
+            NameKind::Preferred => ValueOrigin::Preferred,
+        }
+    }
+}
+
+/// One value of a multi-valued part of a person.
+/// 
+/// - `origin`: where the value comes from.
+/// - `index`: the index in the other names, emails, or phones, if not primary.
+/// - `value`: the value, such as "Alice Adams"; names are the given name and family name.
+/// 
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValueSource {
+    pub origin: ValueOrigin,
+    pub index: Option<usize>,
+    pub value: String,
+}
+
+/// The values of two persons that produced a sub-score.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ValuePair {
+    pub a: ValueSource,
+    pub b: ValueSource,
+}
+
+impl Default for PerField<Option<ValuePair>> {
+    fn default() -> Self {
+        PerField {
+            given_name: None,
+            family_name: None,
+            birth_date_year: None,
+            birth_date_month: None,
+            birth_date_month_day: None,
+            primary_phone: None,
+            primary_email: None,
+            nhs_number: None,
+            address_lines: None,
+            town: None,
+            postcode: None,
+        }
+    }
+}
+
+/// Calculate the similarity of two persons, with an explanation per field.
+/// 
+/// The fields are compared using the function [sub_scores_of_persons],
+/// then the sub-scores are combined using the scoring mode of the profile,
+/// with term frequency adjustment of the fields that the profile names; see
+/// [crate::services::term_frequency::TermFrequencies::adjust_weights],
+/// then in weighted mode the NHS number adjusts the score; see
+/// [crate::services::nhs_number::NhsNumberEvidence::apply],
+/// then suffixes that differ, such as "Jr" and "Sr", lower the score; see
+/// [crate::services::name_parser::NameParts::apply_suffix],
+/// then each field is annotated using the function [notes_of_persons].
+/// 
+pub fn similarity_report_of_persons(input: (&Person, &Person), profile: &SimilarityProfile) -> SimilarityReport {
+    let chosen = chosen_values_of_persons(input, profile);
+    let input = (&chosen.a, &chosen.b);
+    let sub_scores = sub_scores_of_chosen(input, profile);
+    let frequencies = Field::ALL.iter().any(|&field| *profile.term_frequency.get(field)).then(term_frequencies);
+    let mut report = match (profile.mode, frequencies) {
+        (ScoringMode::Weighted, None) => similarity_report_of_sub_scores(&sub_scores, &profile.weights, &profile.missing),
+        (ScoringMode::Weighted, Some(frequencies)) => {
+            let weights = frequencies.adjust_weights(&profile.weights, &profile.term_frequency, &sub_scores, input.0);
+            similarity_report_of_sub_scores(&sub_scores, &weights, &profile.missing)
+        }
+        (ScoringMode::FellegiSunter, None) => fellegi_sunter::similarity_report_of_sub_scores(&sub_scores, &profile.fellegi_sunter),
+        (ScoringMode::FellegiSunter, Some(frequencies)) => {
+            let parameters = frequencies.adjust_parameters(&profile.fellegi_sunter, &profile.term_frequency, &sub_scores, input.0);
+            fellegi_sunter::similarity_report_of_sub_scores(&sub_scores, &parameters)
+        }
+    };
+    if profile.mode == ScoringMode::Weighted {
+        report.score = profile.nhs_number.apply(report.score, sub_scores.nhs_number);
+    }
+    let (a, b) = input;
+    if profile.name_parts.suffixes_disagree(a, b) {
+        match report.match_weight.as_mut() {
+            Some(match_weight) => {
+                *match_weight += profile.name_parts.suffix_disagreement.log2();
+                report.score = fellegi_sunter::probability_of_match_weight(*match_weight);
+            }
+            None => report.score = profile.name_parts.apply_suffix(report.score, a, b),
+        }
+    }
+    let notes = notes_of_chosen(input, profile);
+    for field_report in report.fields.iter_mut() {
+        field_report.note = notes.get(field_report.field).clone();
+        field_report.values = chosen.values.get(field_report.field).clone();
+    }
+    SimilarityReport {
+        decision: profile.thresholds.decide(report.score),
+        mode: profile.mode,
+        profile: profile.name.clone(),
+        profile_version: profile.version.clone(),
+        ..report
+    }
+}
 
-/// Calculate the similarity probability of two persons.
+/// Calculate the sub-score of each field of two persons.
+/// 
+/// A sub-score is `None` when either person lacks the field. When the profile
+/// treats blank as missing, which is the default, a blank text field such as
+/// `Some("")` or `Some("  ")` is the same as `None`.
+/// 
+/// Each field is first normalized by the steps that the profile names for
+/// the field; see [crate::services::normalization::NormalizationStep].
+/// 
+/// Each field is compared using the average of the comparators that the
+/// profile names for the field; see [crate::services::comparators::ComparatorRegistry::compare].
+/// The numeric fields are compared as text, and by default use equality.
+/// 
+/// When the profile compares birth dates fuzzily, which is the default,
+/// the birth date fields come from [compare_birth_dates] instead.
+/// 
+/// When the profile compares phone numbers in E.164 format, which is the
+/// default, the phone field comes from [compare_phones] instead; an invalid
+/// number has no sub-score, as if missing.
+/// 
+/// When the profile compares email addresses structurally, which is the
+/// default, the email field comes from [compare_emails] instead.
+/// 
+/// When the profile compares postcodes by level, which is the default, the
+/// postcode field comes from [compare_postcodes] instead: the same unit,
+/// sector, district, or area; an invalid postcode has no sub-score, as if missing.
+/// The town uses the place name comparator by default, so Welsh and English
+/// place names are equivalents, such as "Abertawe" and "Swansea", and the
+/// address lines are compared by tokens by default.
+/// 
+/// When the profile compares a field by tokens, which is the default for
+/// names, the sub-score is at least the token score, so an added, dropped,
+/// or reordered name part counts for less; see [TokenComparison].
 /// 
-/// This function compares these fields:
+/// When the profile gives a field phonetic credit, and the phonetic codes
+/// agree, the sub-score is at least the credit; see [crate::services::phonetic::PhoneticCredit].
+/// Likewise for nickname credit, when the values are nickname equivalents;
+/// see [crate::services::nicknames::NicknameDictionary].
 /// 
-/// - Given name
-/// - Family name
-/// - Birth date year, month, month day
-/// - Primary email
-/// - Primary phone
+/// When one given name is the initial of the other, such as "A." and
+/// "Alice", the given name sub-score is at least the initial credit of the
+/// profile; see [crate::services::name_parser::NameParts].
 /// 
-/// The text fields are compared using the function [similarity_of_strings].
+/// When the profile detects name swaps, and the given name and family name
+/// of one person match the family name and given name of the other, the
+/// name fields get credit for the swapped pairing; see [crate::services::name_swap::NameSwap].
 /// 
-/// The numeric fields are compared using equality.
+/// When either person has other names, such as a previous family name, or
+/// other emails or phones, every combination of values is compared, and the
+/// best-scoring combination gives the sub-scores. The names score the sum of
+/// the given name and family name sub-scores times the profile weights.
+/// The report of [similarity_report_of_persons] explains which values were used.
 /// 
-pub fn similarity_of_persons(input: (Person, Person)) -> f64 {
+pub fn sub_scores_of_persons(input: (&Person, &Person), profile: &SimilarityProfile) -> PerField<Option<f64>> {
+    let chosen = chosen_values_of_persons(input, profile);
+    sub_scores_of_chosen((&chosen.a, &chosen.b), profile)
+}
+
+// Calculate the sub-score of each field of two persons, using only the
+// primary value of each multi-valued part. Blank values must already be missing.
+fn sub_scores_of_chosen(input: (&Person, &Person), profile: &SimilarityProfile) -> PerField<Option<f64>> {
     let (a, b) = input;
-    let mut max: f64 = 0.0;
-    let mut x: f64 = 0.0;
-    if let Some(a) = a.given_name && let Some(b) = b.given_name {
-        max += GIVEN_NAME_EQ;
-        x += similarity_of_strings((&a, &b)) * GIVEN_NAME_EQ;
-    }
-    if let Some(a) = a.family_name && let Some(b) = b.family_name {
-        max += FAMILY_NAME_EQ;
-        x += similarity_of_strings((&a, &b)) * FAMILY_NAME_EQ;
-    }
-    if let Some(a) = a.birth_date_year && let Some(b) = b.birth_date_year {
-        max += BIRTH_DATE_YEAR_EQ;
-        if a == b {
-            x += BIRTH_DATE_YEAR_EQ;
+    let registry = COMPARATORS.read().unwrap();
+    let nicknames = NICKNAMES.read().unwrap();
+    let sub_score = |field: Field| -> Option<f64> {
+        Some(compare_field_texts(profile, &registry, &nicknames, field, &field_text(a, field)?, &field_text(b, field)?))
+    };
+    let birth_date = match profile.birth_date_comparator {
+        BirthDateComparator::Fuzzy => Some(compare_birth_dates_of_persons(a, b)),
+        BirthDateComparator::Parts => None,
+    };
+    let (given_name, family_name) = names_of_persons(input, profile, &registry, &nicknames);
+    PerField {
+        given_name,
+        family_name,
+        birth_date_year: birth_date.map_or_else(|| sub_score(Field::BirthDateYear), |x| x.year),
+        birth_date_month: birth_date.map_or_else(|| sub_score(Field::BirthDateMonth), |x| x.month),
+        birth_date_month_day: birth_date.map_or_else(|| sub_score(Field::BirthDateMonthDay), |x| x.month_day),
+        primary_phone: phone_of_persons(input, profile, &registry, &nicknames),
+        primary_email: email_of_persons(input, profile, &registry, &nicknames),
+        nhs_number: profile.nhs_number.compare(a.nhs_number.as_ref(), b.nhs_number.as_ref()),
+        address_lines: sub_score(Field::AddressLines),
+        town: sub_score(Field::Town),
+        postcode: postcode_of_persons(input, profile, &registry, &nicknames),
+    }
+}
+
+// Compare the given names and family names of two persons, with any name
+// swap credit and initial credit.
+fn names_of_persons(input: (&Person, &Person), profile: &SimilarityProfile, registry: &ComparatorRegistry, nicknames: &NicknameDictionary) -> (Option<f64>, Option<f64>) {
+    let (a, b) = input;
+    let sub_score = |field: Field| -> Option<f64> {
+        Some(compare_field_texts(profile, registry, nicknames, field, &field_text(a, field)?, &field_text(b, field)?))
+    };
+    match name_swap_of_persons(input, profile, registry, nicknames) {
+        Some((given_name, family_name)) => (Some(given_name), Some(family_name)),
+        None => {
+            let given_name = sub_score(Field::GivenName).map(|value| match (a.given_name.as_deref(), b.given_name.as_deref()) {
+                (Some(x), Some(y)) => profile.name_parts.apply_initial(value, x, y),
+                _ => value,
+            });
+            (given_name, sub_score(Field::FamilyName))
+        }
+    }
+}
+
+// Compare the primary phone numbers of two persons, by the phone comparator of the profile.
+fn phone_of_persons(input: (&Person, &Person), profile: &SimilarityProfile, registry: &ComparatorRegistry, nicknames: &NicknameDictionary) -> Option<f64> {
+    let (a, b) = input;
+    match profile.phone_comparator {
+        PhoneComparator::E164 => compare_phones_of_persons(input, profile).value,
+        PhoneComparator::Text => {
+            let (x, y) = (a.primary_phone.as_deref()?, b.primary_phone.as_deref()?);
+            Some(compare_field_texts(profile, registry, nicknames, Field::PrimaryPhone, x, y))
+        }
+    }
+}
+
+// Compare the primary email addresses of two persons, by the email comparator of the profile.
+fn email_of_persons(input: (&Person, &Person), profile: &SimilarityProfile, registry: &ComparatorRegistry, nicknames: &NicknameDictionary) -> Option<f64> {
+    let (a, b) = input;
+    let (x, y) = (a.primary_email.as_deref()?, b.primary_email.as_deref()?);
+    match profile.email_comparator {
+        EmailComparator::Structured => {
+            let steps = profile.normalization.get(Field::PrimaryEmail);
+            Some(compare_emails(&steps.normalize(x), &steps.normalize(y), &profile.email_weights))
         }
+        EmailComparator::Text => Some(compare_field_texts(profile, registry, nicknames, Field::PrimaryEmail, x, y)),
     }
-    if let Some(a) = a.birth_date_month && let Some(b) = b.birth_date_month {
-        max += BIRTH_DATE_MONTH_EQ;
-        if a == b {
-            x += BIRTH_DATE_MONTH_EQ;
+}
+
+// Compare the postcodes of two persons, by the postcode comparator of the profile.
+fn postcode_of_persons(input: (&Person, &Person), profile: &SimilarityProfile, registry: &ComparatorRegistry, nicknames: &NicknameDictionary) -> Option<f64> {
+    let (a, b) = input;
+    match profile.postcode_comparator {
+        PostcodeComparator::Levels => compare_postcodes_of_persons(input, profile).value,
+        PostcodeComparator::Text => {
+            let (x, y) = (field_text(a, Field::Postcode)?, field_text(b, Field::Postcode)?);
+            Some(compare_field_texts(profile, registry, nicknames, Field::Postcode, &x, &y))
         }
     }
-    if let Some(a) = a.birth_date_month_day && let Some(b) = b.birth_date_month_day {
-        max += BIRTH_DATE_MONTH_DAY_EQ;
-        if a == b {
-            x += BIRTH_DATE_MONTH_DAY_EQ
+}
+
+// The values of two persons that score best, for each multi-valued part:
+// the names, with other names; the email, with other emails; and the phone,
+// with other phones.
+//
+// The persons are copies with blank values missing, and with the best values
+// as the primary values, so the rest of the comparison needs no changes. The
+// values of a field are the values that produced its sub-score, when either
+// person has more than one value.
+struct ChosenValues {
+    a: Person,
+    b: Person,
+    values: PerField<Option<ValuePair>>,
+}
+
+// Choose the values of two persons that score best; see [ChosenValues].
+//
+// The names score the weighted sum of the given name and family name
+// sub-scores, using the profile weights. Ties go to the earlier values,
+// so the primary values win when nothing scores better.
+fn chosen_values_of_persons(input: (&Person, &Person), profile: &SimilarityProfile) -> ChosenValues {
+    let (a, b) = (blanks_as_missing(input.0, profile).into_owned(), blanks_as_missing(input.1, profile).into_owned());
+    let mut values: PerField<Option<ValuePair>> = PerField::default();
+    let has_other_names = !a.other_names.is_empty() || !b.other_names.is_empty();
+    let has_other_emails = !a.other_emails.is_empty() || !b.other_emails.is_empty();
+    let has_other_phones = !a.other_phones.is_empty() || !b.other_phones.is_empty();
+    if !has_other_names && !has_other_emails && !has_other_phones {
+        return ChosenValues { a, b, values };
+    }
+    let registry = COMPARATORS.read().unwrap();
+    let nicknames = NICKNAMES.read().unwrap();
+    let (a_names, b_names) = (name_variants(&a), name_variants(&b));
+    let (a_emails, b_emails) = (email_variants(&a), email_variants(&b));
+    let (a_phones, b_phones) = (phone_variants(&a), phone_variants(&b));
+    let names = best_variants(&a_names, &b_names, |x, y| match names_of_persons((x, y), profile, &registry, &nicknames) {
+        (None, None) => None,
+        (given_name, family_name) => Some(
+            profile.weights.given_name * given_name.unwrap_or(0.0) + profile.weights.family_name * family_name.unwrap_or(0.0)
+        ),
+    });
+    let emails = best_variants(&a_emails, &b_emails, |x, y| email_of_persons((x, y), profile, &registry, &nicknames));
+    let phones = best_variants(&a_phones, &b_phones, |x, y| phone_of_persons((x, y), profile, &registry, &nicknames));
+    let pair = |x: &[(ValueSource, Person)], y: &[(ValueSource, Person)], (i, j): (usize, usize)| {
+        ValuePair { a: x[i].0.clone(), b: y[j].0.clone() }
+    };
+    if has_other_names {
+        values.given_name = Some(pair(&a_names, &b_names, names));
+        values.family_name = values.given_name.clone();
+    }
+    if has_other_emails {
+        values.primary_email = Some(pair(&a_emails, &b_emails, emails));
+    }
+    if has_other_phones {
+        values.primary_phone = Some(pair(&a_phones, &b_phones, phones));
+    }
+    let chosen = |person: &Person, names: &Person, email: &Person, phone: &Person| Person {
+        given_name: names.given_name.clone(),
+        family_name: names.family_name.clone(),
+        primary_email: email.primary_email.clone(),
+        primary_phone: phone.primary_phone.clone(),
+        ..person.clone()
+    };
+    ChosenValues {
+        a: chosen(&a, &a_names[names.0].1, &a_emails[emails.0].1, &a_phones[phones.0].1),
+        b: chosen(&b, &b_names[names.1].1, &b_emails[emails.1].1, &b_phones[phones.1].1),
+        values,
+    }
+}
+
+// Get the indexes of the pair of variants that scores best, where a pair
+// without a score scores least, and ties go to the earlier pair.
+fn best_variants(a: &[(ValueSource, Person)], b: &[(ValueSource, Person)], score: impl Fn(&Person, &Person) -> Option<f64>) -> (usize, usize) {
+    let mut best = ((0, 0), None);
+    for (i, (_, x)) in a.iter().enumerate() {
+        for (j, (_, y)) in b.iter().enumerate() {
+            let value = score(x, y);
+            if value > best.1 {
+                best = ((i, j), value);
+            }
         }
     }
-    if let Some(a) = a.primary_phone && let Some(b) = b.primary_phone {
-        max += PRIMARY_PHONE_EQ;
-        x += similarity_of_strings((&a, &b)) * PRIMARY_PHONE_EQ;
+    best.0
+}
+
+// Get the name variants of a person: the primary names, then each other name,
+// whose missing parts are the primary parts.
+fn name_variants(person: &Person) -> Vec<(ValueSource, Person)> {
+    let text = |x: &Person| [x.given_name.as_deref(), x.family_name.as_deref()].into_iter().flatten().collect::<Vec<&str>>().join(" ");
+    let mut variants = vec![(ValueSource { origin: ValueOrigin::Primary, index: None, value: text(person) }, person.clone())];
+    for (i, other) in person.other_names.iter().enumerate() {
+        let variant = Person {
+            given_name: other.given_name.clone().or_else(|| person.given_name.clone()),
+            family_name: other.family_name.clone().or_else(|| person.family_name.clone()),
+            ..person.clone()
+        };
+        variants.push((ValueSource { origin: ValueOrigin::from(other.kind), index: Some(i), value: text(&variant) }, variant));
+    }
+    variants
+}
+
+// Get the email variants of a person: the primary email, then each other email.
+fn email_variants(person: &Person) -> Vec<(ValueSource, Person)> {
+    let primary = (ValueSource { origin: ValueOrigin::Primary, index: None, value: person.primary_email.clone().unwrap_or_default() }, person.clone());
+    std::iter::once(primary).chain(person.other_emails.iter().enumerate().map(|(i, email)| (
+        ValueSource { origin: ValueOrigin::Other, index: Some(i), value: email.clone() },
+        Person { primary_email: Some(email.clone()), ..person.clone() },
+    ))).collect()
+}
+
+// Get the phone variants of a person: the primary phone, then each other phone.
+fn phone_variants(person: &Person) -> Vec<(ValueSource, Person)> {
+    let primary = (ValueSource { origin: ValueOrigin::Primary, index: None, value: person.primary_phone.clone().unwrap_or_default() }, person.clone());
+    std::iter::once(primary).chain(person.other_phones.iter().enumerate().map(|(i, phone)| (
+        ValueSource { origin: ValueOrigin::Other, index: Some(i), value: phone.clone() },
+        Person { primary_phone: Some(phone.clone()), ..person.clone() },
+    ))).collect()
+}
+
+// Get the person with each blank text field as missing, when the profile
+// treats blank as missing, so that `Some("")` and `None` score the same.
+fn blanks_as_missing<'a>(person: &'a Person, profile: &SimilarityProfile) -> std::borrow::Cow<'a, Person> {
+    if profile.blank_as_missing {
+        std::borrow::Cow::Owned(person.without_blanks())
     }
-    if let Some(a) = a.primary_email && let Some(b) = b.primary_email {
-        max += PRIMARY_EMAIL_EQ;
-        x += similarity_of_strings((&a, &b)) * PRIMARY_EMAIL_EQ;
+    else {
+        std::borrow::Cow::Borrowed(person)
+    }
+}
+
+// Compare the phone numbers of two persons, in the phone region of the profile.
+//
+// The comparison has no value and no note when either person lacks a number.
+fn compare_phones_of_persons(input: (&Person, &Person), profile: &SimilarityProfile) -> PhoneComparison {
+    let (a, b) = input;
+    let region = PhoneRegion::from_code(&profile.phone_region)
+        .unwrap_or_else(|| PhoneRegion::from_code(DEFAULT_PHONE_REGION).unwrap());
+    let steps = profile.normalization.get(Field::PrimaryPhone);
+    match (a.primary_phone.as_deref(), b.primary_phone.as_deref()) {
+        (Some(a), Some(b)) => compare_phones(&steps.normalize(a), &steps.normalize(b), &region),
+        _ => PhoneComparison::default(),
+    }
+}
+
+// Compare the postcodes of two persons by unit, sector, district, and area.
+//
+// The comparison has no value and no note when either person lacks a postcode.
+fn compare_postcodes_of_persons(input: (&Person, &Person), profile: &SimilarityProfile) -> PostcodeComparison {
+    let (a, b) = input;
+    let steps = profile.normalization.get(Field::Postcode);
+    match (field_text(a, Field::Postcode), field_text(b, Field::Postcode)) {
+        (Some(a), Some(b)) => compare_postcodes(&steps.normalize(&a), &steps.normalize(&b)),
+        _ => PostcodeComparison::default(),
+    }
+}
+
+// Compare two texts as values of a field: normalize, compare, then apply
+// any token comparison, phonetic credit, and nickname credit of the field.
+fn compare_field_texts(profile: &SimilarityProfile, registry: &ComparatorRegistry, nicknames: &NicknameDictionary, field: Field, a: &str, b: &str) -> f64 {
+    let steps = profile.normalization.get(field);
+    let (a, b) = (steps.normalize(a), steps.normalize(b));
+    let mut value = registry.compare(profile.comparators.get(field), &a, &b);
+    if let Some(tokens) = profile.tokens.get(field) {
+        value = tokens.apply(value, &a, &b, |x, y| registry.compare(profile.comparators.get(field), x, y));
+    }
+    if let Some(phonetic) = profile.phonetic.get(field) {
+        value = phonetic.apply(value, &a, &b);
+    }
+    if let Some(NicknameCredit(credit)) = *profile.nicknames.get(field) && value < credit && nicknames.are_equivalent(&a, &b) {
+        value = credit;
+    }
+    value
+}
+
+// Get the credited given name and family name sub-scores when the names of
+// two persons are swapped, else `None`; see [crate::services::name_swap::NameSwap].
+//
+// The swapped pairing compares the given name of each person to the family
+// name of the other, using the settings of the field of the first person.
+fn name_swap_of_persons(input: (&Person, &Person), profile: &SimilarityProfile, registry: &ComparatorRegistry, nicknames: &NicknameDictionary) -> Option<(f64, f64)> {
+    let (a, b) = input;
+    let (a_given, a_family, b_given, b_family) = (a.given_name.as_deref()?, a.family_name.as_deref()?, b.given_name.as_deref()?, b.family_name.as_deref()?);
+    let compare = |field: Field, x: &str, y: &str| compare_field_texts(profile, registry, nicknames, field, x, y);
+    let straight = (compare(Field::GivenName, a_given, b_given), compare(Field::FamilyName, a_family, b_family));
+    let swapped = (compare(Field::GivenName, a_given, b_family), compare(Field::FamilyName, a_family, b_given));
+    profile.name_swap.apply(straight, swapped)
+}
+
+/// No value for any field, such as no sub-scores.
+impl Default for PerField<Option<f64>> {
+    fn default() -> Self {
+        PerField {
+            given_name: None,
+            family_name: None,
+            birth_date_year: None,
+            birth_date_month: None,
+            birth_date_month_day: None,
+            primary_phone: None,
+            primary_email: None,
+            nhs_number: None,
+            address_lines: None,
+            town: None,
+            postcode: None,
+        }
+    }
+}
+
+/// No note for any field.
+impl Default for PerField<Option<String>> {
+    fn default() -> Self {
+        PerField {
+            given_name: None,
+            family_name: None,
+            birth_date_year: None,
+            birth_date_month: None,
+            birth_date_month_day: None,
+            primary_phone: None,
+            primary_email: None,
+            nhs_number: None,
+            address_lines: None,
+            town: None,
+            postcode: None,
+        }
+    }
+}
+
+/// Get the note of each field of two persons, which explains a near miss.
+/// 
+/// The birth date fields have notes when the profile compares birth dates
+/// fuzzily; see [crate::services::birth_date::DateNearMiss]. The month and
+/// the month day share the note of the month and day.
+/// 
+/// The name fields have the note "name_swap" when the names are swapped.
+/// Otherwise, the given name has the note "initial" when one given name is
+/// the initial of the other, and the family name has the note
+/// "suffix_disagreement" when the suffixes differ, such as "Jr" and "Sr".
+/// 
+/// The NHS number field has a note when both persons have NHS numbers:
+/// "nhs_number_agreement", "nhs_number_disagreement", or "nhs_number_test"
+/// when the profile rejects a test number.
+/// 
+/// The phone field has a note when the profile compares phone numbers in
+/// E.164 format, such as "phone_invalid"; see [crate::services::phone::PhoneNote].
+/// 
+/// The postcode field has a note when the profile compares postcodes by
+/// level, such as "postcode_sector"; see [crate::services::postcode::PostcodeNote].
+/// The town field has the note "bilingual_place_name" when the towns are the
+/// Welsh name and the English name of one place, such as "Caerdydd" and "Cardiff".
+/// 
+pub fn notes_of_persons(input: (&Person, &Person), profile: &SimilarityProfile) -> PerField<Option<String>> {
+    let chosen = chosen_values_of_persons(input, profile);
+    notes_of_chosen((&chosen.a, &chosen.b), profile)
+}
+
+// Get the note of each field of two persons, using only the primary value
+// of each multi-valued part. Blank values must already be missing.
+fn notes_of_chosen(input: (&Person, &Person), profile: &SimilarityProfile) -> PerField<Option<String>> {
+    let (a, b) = input;
+    let mut notes: PerField<Option<String>> = PerField::default();
+    if profile.birth_date_comparator == BirthDateComparator::Fuzzy {
+        let comparison = compare_birth_dates_of_persons(a, b);
+        let name = |near_miss: Option<DateNearMiss>| near_miss.map(|x| x.name().to_string());
+        notes.birth_date_year = name(comparison.year_near_miss);
+        notes.birth_date_month = name(comparison.month_day_near_miss);
+        notes.birth_date_month_day = name(comparison.month_day_near_miss);
+    }
+    let (registry, nicknames) = (COMPARATORS.read().unwrap(), NICKNAMES.read().unwrap());
+    if name_swap_of_persons(input, profile, &registry, &nicknames).is_some() {
+        notes.given_name = Some(String::from("name_swap"));
+        notes.family_name = Some(String::from("name_swap"));
+    }
+    else {
+        if let (Some(x), Some(y)) = (a.given_name.as_deref(), b.given_name.as_deref()) && x != y && initial_agrees(x, y) {
+            notes.given_name = Some(String::from("initial"));
+        }
+        if profile.name_parts.suffixes_disagree(a, b) {
+            notes.family_name = Some(String::from("suffix_disagreement"));
+        }
+    }
+    notes.nhs_number = match profile.nhs_number.compare(a.nhs_number.as_ref(), b.nhs_number.as_ref()) {
+        Some(x) if x >= 1.0 => Some(String::from("nhs_number_agreement")),
+        Some(_) => Some(String::from("nhs_number_disagreement")),
+        None if a.nhs_number.is_some() && b.nhs_number.is_some() => Some(String::from("nhs_number_test")),
+        None => None,
+    };
+    if profile.phone_comparator == PhoneComparator::E164 {
+        notes.primary_phone = compare_phones_of_persons(input, profile).note.map(|x| x.name().to_string());
+    }
+    if profile.postcode_comparator == PostcodeComparator::Levels {
+        notes.postcode = compare_postcodes_of_persons(input, profile).note.map(|x| x.name().to_string());
+    }
+    if let (Some(x), Some(y)) = (field_text(a, Field::Town), field_text(b, Field::Town))
+        && profile.comparators.town.iter().any(|name| name == "place_name")
+        && are_bilingual_equivalents(&x, &y) {
+        notes.town = Some(String::from("bilingual_place_name"));
+    }
+    notes
+}
+
+/// Combine sub-scores into one similarity probability, with an explanation.
+/// 
+/// Each present sub-score adds its weight to the maximum, and adds its
+/// sub-score times its weight to the total. Each missing sub-score counts
+/// as the missing policy of its field says; see [MissingPolicy]. The total
+/// is divided by the average of the maximum and the sum of the weights of
+/// the fields that count, so with the default policy, which adds nothing to
+/// the maximum, each missing field is a half penalty.
+/// 
+/// The returned report has a blank profile name and version.
+/// 
+pub fn similarity_report_of_sub_scores(sub_scores: &PerField<Option<f64>>, weights: &PerField<f64>, missing: &PerField<MissingPolicy>) -> SimilarityReport {
+    let counted = |field: Field| sub_scores.get(field).or_else(|| missing.get(field).counted_sub_score());
+    let mut max: f64 = 0.0;
+    let mut x: f64 = 0.0;
+    let mut weights_max: f64 = 0.0;
+    for field in Field::ALL {
+        let weight = *weights.get(field);
+        if *missing.get(field) != MissingPolicy::Ignore || sub_scores.get(field).is_some() {
+            weights_max += weight;
+        }
+        if let Some(sub_score) = counted(field) {
+            max += weight;
+            x += sub_score * weight;
+        }
+    }
+    let denominator = (max + weights_max) / 2.0;
+    let fields = Field::ALL.iter().map(|&field| {
+        let value = *sub_scores.get(field);
+        let weight = *weights.get(field);
+        FieldReport {
+            field,
+            present: value.is_some(),
+            value,
+            weight,
+            contribution: match counted(field) {
+                Some(value) if denominator > 0.0 => value * weight / denominator,
+                _ => 0.0,
+            },
+            level: None,
+            note: None,
+            values: None,
+        }
+    }).collect();
+    SimilarityReport {
+        score: if denominator > 0.0 { x / denominator } else { 0.0 },
+        fields,
+        ..SimilarityReport::default()
     }
-    x / ((max + SIMILARITY_MAX) / 2.0)
 }
 
 /// Calculate the similarity of two strings.
 /// 
+/// This is the same as the default comparators of text fields, [TEXT_COMPARATORS].
+/// 
 /// This implementation uses:
 /// 
 /// - If either string is blank, then return 0.0 meaning no similarity.
@@ -92,11 +1137,10 @@ pub fn similarity_of_persons(input: (Person, Person)) -> f64 {
 /// 
 pub fn similarity_of_strings(input: (&str, &str)) -> f64 {
     let (a, b) = input;
-    if a == "" || b == "" { 
+    if a.is_empty() || b.is_empty() { 
         0.0 
     }
-    else 
-    if a == b { 
+    else if a == b { 
         1.0
     }
     else {
@@ -111,302 +1155,591 @@ pub fn similarity_of_strings(input: (&str, &str)) -> f64 {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::models::address::Address;
+    use crate::models::other_name::OtherName;
+    use crate::services::nicknames::NICKNAME_EQUIVALENT;
+    use crate::services::postcode::POSTCODE_SECTOR;
+    use crate::services::name_parser::{INITIAL_CREDIT, SUFFIX_DISAGREEMENT};
+    use crate::services::phonetic::{PhoneticCredit, PhoneticEncoder};
+    use crate::services::welsh::similarity_of_welsh_names;
+
+    fn person(id: &str, given_name: &str, family_name: &str) -> Person {
+        Person {
+            id: String::from(id),
+            given_name: Some(String::from(given_name)),
+            family_name: Some(String::from(family_name)),
+            ..Person::default()
+        }
+    }
 
     #[test]
     fn test_none() {
-        let a = Person {
-            id: String::from("0"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: None,
-            note: None,
-        };
-        let b = Person { 
-            id: String::from("1"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            primary_email: None,
-            primary_phone: None,
-            birth_date_month_day: None,
-            note: None,
-        };
-        assert_eq!(similarity_of_persons((a, b)), 0.0);
+        let a = Person { id: String::from("0"), ..Person::default() };
+        let b = Person { id: String::from("1"), ..Person::default() };
+        assert_eq!(Person::similarity((&a, &b)), 0.0);
     }
 
     #[test]
     fn test_min() {
         let a = Person {
-            id: String::from("0"),
-            given_name: Some(String::from("aaa")), 
-            family_name: Some(String::from("aaa")),
-            birth_date_year: Some(1999),
-            birth_date_month: Some(12),
-            birth_date_month_day: Some(31),
+            birth_date: Some("1999-12-31".parse().unwrap()),
             primary_email: Some(String::from("aaa")),
             primary_phone: Some(String::from("111")),
-            note: None,
+            ..person("0", "aaa", "aaa")
         };
-        let b = Person { 
-            id: String::from("1"),
-            given_name: Some(String::from("bbb")), 
-            family_name: Some(String::from("bbb")),
-            birth_date_year: Some(2022),
-            birth_date_month: Some(2),
-            birth_date_month_day: Some(28),
+        let b = Person {
+            birth_date: Some("2022-02-28".parse().unwrap()),
             primary_email: Some(String::from("bbb")),
             primary_phone: Some(String::from("222")),
-            note: None,
+            ..person("1", "bbb", "bbb")
         };
-        assert_eq!(similarity_of_persons((a, b)), 0.0);
+        assert_eq!(Person::similarity((&a, &b)), 0.0);
     }
 
     #[test]
     fn test_max() {
         let a = Person {
-            id: String::from("0"),
-            given_name: Some(String::from("Alice")), 
-            family_name: Some(String::from("Adams")),
-            birth_date_year: Some(1999),
-            birth_date_month: Some(12),
-            birth_date_month_day: Some(31),
+            birth_date: Some("1999-12-31".parse().unwrap()),
             primary_email: Some(String::from("alice@example.com")),
             primary_phone: Some(String::from("3787581685")),
-            note: None,
+            ..person("0", "Alice", "Adams")
         };
-        let b = Person { 
-            id: String::from("0"),
-            given_name: Some(String::from("Alice")), 
-            family_name: Some(String::from("Adams")),
-            birth_date_year: Some(1999),
-            birth_date_month: Some(12),
-            birth_date_month_day: Some(31),
+        let b = Person {
+            birth_date: Some("1999-12-31".parse().unwrap()),
             primary_email: Some(String::from("alice@example.com")),
             primary_phone: Some(String::from("3787581685")),
-            note: None,
+            ..person("0", "Alice", "Adams")
         };
-        assert_eq!(similarity_of_persons((a, b)), 1.0);
+        assert_eq!(Person::similarity((&a, &b)), 1.0);
     }
 
     #[test]
     fn test_perturbations() {
         let a = Person {
-            id: String::from("0"),
-            given_name: Some(String::from("Alice")), 
-            family_name: Some(String::from("Adams")),
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
             primary_email: Some(String::from("alice@example.com")),
             primary_phone: Some(String::from("3787581685")),
-            note: None,
+            ..person("0", "Alice", "Adams")
         };
-        let b = Person { 
-            id: String::from("0"),
-            given_name: Some(String::from("lAice")), 
-            family_name: Some(String::from("dAams")),
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
+        let b = Person {
             primary_email: Some(String::from("laice@example.com")),
             primary_phone: Some(String::from("7387581685")),
-            note: None,
+            ..person("0", "lAice", "dAams")
         };
-        assert_eq!(similarity_of_persons((a, b)), 0.7415945790080739); // empirical
+        assert_eq!(Person::similarity((&a, &b)), 0.7519607843137255); // empirical, with names case folded, structured emails, and E.164 phones
     }
 
     #[test]
     fn test_given_name() {
+        let a = Person { id: String::from("0"), given_name: Some(String::from("Alice")), ..Person::default() };
+        let b = Person { id: String::from("0"), given_name: Some(String::from("Alice")), ..Person::default() };
+        assert_eq!(Person::similarity((&a, &b)), GIVEN_NAME_EQ / ((GIVEN_NAME_EQ + SIMILARITY_MAX) / 2.0));
+    }
+
+    #[test]
+    fn test_family_name() {
+        let a = Person { id: String::from("0"), family_name: Some(String::from("Adams")), ..Person::default() };
+        let b = Person { id: String::from("0"), family_name: Some(String::from("Adams")), ..Person::default() };
+        assert_eq!(Person::similarity((&a, &b)), FAMILY_NAME_EQ / ((FAMILY_NAME_EQ + SIMILARITY_MAX) / 2.0));
+    }
+
+    #[test]
+    fn test_birth_date_year() {
+        let a = Person { id: String::from("0"), birth_date: Some("1999".parse().unwrap()), ..Person::default() };
+        let b = Person { id: String::from("1"), birth_date: Some("1999".parse().unwrap()), ..Person::default() };
+        assert_eq!(Person::similarity((&a, &b)), BIRTH_DATE_YEAR_EQ  / ((BIRTH_DATE_YEAR_EQ + SIMILARITY_MAX) / 2.0) )
+    }
+
+    #[test]
+    fn test_birth_date_month() {
+        let a = Person { id: String::from("0"), birth_date: Some("1999-12".parse().unwrap()), ..Person::default() };
+        let b = Person { id: String::from("1"), birth_date: Some("2022-12".parse().unwrap()), ..Person::default() };
+        assert_eq!(Person::similarity((&a, &b)), BIRTH_DATE_MONTH_EQ / ((BIRTH_DATE_YEAR_EQ + BIRTH_DATE_MONTH_EQ + SIMILARITY_MAX) / 2.0) )
+    }
+
+    #[test]
+    fn test_birth_date_month_day() {
+        let a = Person { id: String::from("0"), birth_date: Some("1999-12-31".parse().unwrap()), ..Person::default() };
+        let b = Person { id: String::from("1"), birth_date: Some("2022-10-31".parse().unwrap()), ..Person::default() };
+        assert_eq!(Person::similarity((&a, &b)), BIRTH_DATE_MONTH_DAY_EQ / ((BIRTH_DATE_YEAR_EQ + BIRTH_DATE_MONTH_EQ + BIRTH_DATE_MONTH_DAY_EQ + SIMILARITY_MAX) / 2.0) )
+    }
+
+    #[test]
+    fn test_birth_date_common_precision() {
+        let a = Person { id: String::from("0"), birth_date: Some("1999".parse().unwrap()), ..Person::default() };
+        let b = Person { id: String::from("1"), birth_date: Some("1999-12-31".parse().unwrap()), ..Person::default() };
+        let sub_scores = sub_scores_of_persons((&a, &b), &SimilarityProfile::default());
+        assert_eq!((sub_scores.birth_date_year, sub_scores.birth_date_month, sub_scores.birth_date_month_day), (Some(1.0), None, None));
+        let profile = SimilarityProfile { birth_date_comparator: BirthDateComparator::Parts, ..SimilarityProfile::default() };
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!((sub_scores.birth_date_year, sub_scores.birth_date_month, sub_scores.birth_date_month_day), (Some(1.0), None, None));
+    }
+
+    #[test]
+    fn test_primary_email() {
+        let a = Person { id: String::from("0"), primary_email: Some(String::from("alice@example.com")), ..Person::default() };
+        let b = Person { id: String::from("1"), primary_email: Some(String::from("alice@example.com")), ..Person::default() };
+        assert_eq!(Person::similarity((&a, &b)), PRIMARY_EMAIL_EQ / ((PRIMARY_EMAIL_EQ + SIMILARITY_MAX) / 2.0) )
+    }
+
+    #[test]
+    fn test_primary_phone() {
+        let a = Person { id: String::from("0"), primary_phone: Some(String::from("3787581685")), ..Person::default() };
+        let b = Person { id: String::from("1"), primary_phone: Some(String::from("3787581685")), ..Person::default() };
+        assert_eq!(Person::similarity((&a, &b)), PRIMARY_PHONE_EQ / ((PRIMARY_PHONE_EQ + SIMILARITY_MAX) / 2.0) )
+    }
+
+    #[test]
+    fn test_similarity_report() {
         let a = Person {
-            id: String::from("0"),
-            given_name: Some(String::from("Alice")), 
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: None,
-            note: None,
+            birth_date: Some("1999".parse().unwrap()),
+            primary_email: Some(String::from("alice@example.com")),
+            ..person("0", "Alice", "Adams")
         };
-        let b = Person { 
-            id: String::from("0"),
-            given_name: Some(String::from("Alice")), 
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            primary_email: None,
-            primary_phone: None,
-            birth_date_month_day: None,
-            note: None,
+        let b = Person {
+            birth_date: Some("2000-12".parse().unwrap()),
+            primary_email: Some(String::from("alice@example.com")),
+            ..person("1", "lAice", "Adams")
         };
-        assert_eq!(similarity_of_persons((a, b)), GIVEN_NAME_EQ / ((GIVEN_NAME_EQ + SIMILARITY_MAX) / 2.0));
+        let report = similarity_report_of_persons((&a, &b), &SimilarityProfile::default());
+        assert_eq!(report.score, Person::similarity((&a, &b)));
+        assert_eq!(report.profile, "default");
+        assert_eq!(report.profile_version, "1");
+        assert_eq!(report.fields.len(), Field::ALL.len());
+        let given_name = &report.fields[0];
+        assert_eq!(given_name.field, Field::GivenName);
+        assert!(given_name.present);
+        assert!(given_name.value.unwrap() > 0.0 && given_name.value.unwrap() < 1.0);
+        assert_eq!(given_name.weight, GIVEN_NAME_EQ);
+        let birth_date_year = &report.fields[2];
+        assert_eq!(birth_date_year.value, Some(crate::services::birth_date::YEAR_OFF_BY_ONE));
+        assert_eq!(birth_date_year.note.as_deref(), Some("year_off_by_one"));
+        assert!(birth_date_year.contribution > 0.0);
+        assert_eq!(report.fields[0].note, None);
+        let birth_date_month = &report.fields[3];
+        assert!(!birth_date_month.present);
+        assert_eq!(birth_date_month.value, None);
+        let total: f64 = report.fields.iter().map(|field| field.contribution).sum();
+        assert!((total - report.score).abs() < 1e-12);
     }
 
     #[test]
-    fn test_family_name() {
+    fn test_fellegi_sunter_mode() {
+        let a = Person { birth_date: Some("1999-12-31".parse().unwrap()), ..person("0", "Alice", "Adams") };
+        let b = Person { birth_date: Some("1999-12-31".parse().unwrap()), ..person("1", "Alice", "Adams") };
+        let profile = SimilarityProfile { mode: ScoringMode::FellegiSunter, ..SimilarityProfile::default() };
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert_eq!(report.mode, ScoringMode::FellegiSunter);
+        assert!(report.match_weight.unwrap() > 10.0);
+        assert!(report.score > 0.99);
+        assert_eq!(report.fields[0].level, Some(ComparisonLevel::Exact));
+        assert_eq!(report.fields[5].level, Some(ComparisonLevel::Missing));
+    }
+
+    #[test]
+    fn test_match_thresholds() {
+        let thresholds = MatchThresholds { match_min: 0.9, possible_match_min: 0.7 };
+        assert_eq!(thresholds.decide(1.0), MatchDecision::Match);
+        assert_eq!(thresholds.decide(0.9), MatchDecision::Match);
+        assert_eq!(thresholds.decide(0.8), MatchDecision::PossibleMatch);
+        assert_eq!(thresholds.decide(0.7), MatchDecision::PossibleMatch);
+        assert_eq!(thresholds.decide(0.5), MatchDecision::NonMatch);
+    }
+
+    #[test]
+    fn test_decision() {
         let a = Person {
-            id: String::from("0"),
-            given_name: None,
-            family_name: Some(String::from("Adams")),
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: None,
-            note: None,
-        };
-        let b = Person { 
-            id: String::from("0"),
-            given_name: None,
-            family_name: Some(String::from("Adams")),
-            birth_date_year: None,
-            birth_date_month: None,
-            primary_email: None,
-            primary_phone: None,
-            birth_date_month_day: None,
-            note: None,
+            birth_date: Some("1999-12-31".parse().unwrap()),
+            primary_email: Some(String::from("alice@example.com")),
+            primary_phone: Some(String::from("3787581685")),
+            ..person("0", "Alice", "Adams")
         };
-        assert_eq!(similarity_of_persons((a, b)), FAMILY_NAME_EQ / ((FAMILY_NAME_EQ + SIMILARITY_MAX) / 2.0));
+        let mut b = a.clone();
+        let profile = SimilarityProfile::default();
+        assert_eq!(similarity_report_of_persons((&a, &b), &profile).decision, MatchDecision::Match);
+        b.given_name = Some(String::from("Alicia"));
+        b.birth_date = Some("1999-11-30".parse().unwrap());
+        assert_eq!(similarity_report_of_persons((&a, &b), &profile).decision, MatchDecision::PossibleMatch);
+        b.family_name = Some(String::from("Brown"));
+        b.primary_email = None;
+        assert_eq!(similarity_report_of_persons((&a, &b), &profile).decision, MatchDecision::NonMatch);
     }
 
     #[test]
-    fn test_birth_date_year() {
+    fn test_comparators() {
         let a = Person {
             id: String::from("0"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: Some(1999),
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: None,
-            note: None,
+            given_name: Some(String::from("Smith")),
+            birth_date: Some("1999".parse().unwrap()),
+            ..Person::default()
         };
-        let b = Person { 
+        let b = Person {
             id: String::from("1"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: Some(1999),
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: None,
-            note: None,
+            given_name: Some(String::from("Smyth")),
+            birth_date: Some("1998".parse().unwrap()),
+            ..Person::default()
         };
-        assert_eq!(similarity_of_persons((a, b)), BIRTH_DATE_YEAR_EQ  / ((BIRTH_DATE_YEAR_EQ + SIMILARITY_MAX) / 2.0) )
+        let mut profile = SimilarityProfile { birth_date_comparator: BirthDateComparator::Parts, ..SimilarityProfile::default() };
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!(sub_scores.given_name, Some(similarity_of_welsh_names(("Smith", "Smyth"))));
+        assert_eq!(sub_scores.birth_date_year, Some(0.0));
+        profile.comparators.given_name = vec![String::from("exact")];
+        profile.comparators.birth_date_year = vec![String::from("levenshtein")];
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!(sub_scores.given_name, Some(0.0));
+        assert_eq!(sub_scores.birth_date_year, Some(0.75));
+        profile.phonetic.given_name = Some(PhoneticCredit { encoder: PhoneticEncoder::DoubleMetaphone, credit: 0.9 });
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!(sub_scores.given_name, Some(0.9));
     }
 
     #[test]
-    fn test_birth_date_month() {
+    fn test_phonetic_surnames() {
+        let family = |id: &str, family_name: &str| Person { id: String::from(id), family_name: Some(String::from(family_name)), ..Person::default() };
+        let mut profile = SimilarityProfile::default();
+        // Different surnames whose Double Metaphone codes agree when truncated
+        // get no phonetic credit from the built-in profile.
+        for (a, b) in [("Williams", "Williamson"), ("Roberts", "Robertson"), ("Richards", "Richardson")] {
+            let sub_scores = sub_scores_of_persons((&family("0", a), &family("1", b)), &profile);
+            assert!(sub_scores.family_name.unwrap() < 0.9, "{} {}", a, b);
+        }
+        profile.phonetic.family_name = Some(PhoneticCredit { encoder: PhoneticEncoder::DoubleMetaphone, credit: 0.9 });
+        let sub_scores = sub_scores_of_persons((&family("0", "Thomson"), &family("1", "Thompson")), &profile);
+        assert_eq!(sub_scores.family_name, Some(0.9));
+    }
+
+    #[test]
+    fn test_welsh_names() {
+        let a = person("0", "Gwyn", "Llewelyn");
+        let b = person("1", "Wyn", "Llywelyn");
+        let sub_scores = sub_scores_of_persons((&a, &b), &SimilarityProfile::default());
+        assert_eq!(sub_scores.given_name, Some(crate::services::welsh::WELSH_MUTATION));
+        assert_eq!(sub_scores.family_name, Some(crate::services::welsh::WELSH_VARIANT));
+    }
+
+    #[test]
+    fn test_normalization() {
+        let a = Person { primary_email: Some(String::from(" Alice@Example.com")), ..person("0", "ALICE", "Sia\u{0302}n") };
+        let b = Person { primary_email: Some(String::from("alice@example.com")), ..person("1", "Alice", "Sian") };
+        let mut profile = SimilarityProfile::default();
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!(sub_scores.given_name, Some(1.0));
+        assert_eq!(sub_scores.family_name, Some(1.0));
+        assert_eq!(sub_scores.primary_email, Some(1.0));
+        profile.normalization.given_name = Vec::new();
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert!(sub_scores.given_name.unwrap() < 1.0);
+    }
+
+    #[test]
+    fn test_fuzzy_birth_date() {
+        let a = Person { birth_date: Some("1980-05-07".parse().unwrap()), ..person("0", "Alice", "Adams") };
+        let b = Person { birth_date: Some("1980-07-05".parse().unwrap()), ..person("1", "Alice", "Adams") };
+        let report = similarity_report_of_persons((&a, &b), &SimilarityProfile::default());
+        assert_eq!(report.fields[3].value, Some(crate::services::birth_date::DAY_MONTH_TRANSPOSED));
+        assert_eq!(report.fields[3].note.as_deref(), Some("day_month_transposed"));
+        assert_eq!(report.fields[4].note.as_deref(), Some("day_month_transposed"));
+        let profile = SimilarityProfile { birth_date_comparator: BirthDateComparator::Parts, ..SimilarityProfile::default() };
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert_eq!(report.fields[3].value, Some(0.0));
+        assert_eq!(report.fields[3].note, None);
+    }
+
+    #[test]
+    fn test_nicknames() {
+        let a = person("0", "Bob", "Brown");
+        let b = person("1", "Robert", "Brown");
+        let mut profile = SimilarityProfile::default();
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!(sub_scores.given_name, Some(crate::services::nicknames::NICKNAME_EQUIVALENT));
+        profile.nicknames.given_name = None;
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert!(sub_scores.given_name.unwrap() < 0.5);
+    }
+
+    #[test]
+    fn test_name_swap() {
+        let a = person("0", "Thomas", "Owen");
+        let b = person("1", "Owen", "Thomas");
+        let mut profile = SimilarityProfile::default();
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert_eq!(report.fields[0].value, Some(crate::services::name_swap::NAME_SWAP_CREDIT));
+        assert_eq!(report.fields[1].value, Some(crate::services::name_swap::NAME_SWAP_CREDIT));
+        assert_eq!(report.fields[0].note.as_deref(), Some("name_swap"));
+        assert_eq!(report.fields[1].note.as_deref(), Some("name_swap"));
+        let report = similarity_report_of_persons((&a, &a), &profile);
+        assert_eq!(report.fields[0].note, None);
+        profile.name_swap.credit = 0.0;
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert!(report.fields[0].value.unwrap() < 0.5);
+        assert_eq!(report.fields[0].note, None);
+    }
+
+    #[test]
+    fn test_structured_email() {
         let a = Person {
             id: String::from("0"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: Some(12),
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: None,
-            note: None,
+            primary_email: Some(String::from("Alice.Adams@googlemail.com")),
+            ..Person::default()
         };
-        let b = Person { 
+        let b = Person {
             id: String::from("1"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: Some(12),
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: None,
-            note: None,
+            primary_email: Some(String::from("aliceadams+nhs@gmail.com")),
+            ..Person::default()
         };
-        assert_eq!(similarity_of_persons((a, b)), BIRTH_DATE_MONTH_EQ / ((BIRTH_DATE_MONTH_EQ + SIMILARITY_MAX) / 2.0) )
+        let mut profile = SimilarityProfile::default();
+        assert_eq!(sub_scores_of_persons((&a, &b), &profile).primary_email, Some(1.0));
+        profile.email_comparator = EmailComparator::Text;
+        assert!(sub_scores_of_persons((&a, &b), &profile).primary_email.unwrap() < 1.0);
     }
 
-        #[test]
-    fn test_birth_date_month_day() {
+    #[test]
+    fn test_phone() {
+        let a = Person { id: String::from("0"), primary_phone: Some(String::from("+44 29 2074 7747")), ..Person::default() };
+        let mut b = Person { id: String::from("1"), primary_phone: Some(String::from("029 2074 7747")), ..Person::default() };
+        let profile = SimilarityProfile::default();
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert_eq!(report.fields[5].value, Some(1.0));
+        assert_eq!(report.fields[5].note, None);
+        b.primary_phone = Some(String::from("not a number"));
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert!(!report.fields[5].present);
+        assert_eq!(report.fields[5].note.as_deref(), Some("phone_invalid"));
+    }
+
+    #[test]
+    fn test_nhs_number() {
+        let a = Person { nhs_number: Some("943 476 5919".parse().unwrap()), ..person("0", "Alice", "Adams") };
+        let mut b = person("1", "Alys", "Adams");
+        let profile = SimilarityProfile::default();
+        let without = similarity_report_of_persons((&a, &b), &profile);
+        assert_eq!(without.fields[7].note, None);
+        b.nhs_number = a.nhs_number.clone();
+        let agreement = similarity_report_of_persons((&a, &b), &profile);
+        assert!(agreement.score > without.score);
+        assert_eq!(agreement.decision, MatchDecision::Match);
+        assert_eq!(agreement.fields[7].note.as_deref(), Some("nhs_number_agreement"));
+        b.nhs_number = Some("401 023 2137".parse().unwrap());
+        let disagreement = similarity_report_of_persons((&a, &b), &profile);
+        assert!(disagreement.score < without.score);
+        assert_eq!(disagreement.decision, MatchDecision::NonMatch);
+        assert_eq!(disagreement.fields[7].note.as_deref(), Some("nhs_number_disagreement"));
+    }
+
+    #[test]
+    fn test_missing_policy() {
+        let a = Person { id: String::from("0"), given_name: Some(String::from("Alice")), ..Person::default() };
+        let b = a.clone();
+        let mut profile = SimilarityProfile::default();
+        let score = |profile: &SimilarityProfile| similarity_report_of_persons((&a, &b), profile).score;
+        assert_eq!(score(&profile), GIVEN_NAME_EQ / ((GIVEN_NAME_EQ + SIMILARITY_MAX) / 2.0));
+        profile.missing = PerField {
+            given_name: MissingPolicy::Ignore,
+            family_name: MissingPolicy::Ignore,
+            birth_date_year: MissingPolicy::Ignore,
+            birth_date_month: MissingPolicy::Ignore,
+            birth_date_month_day: MissingPolicy::Ignore,
+            primary_phone: MissingPolicy::Ignore,
+            primary_email: MissingPolicy::Ignore,
+            nhs_number: MissingPolicy::Ignore,
+            address_lines: MissingPolicy::Ignore,
+            town: MissingPolicy::Ignore,
+            postcode: MissingPolicy::Ignore,
+        };
+        assert_eq!(score(&profile), 1.0);
+        profile.missing.family_name = MissingPolicy::Penalise;
+        assert_eq!(score(&profile), GIVEN_NAME_EQ / (GIVEN_NAME_EQ + FAMILY_NAME_EQ));
+        profile.missing.family_name = MissingPolicy::Neutral;
+        assert_eq!(score(&profile), (GIVEN_NAME_EQ + 0.5 * FAMILY_NAME_EQ) / (GIVEN_NAME_EQ + FAMILY_NAME_EQ));
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert!(!report.fields[1].present);
+        assert!(report.fields[1].contribution > 0.0);
+    }
+
+    #[test]
+    fn test_name_tokens() {
+        assert_eq!(name_tokens(" Mary  Jane "), vec!["Mary", "Jane"]);
+        assert_eq!(name_tokens("Rhys-Jones"), vec!["Rhys", "Jones"]);
+        assert_eq!(name_tokens("ap Hywel Evans"), vec!["ap", "Hywel", "Evans"]);
+        assert!(name_tokens(" - ").is_empty());
+    }
+
+    #[test]
+    fn test_token_comparison() {
+        let exact = |a: &str, b: &str| if a == b { 1.0 } else { 0.0 };
+        let assignment = TokenComparison::default();
+        let monge_elkan = TokenComparison { method: TokenMethod::MongeElkan, ..assignment };
+        assert_eq!(assignment.compare("mary jane", "jane mary", exact), 1.0);
+        assert_eq!(assignment.compare("mary jane", "mary", exact), TOKEN_SUBSET_CREDIT);
+        assert_eq!(assignment.compare("rhys", "rhys-jones", exact), TOKEN_SUBSET_CREDIT);
+        assert_eq!(assignment.compare("", "mary", exact), 0.0);
+        // Monge–Elkan can take one token twice, and assignment cannot.
+        assert_eq!(monge_elkan.compare("mary mary", "mary jane", exact), 1.0);
+        assert_eq!(assignment.compare("mary mary", "mary jane", exact), 0.5);
+        let whole = TokenComparison { method: TokenMethod::Whole, ..assignment };
+        assert_eq!(whole.compare("mary jane", "jane mary", exact), 0.0);
+        assert_eq!(whole.apply(0.7, "mary jane", "jane mary", exact), 0.7);
+    }
+
+    #[test]
+    fn test_tokens() {
+        let a = person("0", "Mary Jane", "Rhys-Jones");
+        let b = Person { given_name: Some(String::from("Jane Mary")), family_name: Some(String::from("Jones")), ..a.clone() };
+        let profile = SimilarityProfile::default();
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!(sub_scores.given_name, Some(1.0));
+        assert_eq!(sub_scores.family_name, Some(TOKEN_SUBSET_CREDIT));
+        let mut whole = profile.clone();
+        whole.tokens.given_name = None;
+        whole.tokens.family_name = None;
+        let whole = sub_scores_of_persons((&a, &b), &whole);
+        assert!(whole.given_name.unwrap() < 0.9);
+        assert!(whole.family_name.unwrap() < 0.9);
+    }
+
+    #[test]
+    fn test_multiple_values() {
         let a = Person {
-            id: String::from("0"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: Some(31),
-            primary_email: None,
-            primary_phone: None,
-            note: None,
+            other_names: vec![
+                OtherName { kind: NameKind::Previous, family_name: Some(String::from("Adams")), ..OtherName::default() },
+                OtherName { kind: NameKind::Preferred, given_name: Some(String::from("Beth")), ..OtherName::default() },
+            ],
+            primary_email: Some(String::from("beth@example.com")),
+            other_emails: vec![String::from("elizabeth.adams@example.com")],
+            ..person("0", "Elizabeth", "Evans")
         };
-        let b = Person { 
-            id: String::from("1"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: Some(31),
-            primary_email: None,
-            primary_phone: None,
-            note: None,
+        let b = Person {
+            given_name: Some(String::from("Beth")),
+            family_name: Some(String::from("Adams")),
+            other_names: Vec::new(),
+            primary_email: Some(String::from("elizabeth.adams@example.com")),
+            other_emails: Vec::new(),
+            ..a.clone()
         };
-        assert_eq!(similarity_of_persons((a, b)), BIRTH_DATE_MONTH_DAY_EQ / ((BIRTH_DATE_MONTH_DAY_EQ + SIMILARITY_MAX) / 2.0) )
+        let profile = SimilarityProfile::default();
+        // "Elizabeth Adams" scores best, because "Beth" is a nickname of "Elizabeth".
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!(sub_scores.given_name, Some(NICKNAME_EQUIVALENT));
+        assert_eq!(sub_scores.family_name, Some(1.0));
+        assert_eq!(sub_scores.primary_email, Some(1.0));
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        let values = report.fields[0].values.as_ref().unwrap();
+        assert_eq!(values.a, ValueSource { origin: ValueOrigin::Previous, index: Some(0), value: String::from("Elizabeth Adams") });
+        assert_eq!(values.b, ValueSource { origin: ValueOrigin::Primary, index: None, value: String::from("Beth Adams") });
+        assert_eq!(report.fields[1].values, report.fields[0].values);
+        let values = report.fields[6].values.as_ref().unwrap();
+        assert_eq!((values.a.origin, values.a.index), (ValueOrigin::Other, Some(0)));
+        assert_eq!(report.fields[5].values, None);
+        // Without other values, the primary values are compared as before.
+        let single = Person { other_names: Vec::new(), other_emails: Vec::new(), ..a.clone() };
+        let report = similarity_report_of_persons((&single, &b), &profile);
+        assert!(report.fields[1].value.unwrap() < 1.0);
+        assert!(report.fields.iter().all(|x| x.values.is_none()));
     }
 
     #[test]
-    fn test_primary_email() {
+    fn test_name_parts() {
+        let a = Person { suffix: Some(String::from("Jr")), ..person("0", "Alice", "Adams") };
+        let b = Person { given_name: Some(String::from("A.")), ..a.clone() };
+        let mut profile = SimilarityProfile::default();
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert_eq!(report.fields[0].value, Some(INITIAL_CREDIT));
+        assert_eq!(report.fields[0].note.as_deref(), Some("initial"));
+        let c = Person { suffix: None, family_name: Some(String::from("Adams Sr.")), ..a.clone() };
+        let same = similarity_report_of_persons((&a, &a), &profile);
+        let different = similarity_report_of_persons((&a, &c), &profile);
+        assert!(different.score <= profile.name_parts.apply_suffix(same.score, &a, &c));
+        assert!(different.score < same.score / 2.0);
+        assert_eq!(different.fields[1].note.as_deref(), Some("suffix_disagreement"));
+        profile.mode = ScoringMode::FellegiSunter;
+        let same = similarity_report_of_persons((&a, &a), &profile);
+        let different = similarity_report_of_persons((&a, &c), &profile);
+        assert!(different.match_weight.unwrap() <= same.match_weight.unwrap() + SUFFIX_DISAGREEMENT.log2());
+    }
+
+    #[test]
+    fn test_term_frequency() {
+        // Every family name in our DATA differs, so each is of average frequency.
+        let a = person("0", "Alice", "Adams");
+        let mut profile = SimilarityProfile::default();
+        let plain = similarity_report_of_persons((&a, &a), &profile);
+        profile.term_frequency.family_name = true;
+        let adjusted = similarity_report_of_persons((&a, &a), &profile);
+        assert!((adjusted.score - plain.score).abs() < 1e-12);
+        profile.mode = ScoringMode::FellegiSunter;
+        let adjusted = similarity_report_of_persons((&a, &a), &profile);
+        let frequency = term_frequencies().fields.family_name.frequency("Adams").unwrap();
+        let exact = profile.fellegi_sunter.fields.family_name.exact;
+        assert!((adjusted.fields[1].weight - (exact.m / frequency).log2()).abs() < 1e-12);
+    }
+
+    #[test]
+    fn test_address() {
         let a = Person {
-            id: String::from("0"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: Some(String::from("alice@example.com")),
-            primary_phone: None,
-            note: None,
+            address: Some(Address {
+                lines: vec![String::from("Flat 2"), String::from("1 Heol-y-Frenhines")],
+                town: Some(String::from("Caerdydd")),
+                county: None,
+                postcode: Some(String::from("CF10 1AA")),
+            }),
+            ..person("0", "Alice", "Adams")
         };
-        let b = Person { 
-            id: String::from("1"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: Some(String::from("alice@example.com")),
-            primary_phone: None,
-            note: None,
+        let b = Person {
+            address: Some(Address {
+                lines: vec![String::from("1 Heol y Frenhines")],
+                town: Some(String::from("CARDIFF")),
+                county: Some(String::from("South Glamorgan")),
+                postcode: Some(String::from("cf101ab")),
+            }),
+            ..a.clone()
         };
-        assert_eq!(similarity_of_persons((a, b)), PRIMARY_EMAIL_EQ / ((PRIMARY_EMAIL_EQ + SIMILARITY_MAX) / 2.0) )
+        let profile = SimilarityProfile::default();
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert_eq!(sub_scores.address_lines, Some(TOKEN_SUBSET_CREDIT));
+        assert_eq!(sub_scores.town, Some(1.0));
+        assert_eq!(sub_scores.postcode, Some(POSTCODE_SECTOR));
+        let report = similarity_report_of_persons((&a, &b), &profile);
+        assert_eq!(report.fields[9].note.as_deref(), Some("bilingual_place_name"));
+        assert_eq!(report.fields[10].note.as_deref(), Some("postcode_sector"));
+        // Without an address, the score is the same as before addresses.
+        let (c, d) = (Person { address: None, ..a.clone() }, Person { address: None, ..b.clone() });
+        assert_eq!(similarity_report_of_persons((&c, &d), &profile).score, similarity_report_of_persons((&a, &d), &profile).score);
+        assert!(report.score > similarity_report_of_persons((&c, &d), &profile).score);
+        let e = Person { address: Some(Address { postcode: Some(String::from("SA1 1AA")), ..Address::default() }), ..a.clone() };
+        let report = similarity_report_of_persons((&a, &e), &profile);
+        assert_eq!(report.fields[10].value, Some(0.0));
+        assert!(!report.fields[8].present);
+        let profile = SimilarityProfile { postcode_comparator: PostcodeComparator::Text, ..SimilarityProfile::default() };
+        let sub_scores = sub_scores_of_persons((&a, &b), &profile);
+        assert!(sub_scores.postcode.unwrap() < 1.0);
+        assert_eq!(notes_of_persons((&a, &b), &profile).postcode, None);
     }
 
     #[test]
-    fn test_primary_phone() {
+    fn test_blank_as_missing() {
         let a = Person {
-            id: String::from("0"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: Some(String::from("3787581685")),
-            note: None,
-        };
-        let b = Person { 
-            id: String::from("1"),
-            given_name: None,
-            family_name: None,
-            birth_date_year: None,
-            birth_date_month: None,
-            birth_date_month_day: None,
-            primary_email: None,
-            primary_phone: Some(String::from("3787581685")),
-            note: None,
+            primary_email: Some(String::from(" ")),
+            primary_phone: Some(String::from("")),
+            ..person("0", "Alice", "")
         };
-        assert_eq!(similarity_of_persons((a, b)), PRIMARY_PHONE_EQ / ((PRIMARY_PHONE_EQ + SIMILARITY_MAX) / 2.0) )
+        let b = Person { family_name: None, primary_email: None, primary_phone: None, ..a.clone() };
+        let mut profile = SimilarityProfile::default();
+        let blank = similarity_report_of_persons((&a, &a), &profile);
+        let missing = similarity_report_of_persons((&b, &b), &profile);
+        assert_eq!(blank.score, missing.score);
+        assert_eq!(blank.fields, missing.fields);
+        profile.blank_as_missing = false;
+        let blank = similarity_report_of_persons((&a, &a), &profile);
+        assert!(blank.fields[1].present);
+        assert!(blank.score < missing.score);
     }
 
-}
\ No newline at end of file
+    #[test]
+    fn test_similarity_trait_with_profile() {
+        let a = person("0", "Alice", "Adams");
+        let b = a.clone();
+        let profile = SimilarityProfile { mode: ScoringMode::FellegiSunter, ..SimilarityProfile::default() };
+        let report: SimilarityReport = Person::similarity((&a, &b, &profile));
+        assert_eq!(report, similarity_report_of_persons((&a, &b), &profile));
+    }
+
+}
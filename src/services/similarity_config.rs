@@ -0,0 +1,989 @@
+// Use LazyLock for creating a thread-safe global variable e.g. our CONFIG.
+use std::sync::LazyLock;
+
+// Use RwLock because the config is read by every request and written rarely.
+use std::sync::RwLock;
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use serde::{Deserialize, Serialize};
+use crate::services::similarity::{Field, MatchThresholds, MissingPolicy, NAME_COMPARATORS, NUMBER_COMPARATORS, PLACE_COMPARATORS, PerField, ScoringMode, TEXT_COMPARATORS, TokenComparison, WEIGHTS};
+use crate::services::birth_date::BirthDateComparator;
+use crate::services::comparators::COMPARATORS;
+use crate::services::email::{EmailComparator, EmailWeights};
+use crate::services::phone::{DEFAULT_PHONE_REGION, PhoneComparator, PhoneRegion};
+use crate::services::postcode::PostcodeComparator;
+use crate::services::fellegi_sunter::FellegiSunterParameters;
+use crate::services::name_parser::NameParts;
+use crate::services::name_swap::NameSwap;
+use crate::services::nhs_number::NhsNumberEvidence;
+use crate::services::nicknames::{NICKNAME_EQUIVALENT, NicknameCredit};
+use crate::services::normalization::{NAME_STEPS, NUMBER_STEPS, NormalizationStep, TEXT_STEPS};
+use crate::services::phonetic::PhoneticCredit;
+
+/// The name of the built-in profile.
+pub const DEFAULT_PROFILE_NAME: &str = "default";
+
+/// The version of the built-in profile.
+pub const DEFAULT_PROFILE_VERSION: &str = "1";
+
+/// Similarity configuration, with named profiles that callers choose per request.
+///
+/// Example TOML:
+///
+/// ```toml
+/// default_profile = "strict"
+///
+/// [profiles.strict]
+/// version = "2025-06-01"
+///
+/// [profiles.strict.weights]
+/// given_name = 0.8
+/// family_name = 1.0
+/// ```
+///
+/// Any weight that a profile omits uses the built-in weight.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimilarityConfig {
+    pub default_profile: String,
+    pub profiles: BTreeMap<String, SimilarityProfile>,
+}
+
+/// One named set of similarity settings.
+///
+/// The version is recorded with every score that uses the profile,
+/// so a score can be traced back to the settings that produced it.
+///
+/// The mode chooses how field sub-scores are combined: the weights are
+/// used in weighted mode, and the Fellegi–Sunter parameters are used in
+/// Fellegi–Sunter mode.
+///
+/// The missing policy of each field chooses how the field counts in weighted
+/// mode when either person lacks it, such as a half penalty, which is the
+/// default, except for the address fields, which are ignored. A blank text
+/// field counts as missing when blank as missing is true, which is the default.
+///
+/// The normalization of each field is a list of steps, applied in order
+/// before comparison, such as case folding and diacritic stripping.
+///
+/// The comparators of each field are names in the comparator registry;
+/// when a field has more than one comparator, its value is their average.
+///
+/// The birth date comparator chooses whether birth dates are compared as a
+/// whole with partial credit for near misses, or part by part.
+///
+/// The email comparator chooses whether email addresses are canonicalised
+/// and compared by local part and domain, with the email weights, or
+/// compared as text with the comparators of the field.
+///
+/// The phone comparator chooses whether phone numbers are parsed into E.164
+/// format, with national numbers in the phone region, such as "GB", or
+/// compared as text with the comparators of the field.
+///
+/// The postcode comparator chooses whether postcodes are parsed and compared
+/// by unit, sector, district, and area, or compared as text with the
+/// comparators of the field.
+///
+/// The token comparison of a field, if any, splits each value into tokens
+/// on spaces and hyphens, and pairs the tokens, so "Mary Jane" and "Jane Mary"
+/// agree, and "Rhys-Jones" and "Jones" get strong partial credit. The built-in
+/// profile compares the given name, family name, and address lines by tokens; a method of
+/// "whole" turns it off.
+///
+/// The phonetic credit of a field, if any, is the least sub-score of the
+/// field when the phonetic codes agree, such as for "Smith" and "Smyth".
+/// The built-in profile has no phonetic credit.
+///
+/// The nickname credit of a field, if any, is the least sub-score of the
+/// field when the values are nickname equivalents, such as "Bob" and "Robert".
+/// The built-in profile gives nickname credit to the given name;
+/// a credit of 0.0 turns it off.
+///
+/// The term frequency switch of a field scales the weight of an exact
+/// agreement by the rarity of the value, so "Jones" counts for less than
+/// "Gruffudd"; see [crate::services::term_frequency]. The built-in profile
+/// has no term frequency adjustment.
+///
+/// The name swap settings detect a given name and family name that are
+/// swapped, such as "Thomas Owen" and "Owen Thomas", and credit the swapped
+/// pairing. The built-in profile detects name swaps; a credit of 0.0 turns it off.
+///
+/// The name parts settings give a given name credit when one given name is
+/// the initial of the other, such as "A." and "Alice", and lower the score
+/// when the suffixes differ, such as "Jr" and "Sr".
+///
+/// The NHS number evidence sets how much an agreement or disagreement of
+/// NHS numbers moves a weighted score, and whether test numbers count.
+///
+/// The thresholds decide each score as a match, possible match, or non-match.
+///
+/// The Fellegi–Sunter parameters can come from a separate parameter file,
+/// such as one written by the expectation–maximisation trainer. A relative
+/// path is relative to the directory of the config file.
+///
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SimilarityProfile {
+    #[serde(skip)]
+    pub name: String,
+    pub version: String,
+    #[serde(default)]
+    pub mode: ScoringMode,
+    #[serde(default)]
+    pub thresholds: MatchThresholds,
+    #[serde(default)]
+    pub weights: PerField<f64>,
+    #[serde(default)]
+    pub missing: PerField<MissingPolicy>,
+    #[serde(default = "default_blank_as_missing")]
+    pub blank_as_missing: bool,
+    #[serde(default)]
+    pub normalization: PerField<Vec<NormalizationStep>>,
+    #[serde(default)]
+    pub comparators: PerField<Vec<String>>,
+    #[serde(default)]
+    pub birth_date_comparator: BirthDateComparator,
+    #[serde(default)]
+    pub email_comparator: EmailComparator,
+    #[serde(default)]
+    pub email_weights: EmailWeights,
+    #[serde(default)]
+    pub phone_comparator: PhoneComparator,
+    #[serde(default = "default_phone_region")]
+    pub phone_region: String,
+    #[serde(default)]
+    pub postcode_comparator: PostcodeComparator,
+    #[serde(default)]
+    pub tokens: PerField<Option<TokenComparison>>,
+    #[serde(default)]
+    pub phonetic: PerField<Option<PhoneticCredit>>,
+    #[serde(default)]
+    pub nicknames: PerField<Option<NicknameCredit>>,
+    #[serde(default)]
+    pub term_frequency: PerField<bool>,
+    #[serde(default)]
+    pub name_swap: NameSwap,
+    #[serde(default)]
+    pub name_parts: NameParts,
+    #[serde(default)]
+    pub nhs_number: NhsNumberEvidence,
+    #[serde(default)]
+    pub fellegi_sunter: FellegiSunterParameters,
+    #[serde(default)]
+    pub fellegi_sunter_path: Option<PathBuf>,
+}
+
+fn default_blank_as_missing() -> bool {
+    true
+}
+
+fn default_phone_region() -> String {
+    String::from(DEFAULT_PHONE_REGION)
+}
+
+impl Default for PerField<f64> {
+    fn default() -> Self {
+        WEIGHTS
+    }
+}
+
+impl Default for PerField<MissingPolicy> {
+    fn default() -> Self {
+        PerField {
+            given_name: MissingPolicy::default(),
+            family_name: MissingPolicy::default(),
+            birth_date_year: MissingPolicy::default(),
+            birth_date_month: MissingPolicy::default(),
+            birth_date_month_day: MissingPolicy::default(),
+            primary_phone: MissingPolicy::default(),
+            primary_email: MissingPolicy::default(),
+            nhs_number: MissingPolicy::default(),
+            address_lines: MissingPolicy::Ignore,
+            town: MissingPolicy::Ignore,
+            postcode: MissingPolicy::Ignore,
+        }
+    }
+}
+
+impl Default for PerField<Vec<String>> {
+    fn default() -> Self {
+        let name = || NAME_COMPARATORS.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+        let text = || TEXT_COMPARATORS.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+        let number = || NUMBER_COMPARATORS.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+        let place = || PLACE_COMPARATORS.iter().map(|x| x.to_string()).collect::<Vec<String>>();
+        PerField {
+            given_name: name(),
+            family_name: name(),
+            birth_date_year: number(),
+            birth_date_month: number(),
+            birth_date_month_day: number(),
+            primary_phone: text(),
+            primary_email: text(),
+            nhs_number: number(),
+            address_lines: text(),
+            town: place(),
+            postcode: text(),
+        }
+    }
+}
+
+impl Default for PerField<Vec<NormalizationStep>> {
+    fn default() -> Self {
+        PerField {
+            given_name: NAME_STEPS.to_vec(),
+            family_name: NAME_STEPS.to_vec(),
+            birth_date_year: NUMBER_STEPS.to_vec(),
+            birth_date_month: NUMBER_STEPS.to_vec(),
+            birth_date_month_day: NUMBER_STEPS.to_vec(),
+            primary_phone: TEXT_STEPS.to_vec(),
+            primary_email: TEXT_STEPS.to_vec(),
+            nhs_number: NUMBER_STEPS.to_vec(),
+            address_lines: NAME_STEPS.to_vec(),
+            town: NAME_STEPS.to_vec(),
+            postcode: TEXT_STEPS.to_vec(),
+        }
+    }
+}
+
+impl Default for PerField<Option<TokenComparison>> {
+    fn default() -> Self {
+        PerField {
+            given_name: Some(TokenComparison::default()),
+            family_name: Some(TokenComparison::default()),
+            birth_date_year: None,
+            birth_date_month: None,
+            birth_date_month_day: None,
+            primary_phone: None,
+            primary_email: None,
+            nhs_number: None,
+            address_lines: Some(TokenComparison::default()),
+            town: None,
+            postcode: None,
+        }
+    }
+}
+
+impl Default for PerField<Option<PhoneticCredit>> {
+    fn default() -> Self {
+        PerField {
+            given_name: None,
+            family_name: None,
+            birth_date_year: None,
+            birth_date_month: None,
+            birth_date_month_day: None,
+            primary_phone: None,
+            primary_email: None,
+            nhs_number: None,
+            address_lines: None,
+            town: None,
+            postcode: None,
+        }
+    }
+}
+
+impl Default for PerField<Option<NicknameCredit>> {
+    fn default() -> Self {
+        PerField {
+            given_name: Some(NicknameCredit(NICKNAME_EQUIVALENT)),
+            family_name: None,
+            birth_date_year: None,
+            birth_date_month: None,
+            birth_date_month_day: None,
+            primary_phone: None,
+            primary_email: None,
+            nhs_number: None,
+            address_lines: None,
+            town: None,
+            postcode: None,
+        }
+    }
+}
+
+impl Default for PerField<bool> {
+    fn default() -> Self {
+        PerField {
+            given_name: false,
+            family_name: false,
+            birth_date_year: false,
+            birth_date_month: false,
+            birth_date_month_day: false,
+            primary_phone: false,
+            primary_email: false,
+            nhs_number: false,
+            address_lines: false,
+            town: false,
+            postcode: false,
+        }
+    }
+}
+
+impl Default for SimilarityProfile {
+    fn default() -> Self {
+        SimilarityProfile {
+            name: String::from(DEFAULT_PROFILE_NAME),
+            version: String::from(DEFAULT_PROFILE_VERSION),
+            mode: ScoringMode::default(),
+            thresholds: MatchThresholds::default(),
+            weights: PerField::default(),
+            missing: PerField::default(),
+            blank_as_missing: default_blank_as_missing(),
+            normalization: PerField::default(),
+            comparators: PerField::default(),
+            birth_date_comparator: BirthDateComparator::default(),
+            email_comparator: EmailComparator::default(),
+            email_weights: EmailWeights::default(),
+            phone_comparator: PhoneComparator::default(),
+            phone_region: default_phone_region(),
+            postcode_comparator: PostcodeComparator::default(),
+            tokens: PerField::default(),
+            phonetic: PerField::default(),
+            nicknames: PerField::default(),
+            term_frequency: PerField::default(),
+            name_swap: NameSwap::default(),
+            name_parts: NameParts::default(),
+            nhs_number: NhsNumberEvidence::default(),
+            fellegi_sunter: FellegiSunterParameters::default(),
+            fellegi_sunter_path: None,
+        }
+    }
+}
+
+impl Default for SimilarityConfig {
+    fn default() -> Self {
+        SimilarityConfig {
+            default_profile: String::from(DEFAULT_PROFILE_NAME),
+            profiles: BTreeMap::from([
+                (String::from(DEFAULT_PROFILE_NAME), SimilarityProfile::default()),
+            ]),
+        }
+    }
+}
+
+/// Error when loading or validating a similarity configuration.
+#[derive(Debug)]
+pub enum SimilarityConfigError {
+    Io(std::io::Error),
+    Parse(String),
+    Invalid(String),
+}
+
+impl std::fmt::Display for SimilarityConfigError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SimilarityConfigError::Io(e) => write!(f, "similarity config io error: {}", e),
+            SimilarityConfigError::Parse(e) => write!(f, "similarity config parse error: {}", e),
+            SimilarityConfigError::Invalid(e) => write!(f, "similarity config invalid: {}", e),
+        }
+    }
+}
+
+impl std::error::Error for SimilarityConfigError {}
+
+impl SimilarityConfig {
+
+    /// Load a configuration from a TOML file or JSON file, then validate it.
+    ///
+    /// The file format is chosen by the file extension: ".toml" or ".json".
+    ///
+    pub fn from_path(path: &Path) -> Result<Self, SimilarityConfigError> {
+        let text = std::fs::read_to_string(path).map_err(SimilarityConfigError::Io)?;
+        let config: SimilarityConfig = match path.extension().and_then(|x| x.to_str()) {
+            Some("toml") => toml::from_str(&text).map_err(|e| SimilarityConfigError::Parse(e.to_string()))?,
+            Some("json") => serde_json::from_str(&text).map_err(|e| SimilarityConfigError::Parse(e.to_string()))?,
+            _ => return Err(SimilarityConfigError::Parse(format!("unknown file extension for {}; expected .toml or .json", path.display()))),
+        };
+        config.validated(path.parent().unwrap_or(Path::new(".")))
+    }
+
+    /// Parse a configuration from TOML text, then validate it.
+    pub fn from_toml(text: &str) -> Result<Self, SimilarityConfigError> {
+        let config: SimilarityConfig = toml::from_str(text)
+            .map_err(|e| SimilarityConfigError::Parse(e.to_string()))?;
+        config.validated(Path::new("."))
+    }
+
+    /// Parse a configuration from JSON text, then validate it.
+    pub fn from_json(text: &str) -> Result<Self, SimilarityConfigError> {
+        let config: SimilarityConfig = serde_json::from_str(text)
+            .map_err(|e| SimilarityConfigError::Parse(e.to_string()))?;
+        config.validated(Path::new("."))
+    }
+
+    /// Name each profile by its key, load any parameter files relative to
+    /// the base directory, then validate the configuration.
+    fn validated(mut self, base: &Path) -> Result<Self, SimilarityConfigError> {
+        for (name, profile) in self.profiles.iter_mut() {
+            profile.name = name.clone();
+            if let Some(path) = &profile.fellegi_sunter_path {
+                profile.fellegi_sunter = FellegiSunterParameters::from_path(&base.join(path))?;
+            }
+        }
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Validate the configuration.
+    ///
+    /// - There must be at least one profile, and the default profile must exist.
+    ///
+    /// - Each profile must have a name and a version.
+    ///
+    /// - Each weight must be a finite number that is zero or more,
+    ///   and at least one weight must be more than zero.
+    ///
+    /// - Each field must have at least one comparator, and each comparator
+    ///   must be in the comparator registry.
+    ///
+    /// - Each token subset credit, phonetic credit, and nickname credit must be from 0.0 to 1.0.
+    ///
+    /// - The name swap credit and margin must be from 0.0 to 1.0.
+    ///
+    /// - The name parts initial credit must be from 0.0 to 1.0, and the
+    ///   suffix disagreement must be more than 0.0 and at most 1.0.
+    ///
+    /// - Each email weight must be a finite number that is zero or more.
+    ///
+    /// - The NHS number agreement must be at least 1.0, and the disagreement
+    ///   must be more than 0.0 and at most 1.0.
+    ///
+    /// - The phone region must be a supported region; see [crate::services::phone::PHONE_REGIONS].
+    ///
+    /// - The thresholds must be from 0.0 to 1.0, and the possible match
+    ///   threshold must be at most the match threshold.
+    ///
+    /// - The Fellegi–Sunter parameters must be valid.
+    ///
+    pub fn validate(&self) -> Result<(), SimilarityConfigError> {
+        let invalid = |s: String| Err(SimilarityConfigError::Invalid(s));
+        if self.profiles.is_empty() {
+            return invalid(String::from("there must be at least one profile"));
+        }
+        if !self.profiles.contains_key(&self.default_profile) {
+            return invalid(format!("default profile {:?} is not in profiles", self.default_profile));
+        }
+        for (name, profile) in self.profiles.iter() {
+            if name.trim().is_empty() {
+                return invalid(String::from("profile name must not be blank"));
+            }
+            if profile.version.trim().is_empty() {
+                return invalid(format!("profile {:?} version must not be blank", name));
+            }
+            for field in Field::ALL {
+                let weight = *profile.weights.get(field);
+                if !weight.is_finite() || weight < 0.0 {
+                    return invalid(format!("profile {:?} weight {:?} must be a finite number >= 0", name, field));
+                }
+            }
+            if Field::ALL.iter().all(|&field| *profile.weights.get(field) == 0.0) {
+                return invalid(format!("profile {:?} must have at least one weight > 0", name));
+            }
+            let registry = COMPARATORS.read().unwrap();
+            for field in Field::ALL {
+                let comparators = profile.comparators.get(field);
+                if comparators.is_empty() {
+                    return invalid(format!("profile {:?} field {:?} must have at least one comparator", name, field));
+                }
+                if let Some(comparator) = comparators.iter().find(|x| !registry.contains(x)) {
+                    return invalid(format!("profile {:?} field {:?} comparator {:?} is not in the registry", name, field, comparator));
+                }
+            }
+            for field in Field::ALL {
+                if let Some(tokens) = profile.tokens.get(field) && !(0.0..=1.0).contains(&tokens.subset_credit) {
+                    return invalid(format!("profile {:?} field {:?} token subset credit must be from 0 to 1", name, field));
+                }
+                if let Some(phonetic) = profile.phonetic.get(field) && !(0.0..=1.0).contains(&phonetic.credit) {
+                    return invalid(format!("profile {:?} field {:?} phonetic credit must be from 0 to 1", name, field));
+                }
+                if let Some(NicknameCredit(credit)) = profile.nicknames.get(field) && !(0.0..=1.0).contains(credit) {
+                    return invalid(format!("profile {:?} field {:?} nickname credit must be from 0 to 1", name, field));
+                }
+            }
+            if !(0.0..=1.0).contains(&profile.name_swap.credit) || !(0.0..=1.0).contains(&profile.name_swap.margin) {
+                return invalid(format!("profile {:?} name swap credit and margin must be from 0 to 1", name));
+            }
+            let parts = &profile.name_parts;
+            if !((0.0..=1.0).contains(&parts.initial_credit) && parts.suffix_disagreement > 0.0 && parts.suffix_disagreement <= 1.0) {
+                return invalid(format!("profile {:?} name parts must have 0 <= initial_credit <= 1 and 0 < suffix_disagreement <= 1", name));
+            }
+            let email = &profile.email_weights;
+            if !(email.local.is_finite() && email.local >= 0.0 && email.domain.is_finite() && email.domain >= 0.0) {
+                return invalid(format!("profile {:?} email weights must be finite numbers >= 0", name));
+            }
+            let nhs_number = &profile.nhs_number;
+            if !(nhs_number.agreement >= 1.0 && nhs_number.agreement.is_finite() && nhs_number.disagreement > 0.0 && nhs_number.disagreement <= 1.0) {
+                return invalid(format!("profile {:?} nhs_number must have agreement >= 1 and 0 < disagreement <= 1", name));
+            }
+            if PhoneRegion::from_code(&profile.phone_region).is_none() {
+                return invalid(format!("profile {:?} phone region {:?} is not supported", name, profile.phone_region));
+            }
+            let thresholds = &profile.thresholds;
+            if !(0.0 <= thresholds.possible_match_min && thresholds.possible_match_min <= thresholds.match_min && thresholds.match_min <= 1.0) {
+                return invalid(format!("profile {:?} thresholds must have 0 <= possible_match_min <= match_min <= 1", name));
+            }
+            if let Err(e) = profile.fellegi_sunter.validate() {
+                return invalid(format!("profile {:?} fellegi_sunter {}", name, e));
+            }
+        }
+        Ok(())
+    }
+
+    /// Get a profile by name, or the default profile if the name is `None`.
+    pub fn profile(&self, name: Option<&str>) -> Option<&SimilarityProfile> {
+        self.profiles.get(name.unwrap_or(&self.default_profile))
+    }
+
+}
+
+// Create the active configuration as a global variable with `LazyLock` and `RwLock`.
+//
+// This starts as the built-in configuration; `main` replaces it at startup
+// when the environment variable `SIMILARITY_CONFIG` names a config file.
+
+pub static CONFIG: LazyLock<RwLock<SimilarityConfig>> = LazyLock::new(|| {
+    RwLock::new(SimilarityConfig::default())
+});
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::services::phonetic::PhoneticEncoder;
+    use crate::services::similarity::TokenMethod;
+
+    #[test]
+    fn test_default() {
+        let config = SimilarityConfig::default();
+        assert!(config.validate().is_ok());
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.name, DEFAULT_PROFILE_NAME);
+        assert_eq!(profile.weights, WEIGHTS);
+    }
+
+    #[test]
+    fn test_from_toml() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "front-desk"
+
+            [profiles.strict]
+            version = "2"
+
+            [profiles.front-desk]
+            version = "3"
+
+            [profiles.front-desk.weights]
+            given_name = 0.5
+            family_name = 0.5
+            birth_date_year = 0.4
+            birth_date_month = 0.3
+            birth_date_month_day = 0.2
+            primary_phone = 0.0
+            primary_email = 0.0
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.name, "front-desk");
+        assert_eq!(profile.version, "3");
+        assert_eq!(profile.weights.given_name, 0.5);
+        assert_eq!(profile.weights.primary_phone, 0.0);
+        let profile = config.profile(Some("strict")).unwrap();
+        assert_eq!(profile.weights, WEIGHTS);
+        assert_eq!(profile.comparators, PerField::default());
+        assert!(config.profile(Some("missing")).is_none());
+    }
+
+    #[test]
+    fn test_from_json() {
+        let config = SimilarityConfig::from_json(r#"{
+            "default_profile": "strict",
+            "profiles": { "strict": { "version": "2" } }
+        }"#).unwrap();
+        assert_eq!(config.profile(None).unwrap().version, "2");
+    }
+
+    #[test]
+    fn test_from_path_example() {
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("config/similarity.example.toml");
+        let config = SimilarityConfig::from_path(&path).unwrap();
+        assert!(config.profile(Some("strict")).is_some());
+        let profile = config.profile(Some("front-desk")).unwrap();
+        assert_eq!(profile.weights.given_name, 1.0);
+        assert_eq!(profile.weights.birth_date_year, WEIGHTS.birth_date_year);
+        let profile = config.profile(Some("probabilistic")).unwrap();
+        assert_eq!(profile.mode, ScoringMode::FellegiSunter);
+        assert_eq!(profile.fellegi_sunter.fields.family_name.exact.m, 0.9);
+    }
+
+    #[test]
+    fn test_thresholds() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.thresholds]
+            match_min = 0.95
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.thresholds, MatchThresholds { match_min: 0.95, ..MatchThresholds::default() });
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nthresholds = { match = 0.95 }\n"),
+            Err(SimilarityConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_fellegi_sunter() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "probabilistic"
+
+            [profiles.probabilistic]
+            version = "1"
+            mode = "fellegi_sunter"
+
+            [profiles.probabilistic.fellegi_sunter]
+            prior = 0.001
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.mode, ScoringMode::FellegiSunter);
+        assert_eq!(profile.fellegi_sunter.prior, 0.001);
+        assert_eq!(profile.fellegi_sunter.fields, FellegiSunterParameters::default().fields);
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\n[profiles.x.fellegi_sunter]\nprior = 2.0\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_fellegi_sunter_path() {
+        let dir = std::env::temp_dir().join(format!("similarity_config_test_{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let parameters = FellegiSunterParameters { prior: 0.03, ..FellegiSunterParameters::default() };
+        parameters.to_path(&dir.join("parameters.json")).unwrap();
+        std::fs::write(dir.join("config.toml"), concat!(
+            "default_profile = \"trained\"\n",
+            "[profiles.trained]\n",
+            "version = \"1\"\n",
+            "mode = \"fellegi_sunter\"\n",
+            "fellegi_sunter_path = \"parameters.json\"\n",
+        )).unwrap();
+        let config = SimilarityConfig::from_path(&dir.join("config.toml"));
+        std::fs::remove_dir_all(&dir).unwrap();
+        assert_eq!(config.unwrap().profile(None).unwrap().fellegi_sunter, parameters);
+    }
+
+    #[test]
+    fn test_comparators() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.comparators]
+            given_name = ["jaro_winkler", "jaccard"]
+            primary_phone = ["exact"]
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.comparators.given_name, vec!["jaro_winkler", "jaccard"]);
+        assert_eq!(profile.comparators.primary_phone, vec!["exact"]);
+        assert_eq!(profile.comparators.family_name, PerField::<Vec<String>>::default().family_name);
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\ncomparators = { given_name = [\"nope\"] }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\ncomparators = { given_name = [] }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_tokens() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.tokens]
+            given_name = { method = "whole" }
+            family_name = { method = "monge_elkan", subset_credit = 0.8 }
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.tokens.given_name, Some(TokenComparison { method: TokenMethod::Whole, ..TokenComparison::default() }));
+        assert_eq!(profile.tokens.family_name, Some(TokenComparison { method: TokenMethod::MongeElkan, subset_credit: 0.8 }));
+        assert_eq!(profile.tokens.primary_email, None);
+        assert_eq!(SimilarityProfile::default().tokens.family_name, Some(TokenComparison::default()));
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\ntokens = { family_name = { subset_credit = 1.5 } }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_phonetic() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.phonetic]
+            family_name = { encoder = "double_metaphone", credit = 0.9 }
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.phonetic.family_name, Some(PhoneticCredit { encoder: PhoneticEncoder::DoubleMetaphone, credit: 0.9 }));
+        assert_eq!(profile.phonetic.given_name, None);
+        assert_eq!(SimilarityProfile::default().phonetic, PerField::default());
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nphonetic = { given_name = { encoder = \"soundex\", credit = 1.5 } }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nphonetic = { given_name = { encoder = \"nope\", credit = 0.5 } }\n"),
+            Err(SimilarityConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_nicknames() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.nicknames]
+            given_name = 0.9
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.nicknames.given_name, Some(NicknameCredit(0.9)));
+        assert_eq!(profile.nicknames.family_name, None);
+        assert_eq!(SimilarityProfile::default().nicknames.given_name, Some(NicknameCredit(NICKNAME_EQUIVALENT)));
+        let config = SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nnicknames = { family_name = 0.9 }\n").unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.nicknames.given_name, Some(NicknameCredit(NICKNAME_EQUIVALENT)));
+        assert_eq!(profile.nicknames.family_name, Some(NicknameCredit(0.9)));
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nnicknames = { given_name = -0.5 }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_name_swap() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.name_swap]
+            credit = 0.8
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.name_swap, NameSwap { credit: 0.8, ..NameSwap::default() });
+        assert_eq!(SimilarityProfile::default().name_swap, NameSwap::default());
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nname_swap = { margin = 1.5 }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_name_parts() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.name_parts]
+            initial_credit = 0.0
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.name_parts, NameParts { initial_credit: 0.0, ..NameParts::default() });
+        assert_eq!(SimilarityProfile::default().name_parts, NameParts::default());
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nname_parts = { suffix_disagreement = 0.0 }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_email() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+            email_comparator = "text"
+
+            [profiles.x.email_weights]
+            domain = 0.5
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.email_comparator, EmailComparator::Text);
+        assert_eq!(profile.email_weights, EmailWeights { domain: 0.5, ..EmailWeights::default() });
+        assert_eq!(SimilarityProfile::default().email_comparator, EmailComparator::Structured);
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nemail_weights = { local = -1.0 }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_phone() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+            phone_comparator = "text"
+            phone_region = "IE"
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.phone_comparator, PhoneComparator::Text);
+        assert_eq!(profile.phone_region, "IE");
+        assert_eq!(SimilarityProfile::default().phone_comparator, PhoneComparator::E164);
+        assert_eq!(SimilarityProfile::default().phone_region, "GB");
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nphone_region = \"XX\"\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_postcode() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+            postcode_comparator = "text"
+
+            [profiles.x.missing]
+            postcode = "half_penalty"
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.postcode_comparator, PostcodeComparator::Text);
+        assert_eq!(profile.missing.postcode, MissingPolicy::HalfPenalty);
+        assert_eq!(profile.missing.town, MissingPolicy::Ignore);
+        let profile = SimilarityProfile::default();
+        assert_eq!(profile.postcode_comparator, PostcodeComparator::Levels);
+        assert_eq!(profile.comparators.town, vec![String::from("place_name")]);
+        assert!(profile.tokens.address_lines.is_some());
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\npostcode_comparator = \"sector\"\n"),
+            Err(SimilarityConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_nhs_number() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.nhs_number]
+            agreement = 100.0
+            reject_test = true
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.nhs_number, NhsNumberEvidence { agreement: 100.0, reject_test: true, ..NhsNumberEvidence::default() });
+        assert_eq!(SimilarityProfile::default().nhs_number, NhsNumberEvidence::default());
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nnhs_number = { disagreement = 0.0 }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+    }
+
+    #[test]
+    fn test_missing() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+            blank_as_missing = false
+
+            [profiles.x.missing]
+            primary_phone = "ignore"
+            primary_email = "penalise"
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert!(!profile.blank_as_missing);
+        assert_eq!(profile.missing.primary_phone, MissingPolicy::Ignore);
+        assert_eq!(profile.missing.primary_email, MissingPolicy::Penalise);
+        assert_eq!(profile.missing.given_name, MissingPolicy::HalfPenalty);
+        assert!(SimilarityProfile::default().blank_as_missing);
+        assert!(SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nmissing = { given_name = \"sometimes\" }\n").is_err());
+    }
+
+    #[test]
+    fn test_term_frequency() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.term_frequency]
+            family_name = true
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert!(profile.term_frequency.family_name);
+        assert!(!profile.term_frequency.given_name);
+        assert!(Field::ALL.iter().all(|&field| !*SimilarityProfile::default().term_frequency.get(field)));
+        assert!(SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nterm_frequency = { surname = true }\n").is_err());
+    }
+
+    #[test]
+    fn test_normalization() {
+        let config = SimilarityConfig::from_toml(r#"
+            default_profile = "x"
+
+            [profiles.x]
+            version = "1"
+
+            [profiles.x.normalization]
+            given_name = ["nfkc", "case_fold"]
+            primary_phone = ["remove_punctuation", "collapse_whitespace"]
+        "#).unwrap();
+        let profile = config.profile(None).unwrap();
+        assert_eq!(profile.normalization.given_name, vec![NormalizationStep::Nfkc, NormalizationStep::CaseFold]);
+        assert_eq!(profile.normalization.family_name, NAME_STEPS.to_vec());
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nnormalization = { given_name = [\"nope\"] }\n"),
+            Err(SimilarityConfigError::Parse(_))
+        ));
+    }
+
+    #[test]
+    fn test_invalid() {
+        assert!(matches!(
+            SimilarityConfig::from_json(r#"{"default_profile": "x", "profiles": {"y": {"version": "1"}}}"#),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            SimilarityConfig::from_json(r#"{"default_profile": "x", "profiles": {"x": {"version": " "}}}"#),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\n[profiles.x.weights]\ngiven_name = -1.0\nfamily_name = 1.0\nbirth_date_year = 1.0\nbirth_date_month = 1.0\nbirth_date_month_day = 1.0\nprimary_phone = 1.0\nprimary_email = 1.0\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nthresholds = { match_min = 0.5, possible_match_min = 0.6 }\n"),
+            Err(SimilarityConfigError::Invalid(_))
+        ));
+        assert!(matches!(
+            SimilarityConfig::from_toml("default_profile = \"x\"\n[profiles.x]\nversion = \"1\"\nwieghts = {}\n"),
+            Err(SimilarityConfigError::Parse(_))
+        ));
+    }
+
+}
@@ -0,0 +1,399 @@
+// Term-frequency adjustment of agreement weights.
+//
+// In Wales, an exact match on "Jones" is far weaker evidence than an exact
+// match on "Gruffudd", because many more people are called Jones. The flat
+// weight of a field ignores this, so a profile can turn on term-frequency
+// adjustment per field: when two values agree exactly, the weight of the
+// field is scaled by the rarity of the value.
+//
+// The frequency tables count the values of each field in our DATA, and
+// refresh when the records change; see [crate::data::DATA_GENERATION].
+// A reference frequency file, such as national surname counts, replaces
+// the DATA counts of each field that it covers. Load one at startup with
+// the environment variable `TERM_FREQUENCIES_CSV`.
+//
+// The rarity factor of a value with frequency p is ln(p) / ln(q), where q
+// is the chance that two random values of the field agree, i.e. the sum of
+// the squares of the frequencies. A value of average frequency has a factor
+// of 1.0, a common value has less, and a rare value has more. In
+// Fellegi–Sunter mode, the frequency of the value is the u-probability of
+// the exact level instead, which is the standard adjustment.
+
+// Use LazyLock for creating a thread-safe global variable e.g. our TERM_FREQUENCIES.
+use std::sync::LazyLock;
+
+// Use RwLock because the tables are read by every comparison and written rarely.
+use std::sync::RwLock;
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+use std::sync::atomic::Ordering;
+use crate::data::{DATA, DATA_GENERATION};
+use crate::models::person::Person;
+use crate::services::expectation_maximisation::PROBABILITY_MIN;
+use crate::services::fellegi_sunter::FellegiSunterParameters;
+use crate::services::normalization::{NAME_STEPS, Normalizer};
+use crate::services::similarity::{Field, PerField, field_text};
+
+/// The least and most rarity factor of an agreement weight.
+pub const TERM_FREQUENCY_FACTOR_RANGE: (f64, f64) = (0.25, 4.0);
+
+/// The count of each value of one field.
+///
+/// Values are counted by key: normalized like names, so "JONES" and
+/// "Jones" are the same value. Blank values are not counted.
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct FrequencyTable {
+    counts: HashMap<String, usize>,
+    total: usize,
+}
+
+impl FrequencyTable {
+
+    /// Add a count of one value.
+    pub fn add(&mut self, value: &str, count: usize) {
+        let key = key(value);
+        if key.is_empty() || count == 0 {
+            return;
+        }
+        *self.counts.entry(key).or_default() += count;
+        self.total += count;
+    }
+
+    /// The total count of all values.
+    pub fn total(&self) -> usize {
+        self.total
+    }
+
+    /// True when there are no values.
+    pub fn is_empty(&self) -> bool {
+        self.total == 0
+    }
+
+    /// The frequency of a value, from 0.0 to 1.0, if the table is not empty.
+    ///
+    /// A value that is not in the table counts as once, because it is
+    /// at least as rare as the rarest value.
+    ///
+    pub fn frequency(&self, value: &str) -> Option<f64> {
+        if self.total == 0 {
+            return None;
+        }
+        let count = self.counts.get(&key(value)).copied().unwrap_or(0).max(1);
+        Some((count as f64 / self.total as f64).min(1.0))
+    }
+
+    /// The chance that two random values agree, i.e. the sum of the squares of the frequencies.
+    pub fn agreement(&self) -> Option<f64> {
+        if self.total == 0 {
+            return None;
+        }
+        let total = self.total as f64;
+        Some(self.counts.values().map(|&count| (count as f64 / total).powi(2)).sum())
+    }
+
+    /// The rarity factor of a value, within [TERM_FREQUENCY_FACTOR_RANGE].
+    ///
+    /// This is 1.0 when the table cannot tell, such as when it is empty or
+    /// has only one distinct value.
+    ///
+    pub fn factor(&self, value: &str) -> f64 {
+        match (self.frequency(value), self.agreement()) {
+            (Some(frequency), Some(agreement)) if agreement < 1.0 => {
+                (frequency.ln() / agreement.ln()).clamp(TERM_FREQUENCY_FACTOR_RANGE.0, TERM_FREQUENCY_FACTOR_RANGE.1)
+            }
+            _ => 1.0,
+        }
+    }
+
+}
+
+impl Default for PerField<FrequencyTable> {
+    fn default() -> Self {
+        PerField {
+            given_name: FrequencyTable::default(),
+            family_name: FrequencyTable::default(),
+            birth_date_year: FrequencyTable::default(),
+            birth_date_month: FrequencyTable::default(),
+            birth_date_month_day: FrequencyTable::default(),
+            primary_phone: FrequencyTable::default(),
+            primary_email: FrequencyTable::default(),
+            nhs_number: FrequencyTable::default(),
+            address_lines: FrequencyTable::default(),
+            town: FrequencyTable::default(),
+            postcode: FrequencyTable::default(),
+        }
+    }
+}
+
+/// The frequency tables of every field.
+///
+/// The CSV format of a reference frequency file is one value per line:
+/// the field name, the value, and the count. Lines that are blank or
+/// start with "#" are ignored.
+///
+/// ```csv
+/// family_name,Jones,5700
+/// family_name,Gruffudd,12
+/// ```
+///
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TermFrequencies {
+    pub fields: PerField<FrequencyTable>,
+}
+
+impl TermFrequencies {
+
+    /// Count the values of every field of the persons.
+    pub fn of_persons<'a>(persons: impl IntoIterator<Item = &'a Person>) -> Self {
+        let mut frequencies = TermFrequencies::default();
+        for person in persons {
+            for field in Field::ALL {
+                if let Some(value) = field_text(person, field) {
+                    frequencies.fields.get_mut(field).add(&value, 1);
+                }
+            }
+        }
+        frequencies
+    }
+
+    /// Add counts from CSV text.
+    pub fn extend_from_csv(&mut self, text: &str) -> Result<(), String> {
+        for (i, line) in text.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let parts: Vec<&str> = line.split(',').map(|x| x.trim()).collect();
+            let [name, value, count] = parts[..] else {
+                return Err(format!("line {}: expected field,value,count", i + 1));
+            };
+            let field = Field::from_name(name)
+                .ok_or_else(|| format!("line {}: unknown field {:?}", i + 1, name))?;
+            let count: usize = count.parse()
+                .map_err(|_| format!("line {}: count {:?} must be a whole number", i + 1, count))?;
+            self.fields.get_mut(field).add(value, count);
+        }
+        Ok(())
+    }
+
+    /// Add counts from a CSV file.
+    pub fn extend_from_path(&mut self, path: &Path) -> Result<(), String> {
+        let text = std::fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        self.extend_from_csv(&text).map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    /// Replace the table of each field that the other covers.
+    pub fn overlay(&mut self, other: &TermFrequencies) {
+        for field in Field::ALL {
+            if !other.fields.get(field).is_empty() {
+                *self.fields.get_mut(field) = other.fields.get(field).clone();
+            }
+        }
+    }
+
+    /// Scale the weight of each enabled field whose values agree exactly
+    /// by the rarity factor of the value of the first person.
+    pub fn adjust_weights(&self, weights: &PerField<f64>, enabled: &PerField<bool>, sub_scores: &PerField<Option<f64>>, person: &Person) -> PerField<f64> {
+        let mut weights = *weights;
+        for field in Field::ALL {
+            if let Some(value) = self.agreed_value(field, enabled, sub_scores, person) {
+                *weights.get_mut(field) *= self.fields.get(field).factor(&value);
+            }
+        }
+        weights
+    }
+
+    /// Copy parameters, replacing the exact-level u-probability of each
+    /// enabled field whose values agree exactly by the frequency of the
+    /// value of the first person.
+    pub fn adjust_parameters(&self, parameters: &FellegiSunterParameters, enabled: &PerField<bool>, sub_scores: &PerField<Option<f64>>, person: &Person) -> FellegiSunterParameters {
+        let mut parameters = parameters.clone();
+        for field in Field::ALL {
+            if let Some(value) = self.agreed_value(field, enabled, sub_scores, person)
+                && let Some(frequency) = self.fields.get(field).frequency(&value)
+            {
+                parameters.fields.get_mut(field).exact.u = frequency.clamp(PROBABILITY_MIN, 1.0 - PROBABILITY_MIN);
+            }
+        }
+        parameters
+    }
+
+    // The value of a field, when the field is enabled and the values agree exactly.
+    fn agreed_value(&self, field: Field, enabled: &PerField<bool>, sub_scores: &PerField<Option<f64>>, person: &Person) -> Option<String> {
+        if !*enabled.get(field) || !matches!(sub_scores.get(field), Some(x) if *x >= 1.0) {
+            return None;
+        }
+        field_text(person, field)
+    }
+
+}
+
+// The table key of a value: normalized like a name.
+fn key(value: &str) -> String {
+    NAME_STEPS.normalize(value)
+}
+
+/// The cached frequency tables, and the reference tables that replace them.
+///
+/// The cache records the DATA generation that it counted, and counts again
+/// when the generation has changed, i.e. when records have changed.
+///
+#[derive(Debug, Default)]
+pub struct TermFrequencyCache {
+    reference: TermFrequencies,
+    cached: Option<(u64, Arc<TermFrequencies>)>,
+}
+
+impl TermFrequencyCache {
+
+    /// Add reference counts from a CSV file, and clear the cache.
+    pub fn extend_reference_from_path(&mut self, path: &Path) -> Result<(), String> {
+        self.reference.extend_from_path(path)?;
+        self.cached = None;
+        Ok(())
+    }
+
+}
+
+// Create the term frequency cache as a global variable with `LazyLock` and `RwLock`.
+//
+// This starts empty, and counts our DATA on first use; `main` adds reference
+// counts at startup when the environment variable `TERM_FREQUENCIES_CSV`
+// names a CSV file.
+
+pub static TERM_FREQUENCIES: LazyLock<RwLock<TermFrequencyCache>> = LazyLock::new(|| {
+    RwLock::new(TermFrequencyCache::default())
+});
+
+/// Get the current frequency tables, counting our DATA again if records have changed.
+///
+/// Do not call this while holding the DATA lock.
+///
+pub fn term_frequencies() -> Arc<TermFrequencies> {
+    let generation = DATA_GENERATION.load(Ordering::Acquire);
+    if let Some((cached_generation, frequencies)) = &TERM_FREQUENCIES.read().unwrap().cached
+        && *cached_generation == generation
+    {
+        return frequencies.clone();
+    }
+    let mut frequencies = {
+        let data = DATA.lock().unwrap();
+        TermFrequencies::of_persons(data.values())
+    };
+    let mut cache = TERM_FREQUENCIES.write().unwrap();
+    frequencies.overlay(&cache.reference);
+    let frequencies = Arc::new(frequencies);
+    cache.cached = Some((generation, frequencies.clone()));
+    frequencies
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn table(values: &[(&str, usize)]) -> FrequencyTable {
+        let mut table = FrequencyTable::default();
+        for (value, count) in values {
+            table.add(value, *count);
+        }
+        table
+    }
+
+    #[test]
+    fn test_frequency() {
+        let table = table(&[("Jones", 60), ("JONES ", 20), ("Gruffudd", 1), ("", 5)]);
+        assert_eq!(table.total(), 81);
+        assert_eq!(table.frequency("jones"), Some(80.0 / 81.0));
+        assert_eq!(table.frequency("Gruffudd"), Some(1.0 / 81.0));
+        assert_eq!(table.frequency("Zebedee"), Some(1.0 / 81.0));
+        assert_eq!(FrequencyTable::default().frequency("Jones"), None);
+    }
+
+    #[test]
+    fn test_factor() {
+        let table = table(&[("Jones", 50), ("Williams", 30), ("Davies", 15), ("Gruffudd", 5)]);
+        assert!(table.factor("Jones") < 1.0);
+        assert!(table.factor("Gruffudd") > 1.0);
+        assert!(table.factor("Jones") < table.factor("Davies"));
+        assert!(table.factor("Zebedee") <= TERM_FREQUENCY_FACTOR_RANGE.1);
+        let uniform = self::table(&[("a", 1), ("b", 1), ("c", 1)]);
+        assert!((uniform.factor("a") - 1.0).abs() < 1e-12);
+        assert_eq!(self::table(&[("a", 3)]).factor("a"), 1.0);
+        assert_eq!(FrequencyTable::default().factor("a"), 1.0);
+    }
+
+    #[test]
+    fn test_extend_from_csv() {
+        let mut frequencies = TermFrequencies::default();
+        frequencies.extend_from_csv("# comment\n\nfamily_name,Jones,90\nfamily_name, Gruffudd ,10\n").unwrap();
+        assert_eq!(frequencies.fields.family_name.total(), 100);
+        assert_eq!(frequencies.fields.family_name.frequency("gruffudd"), Some(0.1));
+        assert!(frequencies.fields.given_name.is_empty());
+        assert!(frequencies.extend_from_csv("surname,Jones,1\n").is_err());
+        assert!(frequencies.extend_from_csv("family_name,Jones\n").is_err());
+        assert!(frequencies.extend_from_csv("family_name,Jones,many\n").is_err());
+    }
+
+    #[test]
+    fn test_overlay() {
+        let persons: Vec<Person> = DATA.lock().unwrap().values().cloned().collect();
+        let mut frequencies = TermFrequencies::of_persons(&persons);
+        assert_eq!(frequencies.fields.family_name.total(), persons.len());
+        let mut reference = TermFrequencies::default();
+        reference.extend_from_csv("family_name,Jones,90\nfamily_name,Gruffudd,10\n").unwrap();
+        frequencies.overlay(&reference);
+        assert_eq!(frequencies.fields.family_name, reference.fields.family_name);
+        assert_eq!(frequencies.fields.given_name.total(), persons.len());
+    }
+
+    #[test]
+    fn test_adjust() {
+        let mut frequencies = TermFrequencies::default();
+        frequencies.extend_from_csv("family_name,Jones,90\nfamily_name,Gruffudd,5\nfamily_name,Evans,5\n").unwrap();
+        let person = |family_name: &str| Person {
+            id: String::from("x"),
+            nhs_number: None,
+            title: None,
+            given_name: None,
+            middle_names: None,
+            family_name: Some(family_name.to_string()),
+            suffix: None,
+            other_names: Vec::new(),
+            birth_date: None,
+            primary_email: None,
+            other_emails: Vec::new(),
+            primary_phone: None,
+            other_phones: Vec::new(),
+            address: None,
+            note: None,
+        };
+        let enabled = PerField { family_name: true, ..PerField::default() };
+        let weights = PerField { family_name: 1.0, given_name: 1.0, ..PerField::default() };
+        let agree = PerField { family_name: Some(1.0), given_name: Some(1.0), ..PerField::default() };
+        let jones = frequencies.adjust_weights(&weights, &enabled, &agree, &person("Jones"));
+        let gruffudd = frequencies.adjust_weights(&weights, &enabled, &agree, &person("Gruffudd"));
+        assert!(jones.family_name < 1.0);
+        assert!(gruffudd.family_name > 1.0);
+        assert_eq!(jones.given_name, 1.0);
+        let near = PerField { family_name: Some(0.9), ..agree };
+        assert_eq!(frequencies.adjust_weights(&weights, &enabled, &near, &person("Jones")).family_name, 1.0);
+        let parameters = FellegiSunterParameters::default();
+        let adjusted = frequencies.adjust_parameters(&parameters, &enabled, &agree, &person("Gruffudd"));
+        assert_eq!(adjusted.fields.family_name.exact.u, 0.05);
+        assert_eq!(adjusted.fields.given_name, parameters.fields.given_name);
+    }
+
+    #[test]
+    fn test_term_frequencies_refresh() {
+        let before = term_frequencies();
+        assert!(Arc::ptr_eq(&before, &term_frequencies()));
+        crate::data::data_changed();
+        let after = term_frequencies();
+        assert!(!Arc::ptr_eq(&before, &after));
+        assert_eq!(after.fields.given_name.total(), before.fields.given_name.total());
+    }
+
+}
@@ -0,0 +1,370 @@
+// Welsh-aware name normalisation and comparison.
+//
+// Welsh names differ from English names in ways that plain edit distance
+// does not understand:
+//
+// - Digraphs: the Welsh alphabet has letters written with two characters:
+//   ch, dd, ff, ng, ll, ph, rh, th. Edit distance should count each as one.
+//
+// - Initial consonant mutations: the first consonant of a word changes after
+//   certain words, so "Gwyn" can be written "Wyn", and "Bryn" can be "Fryn".
+//   Many English names also differ only in their first letter, such as "Tom"
+//   and "Dom", so a mutation counts only after a particle such as "ap" or
+//   "ferch", or when the unmutated word is a known Welsh name.
+//
+// - Patronymic particles: "ap" (son of) is "ab" before a vowel, and "ferch"
+//   (daughter of) is also written "verch". Some particles fused with the name
+//   into anglicised family names, such as "ap Hywel" into "Powell".
+//
+// - Anglicisations: many names have anglicised spellings, such as
+//   "Llywelyn", "Llewelyn", and "Llewellyn". An English name for a Welsh
+//   name, such as "Evan" for "Ifan", is a different name, not a spelling.
+//
+// The comparator [WelshName] takes the greater of the digraph-aware string
+// similarity, and a fixed credit when the names are Welsh variants.
+
+use crate::services::comparators::StringComparator;
+use crate::services::similarity::similarity_of_strings;
+
+/// The similarity of two names that normalise to the same name,
+/// such as anglicisations and particle variants.
+pub const WELSH_VARIANT: f64 = 0.95;
+
+/// The similarity of two names that differ only by initial consonant mutations.
+pub const WELSH_MUTATION: f64 = 0.9;
+
+/// The Welsh digraphs, which are each one letter of the Welsh alphabet.
+pub const DIGRAPHS: [&str; 8] = ["ch", "dd", "ff", "ng", "ll", "ph", "rh", "th"];
+
+// Each digraph maps to a character in the Unicode private use area:
+// lowercase digraphs from U+E000, and capitalised digraphs from U+E010.
+const DIGRAPH_LOWER: u32 = 0xE000;
+const DIGRAPH_UPPER: u32 = 0xE010;
+
+// Anglicised and variant spellings of one name, each with its normalised Welsh form.
+const VARIANTS: &[(&str, &str)] = &[
+    ("llewelyn", "llywelyn"),
+    ("llewellyn", "llywelyn"),
+    ("llewellin", "llywelyn"),
+    ("llewelin", "llywelyn"),
+    ("llywelin", "llywelyn"),
+    ("lewelyn", "llywelyn"),
+    ("lewellyn", "llywelyn"),
+    ("llewhellin", "llywelyn"),
+    ("gruffydd", "gruffudd"),
+    ("gruffith", "gruffudd"),
+    ("rees", "rhys"),
+    ("reece", "rhys"),
+    ("reese", "rhys"),
+    ("rhees", "rhys"),
+    ("howel", "hywel"),
+    ("howell", "hywel"),
+    ("hywell", "hywel"),
+    ("dafyd", "dafydd"),
+    ("dafid", "dafydd"),
+    ("davydd", "dafydd"),
+    ("ieuan", "ifan"),
+    ("iefan", "ifan"),
+    ("ievan", "ifan"),
+    ("owein", "owain"),
+    ("owyn", "owain"),
+    ("meredydd", "maredudd"),
+    ("meredudd", "maredudd"),
+    ("cadwallader", "cadwaladr"),
+    ("lloyd", "llwyd"),
+    ("loyd", "llwyd"),
+    ("gwynn", "gwyn"),
+    ("gwynne", "gwyn"),
+    ("wynn", "wyn"),
+    ("wynne", "wyn"),
+    ("tudor", "tudur"),
+    ("tewdwr", "tudur"),
+    ("eynon", "einion"),
+    ("einon", "einion"),
+    ("vychan", "fychan"),
+    ("gwillym", "gwilym"),
+    ("rhisiard", "rhisiart"),
+    ("ab", "ap"),
+    ("verch", "ferch"),
+    ("vch", "ferch"),
+];
+
+// Family names fused from a particle and a name, each with its normalised Welsh form.
+const FUSED: &[(&str, &str)] = &[
+    ("powell", "ap hywel"),
+    ("powel", "ap hywel"),
+    ("price", "ap rhys"),
+    ("prys", "ap rhys"),
+    ("preece", "ap rhys"),
+    ("prees", "ap rhys"),
+    ("pugh", "ap huw"),
+    ("pritchard", "ap rhisiart"),
+    ("prichard", "ap rhisiart"),
+    ("parry", "ap harri"),
+    ("probert", "ap robert"),
+    ("bowen", "ap owain"),
+    ("bevan", "ap ifan"),
+    ("beavan", "ap ifan"),
+    ("beynon", "ap einion"),
+    ("broderick", "ap rhydderch"),
+];
+
+// Words after which the next word mutates, in normalised form.
+const TRIGGERS: &[&str] = &["ap", "ferch", "fab"];
+
+// Welsh names, in normalised form, whose mutated forms count as the same name
+// wherever they occur, such as "Wyn" for "Gwyn".
+const NAMES: &[&str] = &[
+    "bethan", "bryn", "cadwaladr", "caradog", "catrin", "cerys", "dafydd",
+    "dewi", "dylan", "einion", "emrys", "gareth", "glyn", "gruffudd", "gwen",
+    "gwenllian", "gwilym", "gwyn", "harri", "huw", "hywel", "idris", "ifan",
+    "iorwerth", "llinos", "llwyd", "llywelyn", "maredudd", "meirion", "morgan",
+    "owain", "rhisiart", "rhodri", "rhys", "tegwen", "tomos", "tudur",
+];
+
+// Initial consonant mutations, as (mutated, radical) prefixes:
+// soft, nasal, then aspirate. The soft mutation of "g" drops it.
+const MUTATIONS: &[(&str, &str)] = &[
+    ("f", "b"),
+    ("f", "m"),
+    ("g", "c"),
+    ("b", "p"),
+    ("d", "t"),
+    ("dd", "d"),
+    ("l", "ll"),
+    ("r", "rh"),
+    ("m", "b"),
+    ("ngh", "c"),
+    ("n", "d"),
+    ("ng", "g"),
+    ("mh", "p"),
+    ("nh", "t"),
+    ("ch", "c"),
+    ("ph", "p"),
+    ("th", "t"),
+];
+
+/// Replace each Welsh digraph with one character, so that edit distance
+/// counts it as one letter.
+///
+/// Case is preserved: "ll" and "Ll" map to different characters, and
+/// "LL" maps to the same character as "Ll".
+///
+pub fn digraphs_as_letters(name: &str) -> String {
+    let chars: Vec<char> = name.chars().collect();
+    let mut s = String::new();
+    let mut i = 0;
+    while i < chars.len() {
+        if i + 1 < chars.len() {
+            let pair: String = chars[i..i + 2].iter().collect::<String>().to_lowercase();
+            if let Some(k) = DIGRAPHS.iter().position(|&d| d == pair) {
+                let base = if chars[i].is_uppercase() { DIGRAPH_UPPER } else { DIGRAPH_LOWER };
+                s.push(char::from_u32(base + k as u32).unwrap());
+                i += 2;
+                continue;
+            }
+        }
+        s.push(chars[i]);
+        i += 1;
+    }
+    s
+}
+
+/// Normalise a Welsh name for comparing variants.
+///
+/// - Lowercase, without accents such as the circumflex in "Siân".
+///
+/// - Words are separated by single spaces; hyphens and apostrophes separate words.
+///
+/// - Particles use one form: "ab" is "ap", and "verch" is "ferch".
+///
+/// - Anglicised and variant spellings use the Welsh form, such as "Llewellyn"
+///   as "llywelyn", and fused family names are split, such as "Powell" as
+///   "ap hywel".
+///
+pub fn normalise(name: &str) -> String {
+    let folded: String = name.to_lowercase()
+        .chars()
+        .map(|c| match c {
+            'â' | 'à' | 'á' | 'ä' => 'a',
+            'ê' | 'è' | 'é' | 'ë' => 'e',
+            'î' | 'ì' | 'í' | 'ï' => 'i',
+            'ô' | 'ò' | 'ó' | 'ö' => 'o',
+            'û' | 'ù' | 'ú' | 'ü' => 'u',
+            'ŵ' | 'ẁ' | 'ẃ' | 'ẅ' => 'w',
+            'ŷ' | 'ỳ' | 'ý' | 'ÿ' => 'y',
+            '-' | '\'' | '’' => ' ',
+            c => c,
+        })
+        .collect();
+    folded.split_whitespace()
+        .map(|word| {
+            FUSED.iter().chain(VARIANTS.iter())
+                .find(|(variant, _)| *variant == word)
+                .map_or(word, |(_, welsh)| welsh)
+        })
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
+
+/// Get the possible radical (unmutated) forms of a normalised word,
+/// including the word itself.
+pub fn radicals(word: &str) -> Vec<String> {
+    let mut forms = vec![word.to_string()];
+    for &(mutated, radical) in MUTATIONS {
+        if let Some(rest) = word.strip_prefix(mutated) {
+            forms.push(format!("{}{}", radical, rest));
+        }
+    }
+    // The soft mutation of "g" drops it, as in "Gwyn" to "Wyn".
+    forms.push(format!("g{}", word));
+    forms
+}
+
+/// True when two normalised names have the same words, except that
+/// words may differ by initial consonant mutations.
+///
+/// A word differs by a mutation when it is a mutated form of the other word,
+/// and either both words follow the same particle, such as "ferch", or the
+/// other word is a known Welsh name. So "Wyn" and "Gwyn" are equivalent, but
+/// "Dom" and "Tom" are not.
+///
+pub fn mutation_equivalent(a: &str, b: &str) -> bool {
+    let a: Vec<&str> = a.split(' ').collect();
+    let b: Vec<&str> = b.split(' ').collect();
+    a.len() == b.len() && (0..a.len()).all(|i| {
+        let after_trigger = i > 0 && a[i - 1] == b[i - 1] && TRIGGERS.contains(&a[i - 1]);
+        a[i] == b[i] || is_mutation_of(a[i], b[i], after_trigger) || is_mutation_of(b[i], a[i], after_trigger)
+    })
+}
+
+// True when the word is a mutated form of the radical, where the radical
+// follows a trigger, or is a known Welsh name.
+fn is_mutation_of(word: &str, radical: &str, after_trigger: bool) -> bool {
+    (after_trigger || NAMES.contains(&radical)) && radicals(word).iter().any(|form| form == radical)
+}
+
+/// Calculate the similarity of two names, using Welsh rules.
+///
+/// - If either name is blank, then return 0.0 meaning no similarity.
+///
+/// - If the names are equal, then return 1.0 meaning identical similarity.
+///
+/// - Otherwise, return the greater of:
+///
+///   - The [similarity_of_strings] of the names, with digraphs as letters.
+///
+///   - [WELSH_VARIANT] if the names normalise to the same name.
+///
+///   - [WELSH_MUTATION] if the normalised names differ only by mutations.
+///
+/// A name without digraphs or Welsh variants has the same similarity
+/// as [similarity_of_strings].
+///
+pub fn similarity_of_welsh_names(input: (&str, &str)) -> f64 {
+    let (a, b) = input;
+    if a.is_empty() || b.is_empty() {
+        return 0.0;
+    }
+    if a == b {
+        return 1.0;
+    }
+    let value = similarity_of_strings((&digraphs_as_letters(a), &digraphs_as_letters(b)));
+    let (a, b) = (normalise(a), normalise(b));
+    if a.is_empty() || b.is_empty() {
+        value
+    }
+    else if a == b {
+        value.max(WELSH_VARIANT)
+    }
+    else if mutation_equivalent(&a, &b) {
+        value.max(WELSH_MUTATION)
+    }
+    else {
+        value
+    }
+}
+
+/// Welsh name comparator; see [similarity_of_welsh_names].
+pub struct WelshName;
+
+impl StringComparator for WelshName {
+    fn compare(&self, a: &str, b: &str) -> f64 {
+        similarity_of_welsh_names((a, b))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_digraphs_as_letters() {
+        assert_eq!(digraphs_as_letters("Llanelli").chars().count(), 6);
+        assert_eq!(digraphs_as_letters("Dafydd").chars().count(), 5);
+        assert_eq!(digraphs_as_letters("LL"), digraphs_as_letters("Ll"));
+        assert_ne!(digraphs_as_letters("ll"), digraphs_as_letters("Ll"));
+        assert_eq!(digraphs_as_letters("Alice"), "Alice");
+    }
+
+    #[test]
+    fn test_digraph_edit_distance() {
+        // One letter differs, so the distance is one, not two.
+        let a = digraphs_as_letters("Llan");
+        let b = digraphs_as_letters("Ddan");
+        assert_eq!(strsim::damerau_levenshtein(&a, &b), 1);
+    }
+
+    #[test]
+    fn test_normalise() {
+        assert_eq!(normalise(" Siân  ap  Llewellyn "), "sian ap llywelyn");
+        assert_eq!(normalise("Llewelyn"), "llywelyn");
+        assert_eq!(normalise("Llywelyn"), "llywelyn");
+        assert_eq!(normalise("ab Owain"), "ap owain");
+        assert_eq!(normalise("Bowen"), "ap owain");
+        assert_eq!(normalise("Powell"), normalise("ab Howell"));
+        assert_eq!(normalise("Nest verch Rhys"), "nest ferch rhys");
+        assert_eq!(normalise("Jones-Powell"), "jones ap hywel");
+    }
+
+    #[test]
+    fn test_mutation_equivalent() {
+        assert!(mutation_equivalent("gwyn", "wyn"));
+        assert!(mutation_equivalent("wyn", "gwyn"));
+        assert!(mutation_equivalent("bryn", "fryn"));
+        assert!(mutation_equivalent("tomos", "nhomos"));
+        assert!(mutation_equivalent("cerys", "gerys"));
+        assert!(mutation_equivalent("ferch gwilym", "ferch wilym"));
+        assert!(!mutation_equivalent("bryn", "glyn"));
+        assert!(!mutation_equivalent("gwyn", "gwyn jones"));
+        assert!(mutation_equivalent("ap bedr", "ap pedr"));
+        assert!(!mutation_equivalent("bedr", "pedr"));
+        for (a, b) in [("tom", "dom"), ("nick", "dick"), ("gary", "cary"), ("fay", "may"), ("tina", "dina"), ("ben", "pen"), ("dan", "tan"), ("ray", "gray"), ("bill", "mill")] {
+            assert!(!mutation_equivalent(a, b), "{} {}", a, b);
+            assert!(!mutation_equivalent(b, a), "{} {}", b, a);
+        }
+    }
+
+    #[test]
+    fn test_similarity_of_welsh_names() {
+        assert_eq!(similarity_of_welsh_names(("", "Gwyn")), 0.0);
+        assert_eq!(similarity_of_welsh_names(("Gwyn", "Gwyn")), 1.0);
+        assert_eq!(similarity_of_welsh_names(("Llewelyn", "Llywelyn")), WELSH_VARIANT);
+        assert_eq!(similarity_of_welsh_names(("Llewellyn", "Llywelyn")), WELSH_VARIANT);
+        assert_eq!(similarity_of_welsh_names(("ab Rhys", "ap Rhys")), WELSH_VARIANT);
+        assert_eq!(similarity_of_welsh_names(("Nest ferch Rhys", "Nest verch Rhys")), WELSH_VARIANT);
+        assert_eq!(similarity_of_welsh_names(("Gwyn", "Wyn")), WELSH_MUTATION);
+        assert_eq!(similarity_of_welsh_names(("Bryn", "Fryn")), WELSH_MUTATION);
+        assert_eq!(similarity_of_welsh_names(("Alice", "lAice")), similarity_of_strings(("Alice", "lAice")));
+        assert!(similarity_of_welsh_names(("Gwyn", "Jones")) < 0.5);
+        for (a, b) in [("Tom", "Dom"), ("Nick", "Dick"), ("Gary", "Cary"), ("Fay", "May"), ("Tina", "Dina"), ("Ben", "Pen"), ("Dan", "Tan"), ("Ray", "Gray"), ("Bill", "Mill")] {
+            assert_eq!(similarity_of_welsh_names((a, b)), similarity_of_strings((&digraphs_as_letters(a), &digraphs_as_letters(b))), "{} {}", a, b);
+        }
+        assert_eq!(similarity_of_welsh_names(("Dafydd", "Dafyd")), WELSH_VARIANT);
+        // English names for Welsh names are different names.
+        for (a, b) in [("Rice", "Rhys"), ("Evan", "Ifan"), ("Owen", "Owain"), ("Hugh", "Huw"), ("Harry", "Harri"), ("Richard", "Rhisiart"), ("Floyd", "Llwyd")] {
+            assert!(similarity_of_welsh_names((a, b)) < WELSH_VARIANT, "{} {}", a, b);
+        }
+    }
+
+}